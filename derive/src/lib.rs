@@ -0,0 +1,522 @@
+//! Derive macros for `among_us::net::binary`.
+//!
+//! `#[derive(Encode)]` and `#[derive(Decode)]` work on structs and enums.
+//! Struct fields are encoded one after the other, in the order they are
+//! declared. Enums are encoded as a tag followed by the fields of the variant.
+//!
+//! `#[derive(BorrowDecode)]` is for types that borrow from what they are
+//! decoded from, like `&'de str`. The first lifetime parameter of the type is
+//! the one that is borrowed for. Types that derive `Decode` can already be
+//! borrow decoded, so only derive it on types that actually borrow.
+//!
+//! # Attributes
+//! Everything is configured through `#[binary(...)]` attributes.
+//!
+//! On enums:
+//! * `#[binary(tag = u16)]` sets the integer type of the tag. This is `u8`
+//!   by default.
+//!
+//! On enum variants:
+//! * `#[binary(tag = 4)]` sets the tag of the variant. Like discriminants,
+//!   variants without one take the tag after the previous variant. No two
+//!   variants can have the same tag, and every tag has to fit in the tag
+//!   type.
+//!
+//! ```compile_fail
+//! # use among_us::net::binary::encode::Encode;
+//! #[derive(Encode)]
+//! enum Shape {
+//!     #[binary(tag = 1)]
+//!     Circle,
+//!     #[binary(tag = 1)]
+//!     Square,
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! # use among_us::net::binary::encode::Encode;
+//! #[derive(Encode)]
+//! enum Shape {
+//!     #[binary(tag = 255)]
+//!     Circle,
+//!     Square,
+//! }
+//! ```
+//!
+//! On fields:
+//! * `#[binary(len = u8)]` encodes the length of a sequence with a different
+//!   integer type than it normally would.
+//! * `#[binary(skip)]` does not encode the field at all, and decodes it with
+//!   [`Default`].
+
+extern crate proc_macro;
+
+use std::collections::BTreeSet;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, parse_quote, Attribute, Data, DeriveInput, Error, Fields, GenericParam,
+    Generics, Ident, Lifetime, LifetimeParam, LitInt, Type,
+};
+
+/// Derive `among_us::net::binary::encode::Encode`.
+#[proc_macro_derive(Encode, attributes(binary))]
+pub fn derive_encode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_encode(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Derive `among_us::net::binary::decode::Decode`.
+#[proc_macro_derive(Decode, attributes(binary))]
+pub fn derive_decode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_decode(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Derive `among_us::net::binary::decode::BorrowDecode`.
+#[proc_macro_derive(BorrowDecode, attributes(binary))]
+pub fn derive_borrow_decode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_borrow_decode(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// The options on an enum.
+struct ContainerOptions {
+    tag: Type,
+}
+
+impl ContainerOptions {
+    fn parse(attrs: &[Attribute]) -> Result<ContainerOptions, Error> {
+        let mut tag = parse_quote!(u8);
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("binary")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    tag = meta.value()?.parse()?;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown container attribute"))
+                }
+            })?;
+        }
+
+        Ok(ContainerOptions { tag })
+    }
+}
+
+/// The options on an enum variant.
+struct VariantOptions {
+    tag: Option<u64>,
+}
+
+impl VariantOptions {
+    fn parse(attrs: &[Attribute]) -> Result<VariantOptions, Error> {
+        let mut tag = None;
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("binary")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("tag") {
+                    tag = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown variant attribute"))
+                }
+            })?;
+        }
+
+        Ok(VariantOptions { tag })
+    }
+}
+
+/// The options on a field.
+struct FieldOptions {
+    len: Option<Type>,
+    skip: bool,
+}
+
+impl FieldOptions {
+    fn parse(attrs: &[Attribute]) -> Result<FieldOptions, Error> {
+        let mut options = FieldOptions {
+            len: None,
+            skip: false,
+        };
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("binary")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("len") {
+                    options.len = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown field attribute"))
+                }
+            })?;
+        }
+
+        if options.skip && options.len.is_some() {
+            return Err(Error::new(
+                Span::call_site(),
+                "skipped fields can't have a length prefix",
+            ));
+        }
+
+        Ok(options)
+    }
+}
+
+/// A field, with a name to bind it to.
+struct Field {
+    member: syn::Member,
+    binding: Ident,
+    options: FieldOptions,
+}
+
+fn fields(fields: &Fields) -> Result<Vec<Field>, Error> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let (member, binding) = match &field.ident {
+                Some(ident) => (syn::Member::Named(ident.clone()), format_ident!("__{}", ident)),
+                None => (syn::Member::Unnamed(i.into()), format_ident!("__{}", i)),
+            };
+
+            Ok(Field {
+                member,
+                binding,
+                options: FieldOptions::parse(&field.attrs)?,
+            })
+        })
+        .collect()
+}
+
+/// Find the tag of every variant.
+///
+/// Two variants can't share a tag, since they couldn't be told apart when
+/// decoding, and every tag has to fit in the tag type.
+fn tags(data: &syn::DataEnum, tag_ty: &Type) -> Result<Vec<(u64, VariantOptions)>, Error> {
+    let max = max_tag(tag_ty);
+    let mut next = Some(0);
+    let mut seen = BTreeSet::new();
+
+    data.variants
+        .iter()
+        .map(|variant| {
+            let options = VariantOptions::parse(&variant.attrs)?;
+
+            let tag = match options.tag.or(next) {
+                Some(tag) if tag <= max => tag,
+                _ => {
+                    return Err(Error::new(
+                        variant.ident.span(),
+                        format!(
+                            "the tag of variant `{}` doesn't fit in `{}`",
+                            variant.ident,
+                            quote!(#tag_ty)
+                        ),
+                    ))
+                }
+            };
+
+            next = tag.checked_add(1);
+
+            if !seen.insert(tag) {
+                return Err(Error::new(
+                    variant.ident.span(),
+                    format!(
+                        "variant `{}` has the same tag, {}, as another variant",
+                        variant.ident, tag
+                    ),
+                ));
+            }
+
+            Ok((tag, options))
+        })
+        .collect()
+}
+
+/// The biggest tag that fits in a tag type.
+///
+/// Types that aren't integers are left for the compiler to check.
+fn max_tag(ty: &Type) -> u64 {
+    let ident = match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.get_ident(),
+        _ => None,
+    };
+
+    match ident.map(Ident::to_string).as_deref() {
+        Some("u8") => u8::MAX.into(),
+        Some("u16") => u16::MAX.into(),
+        Some("u32") => u32::MAX.into(),
+        Some("i8") => i8::MAX as u64,
+        Some("i16") => i16::MAX as u64,
+        Some("i32") => i32::MAX as u64,
+        Some("i64") => i64::MAX as u64,
+        _ => u64::MAX,
+    }
+}
+
+/// Add a bound to every type parameter.
+fn bound(generics: &Generics, bound: TokenStream) -> Generics {
+    let mut generics = generics.clone();
+
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+
+    generics
+}
+
+/// Build a pattern that binds every field.
+fn pattern(path: TokenStream, fields: &[Field], style: &Fields) -> TokenStream {
+    let members = fields.iter().map(|field| &field.member);
+    let bindings = fields.iter().map(|field| &field.binding);
+
+    match style {
+        Fields::Named(_) | Fields::Unnamed(_) => quote!(#path { #(#members: #bindings),* }),
+        Fields::Unit => quote!(#path),
+    }
+}
+
+fn encode_fields(fields: &[Field]) -> TokenStream {
+    let binary = quote!(::among_us::net::binary);
+
+    let statements = fields.iter().filter(|field| !field.options.skip).map(|field| {
+        let binding = &field.binding;
+
+        match &field.options.len {
+            Some(len) => quote! {
+                #binary::EncodePrefixed::encode_prefixed::<#len, __W>(#binding, cursor)?;
+            },
+            None => quote! {
+                cursor.encode(#binding)?;
+            },
+        }
+    });
+
+    quote!(#(#statements)*)
+}
+
+/// How fields are decoded.
+struct Decoder {
+    /// The method on the cursor that decodes a field.
+    method: TokenStream,
+    /// The type the cursor is reading from.
+    inner: TokenStream,
+}
+
+fn decode_fields(decoder: &Decoder, path: TokenStream, fields: &[Field], style: &Fields) -> TokenStream {
+    let binary = quote!(::among_us::net::binary);
+    let Decoder { method, inner } = decoder;
+
+    let values = fields.iter().map(|field| {
+        let member = &field.member;
+
+        if field.options.skip {
+            return quote!(#member: ::std::default::Default::default());
+        }
+
+        match &field.options.len {
+            Some(len) => quote! {
+                #member: #binary::DecodePrefixed::decode_prefixed::<#len, #inner>(cursor)?
+            },
+            None => quote! {
+                #member: cursor.#method()?
+            },
+        }
+    });
+
+    match style {
+        Fields::Named(_) | Fields::Unnamed(_) => quote!(#path { #(#values),* }),
+        Fields::Unit => quote!(#path),
+    }
+}
+
+/// Build the body of a decode function.
+fn decode_body(decoder: &Decoder, input: &DeriveInput) -> Result<TokenStream, Error> {
+    let binary = quote!(::among_us::net::binary);
+    let name = &input.ident;
+    let method = &decoder.method;
+
+    match &input.data {
+        Data::Struct(data) => {
+            let fields = fields(&data.fields)?;
+            let value = decode_fields(decoder, quote!(#name), &fields, &data.fields);
+
+            Ok(quote!(::std::result::Result::Ok(#value)))
+        }
+        Data::Enum(data) => {
+            let options = ContainerOptions::parse(&input.attrs)?;
+            let tag_ty = &options.tag;
+
+            let arms = data
+                .variants
+                .iter()
+                .zip(tags(data, tag_ty)?)
+                .map(|(variant, (tag, _))| {
+                    let ident = &variant.ident;
+                    let fields = fields(&variant.fields)?;
+                    let value = decode_fields(decoder, quote!(#name::#ident), &fields, &variant.fields);
+                    let tag = LitInt::new(&tag.to_string(), Span::call_site());
+
+                    Ok(quote! {
+                        #tag => ::std::result::Result::Ok(#value),
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            Ok(quote! {
+                let tag: #tag_ty = cursor.#method()?;
+
+                match tag {
+                    #(#arms)*
+                    _ => ::std::result::Result::Err(#binary::decode::Error::invalid_tag()),
+                }
+            })
+        }
+        Data::Union(_) => Err(Error::new(Span::call_site(), "unions can't be decoded")),
+    }
+}
+
+fn expand_encode(input: DeriveInput) -> Result<TokenStream, Error> {
+    let binary = quote!(::among_us::net::binary);
+    let name = &input.ident;
+
+    let body = match &input.data {
+        Data::Struct(data) => {
+            let fields = fields(&data.fields)?;
+            let pattern = pattern(quote!(#name), &fields, &data.fields);
+            let encode = encode_fields(&fields);
+
+            quote! {
+                let #pattern = self;
+                #encode
+            }
+        }
+        Data::Enum(data) => {
+            let options = ContainerOptions::parse(&input.attrs)?;
+            let tag_ty = &options.tag;
+
+            let arms = data
+                .variants
+                .iter()
+                .zip(tags(data, tag_ty)?)
+                .map(|(variant, (tag, _))| {
+                    let ident = &variant.ident;
+                    let fields = fields(&variant.fields)?;
+                    let pattern = pattern(quote!(#name::#ident), &fields, &variant.fields);
+                    let encode = encode_fields(&fields);
+                    let tag = LitInt::new(&tag.to_string(), Span::call_site());
+
+                    Ok(quote! {
+                        #pattern => {
+                            let tag: #tag_ty = #tag;
+                            cursor.encode(&tag)?;
+                            #encode
+                        }
+                    })
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => {
+            return Err(Error::new(Span::call_site(), "unions can't be encoded"));
+        }
+    };
+
+    let generics = bound(&input.generics, quote!(#binary::encode::Encode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #binary::encode::Encode for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn encode<__W>(
+                &self,
+                cursor: &mut #binary::encode::CursorMut<__W>,
+            ) -> ::std::result::Result<(), #binary::encode::Error>
+            where __W: ::std::io::Write {
+                #body
+                ::std::result::Result::Ok(())
+            }
+        }
+    })
+}
+
+fn expand_decode(input: DeriveInput) -> Result<TokenStream, Error> {
+    let binary = quote!(::among_us::net::binary);
+    let name = &input.ident;
+
+    let decoder = Decoder {
+        method: quote!(decode),
+        inner: quote!(__T),
+    };
+    let body = decode_body(&decoder, &input)?;
+
+    let generics = bound(&input.generics, quote!(#binary::decode::Decode));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #binary::decode::Decode for #name #ty_generics #where_clause {
+            fn decode<__T>(
+                cursor: &mut #binary::decode::Cursor<__T>,
+            ) -> ::std::result::Result<Self, #binary::decode::Error>
+            where __T: #binary::decode::Source {
+                #body
+            }
+        }
+    })
+}
+
+fn expand_borrow_decode(input: DeriveInput) -> Result<TokenStream, Error> {
+    let binary = quote!(::among_us::net::binary);
+    let name = &input.ident;
+
+    // borrow for the first lifetime of the type, or a new one if it has none
+    let (lifetime, mut generics) = match input.generics.lifetimes().next() {
+        Some(param) => (param.lifetime.clone(), input.generics.clone()),
+        None => {
+            let lifetime = Lifetime::new("'__de", Span::call_site());
+            let mut generics = input.generics.clone();
+            generics.params.insert(0, GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())));
+
+            (lifetime, generics)
+        }
+    };
+
+    let decoder = Decoder {
+        method: quote!(borrow_decode),
+        inner: quote!(&#lifetime [u8]),
+    };
+    let body = decode_body(&decoder, &input)?;
+
+    generics = bound(&generics, quote!(#binary::decode::BorrowDecode<#lifetime>));
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #binary::decode::BorrowDecode<#lifetime> for #name #ty_generics #where_clause {
+            fn borrow_decode(
+                cursor: &mut #binary::decode::Cursor<&#lifetime [u8]>,
+            ) -> ::std::result::Result<Self, #binary::decode::Error> {
+                #body
+            }
+        }
+    })
+}
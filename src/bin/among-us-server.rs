@@ -0,0 +1,147 @@
+//! A dedicated server, without a client.
+//!
+//! Settings come from a RON config file given with `--config`, and flags
+//! override anything in it. Run with `--help` to see every flag.
+
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::process;
+
+use among_us::map::Map;
+use among_us::math::*;
+use among_us::server::{Config, Server};
+
+const USAGE: &str = "\
+usage: among-us-server [options]
+
+options:
+    --config <file>       load settings from a RON file
+    --map <file>          the RON map to play on
+    --port <port>         the UDP port to listen on
+    --max-lobbies <n>     the most lobbies that can be open at once
+    --tick-rate <n>       how many times a second games are stepped
+    --code-length <n>     how many letters room codes have, 4 or 6
+    --help                print this message";
+
+/// The command line arguments.
+#[derive(Default)]
+struct Args {
+    config: Option<String>,
+    map: Option<String>,
+    port: Option<u16>,
+    max_lobbies: Option<usize>,
+    tick_rate: Option<u32>,
+    code_length: Option<usize>,
+}
+
+impl Args {
+    /// Parse the arguments, returning `None` if help was asked for.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+        let mut parsed = Args::default();
+
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                return Ok(None);
+            }
+
+            let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            let invalid = || format!("invalid value for {}: {}", arg, value);
+
+            match arg.as_str() {
+                "--config" => parsed.config = Some(value),
+                "--map" => parsed.map = Some(value),
+                "--port" => parsed.port = Some(value.parse().map_err(|_| invalid())?),
+                "--max-lobbies" => parsed.max_lobbies = Some(value.parse().map_err(|_| invalid())?),
+                "--tick-rate" => match value.parse() {
+                    Ok(0) | Err(_) => return Err(invalid()),
+                    Ok(tick_rate) => parsed.tick_rate = Some(tick_rate),
+                },
+                "--code-length" => match value.parse() {
+                    Ok(code_length @ (4 | 6)) => parsed.code_length = Some(code_length),
+                    _ => return Err(invalid()),
+                },
+                _ => return Err(format!("unknown option {}", arg)),
+            }
+        }
+
+        Ok(Some(parsed))
+    }
+
+    /// Build the configuration of the server.
+    fn config(&self) -> Result<Config, Box<dyn Error>> {
+        let mut config = match &self.config {
+            Some(path) => {
+                let source = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+                ron::from_str(&source).map_err(|e| format!("invalid config file {}: {}", path, e))?
+            }
+            None => Config::new(),
+        };
+
+        if let Some(port) = self.port {
+            config.set_port(port);
+        }
+
+        if let Some(max_lobbies) = self.max_lobbies {
+            config.set_max_lobbies(max_lobbies);
+        }
+
+        if let Some(tick_rate) = self.tick_rate {
+            config.set_tick_rate(tick_rate);
+        }
+
+        if let Some(code_length) = self.code_length {
+            config.set_code_length(code_length);
+        }
+
+        // flags can fix what was wrong with the file, so everything is
+        // checked at the end
+        config.validate().map_err(|e| format!("invalid config: {}", e))?;
+
+        Ok(config)
+    }
+
+    /// Load the map, or make an empty one.
+    fn map(&self) -> Result<Map, Box<dyn Error>> {
+        match &self.map {
+            Some(path) => {
+                let source = fs::read_to_string(path).map_err(|e| format!("can't read {}: {}", path, e))?;
+                Ok(Map::from_ron(&source)?)
+            }
+            None => Ok(Map::new("empty", Vector2::new(0.0, 0.0))),
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let args = match Args::parse(env::args().skip(1))? {
+        Some(args) => args,
+        None => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+    };
+
+    let config = args.config()?;
+    let map = args.map()?;
+
+    let mut server = Server::bind(config, map)?;
+
+    println!(
+        "listening on {}, with up to {} lobbies at {} ticks a second",
+        server.endpoint().local_addr()?,
+        server.config().max_lobbies(),
+        server.config().tick_rate()
+    );
+
+    server.run(|e| eprintln!("{}", e))?;
+
+    Ok(())
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
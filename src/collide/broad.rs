@@ -0,0 +1,344 @@
+//! Broad-phase collision.
+//!
+//! Colliding every shape against every other shape gets slow fast. The
+//! broad phase cheaply narrows a query down to the few shapes that are close
+//! enough to possibly touch it, which can then be checked properly with
+//! [`Geometry::collide()`].
+//!
+//! Shapes are stored in a uniform [`Grid`] by their bounding box. A shape is
+//! put in every cell its bounding box overlaps, so the cell size should be
+//! around the size of a typical shape.
+
+use std::collections::HashMap;
+
+use crate::collide::{Circle, Geometry};
+use crate::math::*;
+
+/// The most cells a bounding box is put in.
+///
+/// Boxes that would span more are kept in a list that every query checks
+/// instead, so one huge shape can't fill the grid.
+const MAX_CELLS: FLOAT = 1024.0;
+
+/// The cells a bounding box covers, from the bottom-left to the top-right.
+type CellRange = ((i64, i64), (i64, i64));
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    min: Vector2,
+    max: Vector2,
+}
+
+impl Aabb {
+    /// Create a new bounding box from two corners.
+    pub fn new(a: Vector2, b: Vector2) -> Aabb {
+        Aabb {
+            min: Vector2::new(a.x.min(b.x), a.y.min(b.y)),
+            max: Vector2::new(a.x.max(b.x), a.y.max(b.y)),
+        }
+    }
+
+    /// Get the bounding box of a shape.
+    pub fn from_geometry<T>(shape: &T) -> Aabb
+    where
+        T: Geometry,
+    {
+        let x = shape.project(Vector2::unit_x());
+        let y = shape.project(Vector2::unit_y());
+
+        Aabb {
+            min: Vector2::new(x.start(), y.start()),
+            max: Vector2::new(x.end(), y.end()),
+        }
+    }
+
+    /// The bottom-left corner.
+    pub fn min(&self) -> Vector2 {
+        self.min
+    }
+
+    /// The top-right corner.
+    pub fn max(&self) -> Vector2 {
+        self.max
+    }
+
+    /// Check if there is an overlap between two bounding boxes.
+    ///
+    /// Unlike [`Projection::overlap()`](super::Projection::overlap), boxes
+    /// that are only touching overlap.
+    pub fn overlap(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    /// Check if every corner of the bounding box is a finite number.
+    pub fn is_finite(&self) -> bool {
+        self.min.x.is_finite() && self.min.y.is_finite() && self.max.x.is_finite() && self.max.y.is_finite()
+    }
+
+    /// Grow the bounding box by `amount` in every direction.
+    pub fn grow(&self, amount: FLOAT) -> Aabb {
+        let amount = Vector2::new(amount, amount);
+
+        Aabb {
+            min: self.min - amount,
+            max: self.max + amount,
+        }
+    }
+}
+
+/// A handle to a shape in a [`Grid`].
+///
+/// Handles of removed shapes are reused by later insertions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Handle(usize);
+
+struct Entry<T> {
+    aabb: Aabb,
+    value: T,
+}
+
+/// A uniform grid of bounding boxes.
+///
+/// Every shape carries a value `T` with it, which is usually an index into
+/// wherever the real shapes are stored.
+pub struct Grid<T> {
+    cell_size: FLOAT,
+    cells: HashMap<(i64, i64), Vec<Handle>>,
+    /// The shapes that are too big, or not finite, to put in cells.
+    large: Vec<Handle>,
+    entries: Vec<Option<Entry<T>>>,
+    free: Vec<usize>,
+}
+
+impl<T> Grid<T> {
+    /// Create a new, empty grid.
+    ///
+    /// # Panics
+    /// Panics if `cell_size` is not positive.
+    pub fn new(cell_size: FLOAT) -> Grid<T> {
+        assert!(cell_size > 0.0, "grid cells must have a positive size!");
+
+        Grid {
+            cell_size,
+            cells: HashMap::new(),
+            large: Vec::new(),
+            entries: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// The size of a single cell.
+    pub fn cell_size(&self) -> FLOAT {
+        self.cell_size
+    }
+
+    /// Insert a shape into the grid.
+    pub fn insert<G>(&mut self, shape: &G, value: T) -> Handle
+    where
+        G: Geometry,
+    {
+        self.insert_aabb(Aabb::from_geometry(shape), value)
+    }
+
+    /// Insert a bounding box into the grid.
+    ///
+    /// A bounding box that isn't finite is kept, but is never found by a
+    /// query.
+    pub fn insert_aabb(&mut self, aabb: Aabb, value: T) -> Handle {
+        let entry = Some(Entry { aabb, value });
+
+        let handle = match self.free.pop() {
+            Some(index) => {
+                self.entries[index] = entry;
+                Handle(index)
+            }
+            None => {
+                self.entries.push(entry);
+                Handle(self.entries.len() - 1)
+            }
+        };
+
+        self.link(handle, aabb);
+
+        handle
+    }
+
+    /// Remove a shape from the grid, returning its value.
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let entry = self.entries.get_mut(handle.0)?.take()?;
+
+        self.unlink(handle, entry.aabb);
+        self.free.push(handle.0);
+
+        Some(entry.value)
+    }
+
+    /// Move a shape to a new position.
+    ///
+    /// Returns `false` if there is no shape with this handle.
+    pub fn update<G>(&mut self, handle: Handle, shape: &G) -> bool
+    where
+        G: Geometry,
+    {
+        self.update_aabb(handle, Aabb::from_geometry(shape))
+    }
+
+    /// Move a bounding box to a new position.
+    ///
+    /// Returns `false` if there is no shape with this handle.
+    pub fn update_aabb(&mut self, handle: Handle, aabb: Aabb) -> bool {
+        let old = match self.entries.get_mut(handle.0) {
+            Some(Some(entry)) => std::mem::replace(&mut entry.aabb, aabb),
+            _ => return false,
+        };
+
+        if self.cell_range(&old) != self.cell_range(&aabb) || old.is_finite() != aabb.is_finite() {
+            self.unlink(handle, old);
+            self.link(handle, aabb);
+        }
+
+        true
+    }
+
+    /// Get the value of a shape.
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        self.entry(handle).map(|entry| &entry.value)
+    }
+
+    /// Get the value of a shape mutably.
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        match self.entries.get_mut(handle.0) {
+            Some(Some(entry)) => Some(&mut entry.value),
+            _ => None,
+        }
+    }
+
+    /// Get the bounding box of a shape.
+    pub fn aabb(&self, handle: Handle) -> Option<Aabb> {
+        self.entry(handle).map(|entry| entry.aabb)
+    }
+
+    /// Find every shape whose bounding box overlaps `aabb`.
+    ///
+    /// The handles are sorted, so the result does not depend on how the grid
+    /// is laid out in memory. A query that isn't finite finds nothing.
+    pub fn query(&self, aabb: &Aabb) -> Vec<Handle> {
+        let overlaps = |handle: &&Handle| {
+            self.entry(**handle)
+                .map(|entry| entry.aabb.is_finite() && entry.aabb.overlap(aabb))
+                .unwrap_or(false)
+        };
+
+        let ((x0, y0), (x1, y1)) = match self.cell_range(aabb) {
+            Some(range) => range,
+            None if !aabb.is_finite() => return Vec::new(),
+            // a query that covers too many cells checks every shape instead
+            None => {
+                let handles = (0..self.entries.len()).map(Handle);
+                return handles.filter(|handle| overlaps(&handle)).collect();
+            }
+        };
+
+        let mut found: Vec<Handle> = self.large.iter().filter(overlaps).copied().collect();
+
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                let cell = match self.cells.get(&(x, y)) {
+                    Some(cell) => cell,
+                    None => continue,
+                };
+
+                found.extend(cell.iter().filter(overlaps));
+            }
+        }
+
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    /// Find every shape that might touch a circle.
+    pub fn query_circle(&self, circle: &Circle) -> Vec<Handle> {
+        self.query(&Aabb::from_geometry(circle))
+    }
+
+    /// The number of shapes in the grid.
+    pub fn len(&self) -> usize {
+        self.entries.len() - self.free.len()
+    }
+
+    /// Check if the grid has no shapes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterate over every shape in the grid.
+    pub fn iter(&self) -> impl Iterator<Item = (Handle, &T)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(i, entry)| entry.as_ref().map(|entry| (Handle(i), &entry.value)))
+    }
+
+    fn entry(&self, handle: Handle) -> Option<&Entry<T>> {
+        self.entries.get(handle.0).and_then(|entry| entry.as_ref())
+    }
+
+    fn cell(&self, point: Vector2) -> (i64, i64) {
+        (
+            (point.x / self.cell_size).floor() as i64,
+            (point.y / self.cell_size).floor() as i64,
+        )
+    }
+
+    /// Find the cells a bounding box covers, or `None` if it isn't finite or
+    /// covers more than [`MAX_CELLS`].
+    fn cell_range(&self, aabb: &Aabb) -> Option<CellRange> {
+        let min = (aabb.min / self.cell_size).map(FLOAT::floor);
+        let max = (aabb.max / self.cell_size).map(FLOAT::floor);
+        let cells = (max.x - min.x + 1.0) * (max.y - min.y + 1.0);
+
+        if !aabb.is_finite() || cells > MAX_CELLS {
+            return None;
+        }
+
+        Some((self.cell(aabb.min), self.cell(aabb.max)))
+    }
+
+    fn link(&mut self, handle: Handle, aabb: Aabb) {
+        let ((x0, y0), (x1, y1)) = match self.cell_range(&aabb) {
+            Some(range) => range,
+            None if aabb.is_finite() => return self.large.push(handle),
+            None => return,
+        };
+
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                self.cells.entry((x, y)).or_default().push(handle);
+            }
+        }
+    }
+
+    fn unlink(&mut self, handle: Handle, aabb: Aabb) {
+        let ((x0, y0), (x1, y1)) = match self.cell_range(&aabb) {
+            Some(range) => range,
+            None => return self.large.retain(|h| *h != handle),
+        };
+
+        for x in x0..=x1 {
+            for y in y0..=y1 {
+                if let Some(cell) = self.cells.get_mut(&(x, y)) {
+                    cell.retain(|h| *h != handle);
+
+                    if cell.is_empty() {
+                        self.cells.remove(&(x, y));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,531 @@
+//! Convex decomposition.
+//!
+//! [`Geometry`](super::Geometry) has to be convex, but the rooms on a map
+//! almost never are. This splits any simple polygon, holes and all, into
+//! convex [`Polygon`]s that can be used for collision.
+//!
+//! Holes are first joined to the outline with bridges, which leaves a single
+//! polygon that touches itself along the bridges. That polygon is cut into
+//! triangles by clipping ears off of it, and neighbouring triangles are then
+//! merged back together for as long as they stay convex.
+
+use std::fmt;
+
+use crate::collide::{contains_point, Polygon};
+use crate::math::*;
+
+/// A ring of vertices in a polygon with holes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Ring {
+    /// The outline of the polygon.
+    Outline,
+    /// One of the holes, by index.
+    Hole(usize),
+}
+
+impl fmt::Display for Ring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ring::Outline => write!(f, "outline"),
+            Ring::Hole(i) => write!(f, "hole {}", i),
+        }
+    }
+}
+
+/// An edge of a polygon with holes.
+///
+/// The edge at `index` goes from vertex `index` to the vertex after it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edge {
+    ring: Ring,
+    index: usize,
+}
+
+impl Edge {
+    /// The ring the edge belongs to.
+    pub fn ring(&self) -> Ring {
+        self.ring
+    }
+
+    /// The index of the first vertex of the edge.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl fmt::Display for Edge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "edge {} of the {}", self.index, self.ring)
+    }
+}
+
+/// Two edges that cross each other.
+#[derive(Clone, Copy, Debug)]
+pub struct Intersection {
+    first: Edge,
+    second: Edge,
+    point: Vector2,
+}
+
+impl Intersection {
+    /// The first edge.
+    pub fn first(&self) -> Edge {
+        self.first
+    }
+
+    /// The second edge.
+    pub fn second(&self) -> Edge {
+        self.second
+    }
+
+    /// Where the edges cross.
+    ///
+    /// If the edges overlap along a line, this is one of the points they
+    /// share.
+    pub fn point(&self) -> Vector2 {
+        self.point
+    }
+}
+
+impl fmt::Display for Intersection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} crosses {} at ({}, {})",
+            self.first, self.second, self.point.x, self.point.y
+        )
+    }
+}
+
+/// An error that can occur during decomposition.
+#[derive(Clone, Debug)]
+pub enum Error {
+    /// A ring has less than three vertices, or no area.
+    Degenerate(Ring),
+    /// A ring has a vertex that is infinite or not a number.
+    NotFinite(Ring),
+    /// A hole is not inside of the outline, or is inside of another hole.
+    Misplaced(usize),
+    /// The polygon intersects itself.
+    Intersections(Vec<Intersection>),
+    /// The polygon could not be triangulated.
+    ///
+    /// This should only happen with polygons that are valid, but so thin that
+    /// rounding errors get in the way, either while cutting it up or while
+    /// joining its holes to the outline.
+    Triangulation,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Degenerate(ring) => write!(f, "the {} has no area", ring),
+            Error::NotFinite(ring) => write!(f, "the {} has a vertex that is not a finite number", ring),
+            Error::Misplaced(i) => write!(f, "hole {} is not inside of the outline", i),
+            Error::Intersections(intersections) => {
+                write!(f, "the polygon intersects itself")?;
+
+                for intersection in intersections {
+                    write!(f, "; {}", intersection)?;
+                }
+
+                Ok(())
+            }
+            Error::Triangulation => write!(f, "the polygon could not be triangulated"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Check that a polygon with holes is simple.
+///
+/// Every vertex must be finite, and the outline and holes must all have an
+/// area. No two edges can touch except for neighbouring edges at their shared
+/// vertex, and every hole must be inside of the outline and outside of every
+/// other hole. The rings can be in either winding order.
+pub fn validate(outline: &[Vector2], holes: &[Vec<Vector2>]) -> Result<(), Error> {
+    let rings = std::iter::once((Ring::Outline, outline))
+        .chain(holes.iter().enumerate().map(|(i, hole)| (Ring::Hole(i), &hole[..])))
+        .collect::<Vec<_>>();
+
+    for (ring, vertices) in rings.iter() {
+        if vertices.len() < 3 {
+            return Err(Error::Degenerate(*ring));
+        }
+
+        if !vertices.iter().all(|v| v.x.is_finite() && v.y.is_finite()) {
+            return Err(Error::NotFinite(*ring));
+        }
+    }
+
+    let edges = rings
+        .iter()
+        .flat_map(|(ring, vertices)| {
+            (0..vertices.len()).map(move |index| {
+                let a = vertices[index];
+                let b = vertices[(index + 1) % vertices.len()];
+
+                (Edge { ring: *ring, index }, vertices.len(), a, b)
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut intersections = Vec::new();
+
+    for (i, (first, len, a, b)) in edges.iter().enumerate() {
+        for (second, _, c, d) in edges.iter().skip(i + 1) {
+            // neighbours only share their vertex, unless they fold back
+            let neighbours = first.ring == second.ring
+                && (second.index == (first.index + 1) % len
+                    || first.index == (second.index + 1) % len);
+
+            if neighbours {
+                let (shared, p, q) = if second.index == (first.index + 1) % len {
+                    (*b, *a, *d)
+                } else {
+                    (*a, *b, *c)
+                };
+
+                let (u, v) = (p - shared, q - shared);
+                if u.perp_dot(v) == 0.0 && u.dot(v) > 0.0 {
+                    intersections.push(Intersection {
+                        first: *first,
+                        second: *second,
+                        point: shared,
+                    });
+                }
+
+                continue;
+            }
+
+            if let Some(point) = segment_intersection(*a, *b, *c, *d) {
+                intersections.push(Intersection {
+                    first: *first,
+                    second: *second,
+                    point,
+                });
+            }
+        }
+    }
+
+    if !intersections.is_empty() {
+        return Err(Error::Intersections(intersections));
+    }
+
+    for (ring, vertices) in rings.iter() {
+        if area(vertices) == 0.0 {
+            return Err(Error::Degenerate(*ring));
+        }
+    }
+
+    // nothing crosses, so checking one vertex of every hole is enough
+    for (i, hole) in holes.iter().enumerate() {
+        let inside_outline = contains_point(outline, hole[0]);
+        let inside_hole = holes
+            .iter()
+            .enumerate()
+            .any(|(j, other)| i != j && contains_point(other, hole[0]));
+
+        if !inside_outline || inside_hole {
+            return Err(Error::Misplaced(i));
+        }
+    }
+
+    Ok(())
+}
+
+/// Split a polygon with holes into convex polygons.
+///
+/// The polygon is [`validate`]d first. The resulting polygons are all wound
+/// counter-clockwise.
+pub fn decompose(outline: &[Vector2], holes: &[Vec<Vector2>]) -> Result<Vec<Polygon>, Error> {
+    validate(outline, holes)?;
+
+    let mut vertices = outline.to_vec();
+    if area(&vertices) < 0.0 {
+        vertices.reverse();
+    }
+
+    let mut holes = holes
+        .iter()
+        .map(|hole| {
+            let mut hole = hole.clone();
+            if area(&hole) > 0.0 {
+                hole.reverse();
+            }
+            hole
+        })
+        .collect::<Vec<_>>();
+
+    // bridge the rightmost holes first, so later bridges can't cross them
+    holes.sort_by(|a, b| rightmost(b).1.x.total_cmp(&rightmost(a).1.x));
+
+    for hole in holes {
+        bridge(&mut vertices, &hole).ok_or(Error::Triangulation)?;
+    }
+
+    let triangles = triangulate(vertices).ok_or(Error::Triangulation)?;
+
+    Ok(merge(triangles).into_iter().map(Polygon::from).collect())
+}
+
+/// Get the signed area of a ring.
+///
+/// This is positive for counter-clockwise rings.
+fn area(vertices: &[Vector2]) -> FLOAT {
+    vertices
+        .iter()
+        .zip(vertices.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum::<FLOAT>()
+        / 2.0
+}
+
+/// Get the cross product of `b - a` and `c - a`.
+fn cross(a: Vector2, b: Vector2, c: Vector2) -> FLOAT {
+    (b - a).perp_dot(c - a)
+}
+
+/// Find where the segments `a -> b` and `c -> d` touch.
+fn segment_intersection(a: Vector2, b: Vector2, c: Vector2, d: Vector2) -> Option<Vector2> {
+    let d1 = cross(c, d, a);
+    let d2 = cross(c, d, b);
+    let d3 = cross(a, b, c);
+    let d4 = cross(a, b, d);
+
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        let t = d1 / (d1 - d2);
+        return Some(a + (b - a) * t);
+    }
+
+    // the segments touch at an end point, or overlap along a line
+    let on = |p: Vector2, q: Vector2, r: Vector2| {
+        r.x >= p.x.min(q.x) && r.x <= p.x.max(q.x) && r.y >= p.y.min(q.y) && r.y <= p.y.max(q.y)
+    };
+
+    if d1 == 0.0 && on(c, d, a) {
+        Some(a)
+    } else if d2 == 0.0 && on(c, d, b) {
+        Some(b)
+    } else if d3 == 0.0 && on(a, b, c) {
+        Some(c)
+    } else if d4 == 0.0 && on(a, b, d) {
+        Some(d)
+    } else {
+        None
+    }
+}
+
+/// Get the rightmost vertex of a ring.
+fn rightmost(vertices: &[Vector2]) -> (usize, Vector2) {
+    vertices
+        .iter()
+        .copied()
+        .enumerate()
+        .fold((0, vertices[0]), |best, (i, v)| if v.x > best.1.x { (i, v) } else { best })
+}
+
+/// Join a clockwise hole to a counter-clockwise outline.
+///
+/// A ray is cast right from the rightmost vertex of the hole, and the hole is
+/// joined to the closest outline vertex that can be seen from there. This
+/// fails if rounding errors make the ray miss the outline.
+fn bridge(outline: &mut Vec<Vector2>, hole: &[Vector2]) -> Option<()> {
+    let (start, m) = rightmost(hole);
+    let len = outline.len();
+
+    // find the closest edge hit by the ray
+    let mut hit: Option<(FLOAT, usize)> = None;
+
+    for i in 0..len {
+        let (a, b) = (outline[i], outline[(i + 1) % len]);
+
+        if a.y == b.y || m.y < a.y.min(b.y) || m.y > a.y.max(b.y) {
+            continue;
+        }
+
+        let x = a.x + (m.y - a.y) / (b.y - a.y) * (b.x - a.x);
+
+        if x >= m.x && hit.map(|(best, _)| x < best).unwrap_or(true) {
+            hit = Some((x, i));
+        }
+    }
+
+    let (x, edge) = hit?;
+    let i = Vector2::new(x, m.y);
+    let (a, b) = (edge, (edge + 1) % len);
+
+    let mut target = if outline[a] == i {
+        a
+    } else if outline[b] == i {
+        b
+    } else if outline[a].x > outline[b].x {
+        a
+    } else {
+        b
+    };
+
+    // a reflex vertex in the way blocks the view, so use the closest one
+    // instead
+    if outline[target] != i {
+        let p = outline[target];
+        let mut best: Option<(FLOAT, FLOAT)> = None;
+
+        for r in 0..len {
+            let v = outline[r];
+
+            if r == target || v == p {
+                continue;
+            }
+
+            let prev = outline[(r + len - 1) % len];
+            let next = outline[(r + 1) % len];
+
+            if cross(prev, v, next) >= 0.0 || !in_triangle(m, i, p, v) {
+                continue;
+            }
+
+            let offset = v - m;
+            let angle = offset.y.abs() / offset.x.max(FLOAT::EPSILON);
+            let distance = offset.magnitude2();
+
+            let better = best
+                .map(|(a, d)| angle < a || (angle == a && distance < d))
+                .unwrap_or(true);
+
+            if better {
+                best = Some((angle, distance));
+                target = r;
+            }
+        }
+    }
+
+    let p = outline[target];
+    let mut spliced = Vec::with_capacity(len + hole.len() + 2);
+
+    spliced.extend_from_slice(&outline[..=target]);
+    spliced.extend(hole[start..].iter().chain(hole[..start].iter()));
+    spliced.push(m);
+    spliced.push(p);
+    spliced.extend_from_slice(&outline[target + 1..]);
+
+    *outline = spliced;
+
+    Some(())
+}
+
+/// Check if `p` is inside of or on the edge of the triangle `a, b, c`.
+fn in_triangle(a: Vector2, b: Vector2, c: Vector2, p: Vector2) -> bool {
+    let d1 = cross(a, b, p);
+    let d2 = cross(b, c, p);
+    let d3 = cross(c, a, p);
+
+    let negative = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+    let positive = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+
+    !(negative && positive)
+}
+
+/// Cut a counter-clockwise polygon into triangles by clipping its ears.
+fn triangulate(mut vertices: Vec<Vector2>) -> Option<Vec<Vec<Vector2>>> {
+    let mut triangles = Vec::with_capacity(vertices.len().saturating_sub(2));
+
+    while vertices.len() > 3 {
+        let len = vertices.len();
+
+        let corner = |i: usize| {
+            (vertices[(i + len - 1) % len], vertices[i], vertices[(i + 1) % len])
+        };
+
+        let ear = (0..len).find(|i| {
+            let (prev, v, next) = corner(*i);
+
+            cross(prev, v, next) > 0.0
+                && !vertices.iter().any(|p| {
+                    *p != prev && *p != v && *p != next && in_triangle(prev, v, next, *p)
+                })
+        });
+
+        // if there are no ears left, there might still be vertices in the
+        // middle of a straight line that can be dropped
+        let ear = match ear {
+            Some(i) => (i, true),
+            None => {
+                let i = (0..len).find(|i| {
+                    let (prev, v, next) = corner(*i);
+                    cross(prev, v, next) == 0.0 && (v - prev).dot(next - v) > 0.0
+                })?;
+
+                (i, false)
+            }
+        };
+
+        let (i, emit) = ear;
+
+        if emit {
+            let len = vertices.len();
+            triangles.push(vec![vertices[(i + len - 1) % len], vertices[i], vertices[(i + 1) % len]]);
+        }
+
+        vertices.remove(i);
+    }
+
+    if vertices.len() == 3 && area(&vertices) > 0.0 {
+        triangles.push(vertices);
+    }
+
+    Some(triangles)
+}
+
+/// Merge neighbouring convex polygons for as long as they stay convex.
+fn merge(mut polygons: Vec<Vec<Vector2>>) -> Vec<Vec<Vector2>> {
+    'search: loop {
+        for i in 0..polygons.len() {
+            for j in (i + 1)..polygons.len() {
+                if let Some(merged) = try_merge(&polygons[i], &polygons[j]) {
+                    polygons[i] = merged;
+                    polygons.remove(j);
+                    continue 'search;
+                }
+            }
+        }
+
+        return polygons;
+    }
+}
+
+/// Merge two counter-clockwise polygons along a shared edge, if the result is
+/// convex.
+fn try_merge(first: &[Vector2], second: &[Vector2]) -> Option<Vec<Vector2>> {
+    let (n, m) = (first.len(), second.len());
+
+    // find an edge a -> b in the first polygon that is b -> a in the second
+    let (i, j) = (0..n).find_map(|i| {
+        let (a, b) = (first[i], first[(i + 1) % n]);
+
+        (0..m)
+            .find(|j| second[*j] == b && second[(j + 1) % m] == a)
+            .map(|j| (i, j))
+    })?;
+
+    // walk the first polygon from b around to a, then the second polygon
+    // from just after a around to just before b
+    let mut merged = Vec::with_capacity(n + m - 2);
+
+    merged.extend((0..n).map(|k| first[(i + 1 + k) % n]));
+    merged.extend((0..m - 2).map(|k| second[(j + 2 + k) % m]));
+
+    let len = merged.len();
+    let convex = (0..len).all(|k| {
+        cross(merged[(k + len - 1) % len], merged[k], merged[(k + 1) % len]) >= 0.0
+    });
+
+    if convex {
+        Some(merged)
+    } else {
+        None
+    }
+}
@@ -1,8 +1,16 @@
 use crate::math::*;
+use crate::net::binary::{decode::Decode, encode::Encode};
+
+pub mod broad;
+pub mod decompose;
+pub mod ray;
+pub mod sweep;
+pub mod transform;
 
 /// A projection is a segment on an axis, represented by two numbers.
 ///
 /// This gaurentees that `start` is less than `end`.
+#[derive(Clone, Copy, Debug)]
 pub struct Projection {
     start: FLOAT,
     end: FLOAT,
@@ -51,18 +59,58 @@ impl Projection {
         !(self.start >= other.end || self.end <= other.start)
     }
 
+    /// Get the amount of overlap between two projections.
+    ///
+    /// This is the shortest distance either projection has to be moved along
+    /// the axis so that they no longer overlap. If the projections do not
+    /// overlap, this is zero or negative.
+    pub fn overlap_amount(&self, other: &Projection) -> FLOAT {
+        (self.end - other.start).min(other.end - self.start)
+    }
+
     /// Check if `self` contains another projection.
     pub fn contains(&self, other: &Projection) -> bool {
         self.start <= other.start && self.end >= other.end
     }
 }
 
+/// The minimum translation vector of a collision.
+///
+/// Moving the first shape by [`Penetration::mtv()`] will push it out of the
+/// second shape.
+#[derive(Clone, Copy, Debug)]
+pub struct Penetration {
+    axis: Vector2,
+    depth: FLOAT,
+}
+
+impl Penetration {
+    /// The separating axis.
+    ///
+    /// This is normalized, and points in the direction the first shape should
+    /// be moved in.
+    pub fn axis(&self) -> Vector2 {
+        self.axis
+    }
+
+    /// The penetration depth.
+    pub fn depth(&self) -> FLOAT {
+        self.depth
+    }
+
+    /// The minimum translation vector.
+    pub fn mtv(&self) -> Vector2 {
+        self.axis * self.depth
+    }
+}
+
 /// Geometry is any shape that can collide.
 ///
 /// Geometry in this context MUST BE CONVEX. Concave shapes will mess with the
 /// collision and ruin your life.
 ///
-/// Geometry does not mean that the shape can be translated, rotated or scaled.
+/// Geometry on its own cannot be translated, rotated or scaled. Wrap it in a
+/// [`Transformed`](transform::Transformed) to place it in the world.
 pub trait Geometry: Sized {
     /// Project this geometry onto an axis.
     fn project(&self, axis: Vector2) -> Projection;
@@ -83,10 +131,58 @@ pub trait Geometry: Sized {
         T: Geometry,
     {
         self.axis(other).into_iter()
-            .chain(other.axis(self).into_iter())
+            .chain(other.axis(self))
             .all(|p| self.project(p).overlap(&other.project(p)))
     }
 
+    /// Collide two objects together, returning the minimum translation vector
+    /// to push `self` out of `other` if they collide.
+    fn penetration<T>(&self, other: &T) -> Option<Penetration>
+    where
+        T: Geometry,
+    {
+        let mut best: Option<Penetration> = None;
+
+        for axis in self.axis(other).into_iter().chain(other.axis(self)) {
+            // concentric circles do not have an axis between them
+            if !axis.x.is_finite() || !axis.y.is_finite() {
+                continue;
+            }
+
+            let a = self.project(axis);
+            let b = other.project(axis);
+
+            let depth = a.overlap_amount(&b);
+
+            if depth <= 0.0 {
+                return None;
+            }
+
+            if best.map(|best| depth < best.depth).unwrap_or(true) {
+                // push `self` towards whichever side is closer
+                let axis = if a.end() - b.start() < b.end() - a.start() {
+                    -axis
+                } else {
+                    axis
+                };
+
+                best = Some(Penetration { axis, depth });
+            }
+        }
+
+        best.or_else(|| {
+            // there were no usable axes, so pick an arbitrary one
+            let axis = Vector2::unit_x();
+            let depth = self.project(axis).overlap_amount(&other.project(axis));
+
+            if depth <= 0.0 {
+                return None;
+            }
+
+            Some(Penetration { axis, depth })
+        })
+    }
+
     /// Contain one object within the other, returning true if the shape is
     /// contained within the container shape.
     fn contain<T>(&self, other: &T) -> bool
@@ -94,14 +190,36 @@ pub trait Geometry: Sized {
         T: Geometry,
     {
         self.axis(other).into_iter()
-            .chain(other.axis(self).into_iter())
+            .chain(other.axis(self))
             .all(|p| self.project(p).contains(&other.project(p)))
     }
 }
 
+/// Check if a point is inside of a ring of vertices.
+///
+/// Unlike [`Geometry::collide()`], the ring can be concave. This counts how
+/// many times the ring crosses a ray going right from the point, so points
+/// exactly on an edge can land on either side.
+pub fn contains_point(vertices: &[Vector2], point: Vector2) -> bool {
+    let mut inside = false;
+
+    for (a, b) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+        if (a.y > point.y) != (b.y > point.y) {
+            let x = a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x);
+
+            if point.x < x {
+                inside = !inside;
+            }
+        }
+    }
+
+    inside
+}
+
 /// A circle.
 ///
 /// *Circles are geometry too!*
+#[derive(Clone, Copy, Debug, Encode, Decode)]
 pub struct Circle {
     center: Vector2,
     radius: FLOAT,
@@ -112,6 +230,16 @@ impl Circle {
     pub fn new(center: Vector2, radius: FLOAT) -> Circle {
         Circle { center, radius }
     }
+
+    /// The center of the circle.
+    pub fn center(&self) -> Vector2 {
+        self.center
+    }
+
+    /// The radius of the circle.
+    pub fn radius(&self) -> FLOAT {
+        self.radius
+    }
 }
 
 impl Geometry for Circle {
@@ -138,7 +266,9 @@ impl Geometry for Circle {
 }
 
 /// A closed polygon with `N` vertices.
-#[derive(Clone)]
+#[derive(Clone, Debug, Default, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Polygon(Vec<Vector2>);
 
 impl Polygon {
@@ -183,9 +313,10 @@ impl Geometry for Polygon {
     where
         T: Geometry,
     {
+        // include the closing edge between the last and first vertex
         self.0
             .iter()
-            .zip(self.0.iter().skip(1))
+            .zip(self.0.iter().cycle().skip(1))
             .map(|v| {
                 let edge = v.1 - v.0;
                 Vector2::new(-edge.y, edge.x).normalize()
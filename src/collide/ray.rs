@@ -0,0 +1,267 @@
+//! Ray casting.
+//!
+//! Rays answer questions like "can this player see that player" and "which
+//! console did the player click on". A [`Ray`] starts at a point and goes on
+//! in one direction, while a [`Segment`] stops at an end point.
+
+use crate::collide::{Circle, Polygon};
+use crate::math::*;
+
+/// A ray.
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    origin: Vector2,
+    direction: Vector2,
+}
+
+impl Ray {
+    /// Create a new ray.
+    ///
+    /// `direction` does not need to be normalized. A zero `direction` has no
+    /// way to go, so the ray points along the x axis instead.
+    pub fn new(origin: Vector2, direction: Vector2) -> Ray {
+        let direction = if direction.magnitude2() == 0.0 {
+            Vector2::unit_x()
+        } else {
+            direction.normalize()
+        };
+
+        Ray { origin, direction }
+    }
+
+    /// The start of the ray.
+    pub fn origin(&self) -> Vector2 {
+        self.origin
+    }
+
+    /// The direction of the ray.
+    ///
+    /// This is normalized.
+    pub fn direction(&self) -> Vector2 {
+        self.direction
+    }
+
+    /// Get the point `distance` along the ray.
+    pub fn at(&self, distance: FLOAT) -> Vector2 {
+        self.origin + self.direction * distance
+    }
+}
+
+/// A line segment.
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    start: Vector2,
+    end: Vector2,
+}
+
+impl Segment {
+    /// Create a new segment.
+    pub fn new(start: Vector2, end: Vector2) -> Segment {
+        Segment { start, end }
+    }
+
+    /// The start point.
+    pub fn start(&self) -> Vector2 {
+        self.start
+    }
+
+    /// The end point.
+    pub fn end(&self) -> Vector2 {
+        self.end
+    }
+
+    /// The length of the segment.
+    pub fn length(&self) -> FLOAT {
+        (self.end - self.start).magnitude()
+    }
+
+    /// Get the ray going from the start to the end of the segment.
+    pub fn ray(&self) -> Ray {
+        Ray::new(self.start, self.end - self.start)
+    }
+
+    /// Cast the segment against a shape, returning where it first hits.
+    ///
+    /// The distance of the hit is measured from the start of the segment. A
+    /// segment that starts and ends at the same point only hits shapes that
+    /// point is inside of.
+    pub fn cast<T>(&self, shape: &T) -> Option<RayHit>
+    where
+        T: Raycast,
+    {
+        shape.raycast(&self.ray(), self.length())
+    }
+
+    /// Check if nothing in `shapes` blocks the segment.
+    ///
+    /// Like [`Segment::cast()`], a segment with no length is only blocked by
+    /// shapes its point is inside of.
+    pub fn clear<'a, T, I>(&self, shapes: I) -> bool
+    where
+        T: Raycast + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        shapes.into_iter().all(|shape| self.cast(shape).is_none())
+    }
+}
+
+/// Where a ray hit a shape.
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+    distance: FLOAT,
+    normal: Vector2,
+}
+
+impl RayHit {
+    /// The distance along the ray to the hit.
+    pub fn distance(&self) -> FLOAT {
+        self.distance
+    }
+
+    /// The surface normal at the hit.
+    ///
+    /// This is normalized. If the ray started inside of the shape, this points
+    /// back along the ray.
+    pub fn normal(&self) -> Vector2 {
+        self.normal
+    }
+}
+
+/// A shape that can be hit by a ray.
+pub trait Raycast {
+    /// Cast a ray against the shape, returning where it first hits if that is
+    /// within `max_distance`.
+    ///
+    /// Rays that start inside of the shape hit at a distance of `0.0`.
+    fn raycast(&self, ray: &Ray, max_distance: FLOAT) -> Option<RayHit>;
+}
+
+impl Raycast for Circle {
+    fn raycast(&self, ray: &Ray, max_distance: FLOAT) -> Option<RayHit> {
+        let offset = ray.origin - self.center;
+
+        let b = offset.dot(ray.direction);
+        let c = offset.dot(offset) - self.radius * self.radius;
+
+        if c <= 0.0 {
+            return Some(RayHit {
+                distance: 0.0,
+                normal: -ray.direction,
+            });
+        }
+
+        let discriminant = b * b - c;
+
+        if b > 0.0 || discriminant < 0.0 {
+            return None;
+        }
+
+        let distance = -b - discriminant.sqrt();
+
+        if distance > max_distance {
+            None
+        } else {
+            Some(RayHit {
+                distance,
+                normal: (ray.at(distance) - self.center).normalize(),
+            })
+        }
+    }
+}
+
+impl Raycast for Polygon {
+    fn raycast(&self, ray: &Ray, max_distance: FLOAT) -> Option<RayHit> {
+        let vertices = &self.0;
+
+        if vertices.is_empty() {
+            return None;
+        }
+
+        let centroid = vertices.iter().fold(Vector2::zero(), |acc, v| acc + v)
+            / vertices.len() as FLOAT;
+
+        // clip the ray against every edge of the polygon
+        let mut enter = FLOAT::NEG_INFINITY;
+        let mut exit = FLOAT::INFINITY;
+        let mut normal = -ray.direction;
+
+        for (a, b) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+            let edge = b - a;
+
+            if edge.magnitude2() == 0.0 {
+                continue;
+            }
+
+            let mut n = Vector2::new(-edge.y, edge.x).normalize();
+            if (a - centroid).dot(n) < 0.0 {
+                n = -n;
+            }
+
+            let distance = (ray.origin - a).dot(n);
+            let speed = ray.direction.dot(n);
+
+            if speed == 0.0 {
+                if distance > 0.0 {
+                    return None;
+                }
+
+                continue;
+            }
+
+            let t = -distance / speed;
+
+            if speed < 0.0 {
+                if t > enter {
+                    enter = t;
+                    normal = n;
+                }
+            } else {
+                exit = exit.min(t);
+            }
+
+            if enter > exit {
+                return None;
+            }
+        }
+
+        if exit < 0.0 {
+            None
+        } else if enter <= 0.0 {
+            Some(RayHit {
+                distance: 0.0,
+                normal: -ray.direction,
+            })
+        } else if enter > max_distance {
+            None
+        } else {
+            Some(RayHit {
+                distance: enter,
+                normal,
+            })
+        }
+    }
+}
+
+/// Cast a ray against a set of shapes, returning the index of the first shape
+/// hit and where it was hit.
+///
+/// If two shapes are hit at the same distance, the earlier one wins.
+pub fn first_hit<'a, T, I>(ray: &Ray, max_distance: FLOAT, shapes: I) -> Option<(usize, RayHit)>
+where
+    T: Raycast + 'a,
+    I: IntoIterator<Item = &'a T>,
+{
+    let mut best: Option<(usize, RayHit)> = None;
+
+    for (i, shape) in shapes.into_iter().enumerate() {
+        let max_distance = best.map(|(_, hit)| hit.distance).unwrap_or(max_distance);
+
+        if let Some(hit) = shape.raycast(ray, max_distance) {
+            if best.map(|(_, best)| hit.distance < best.distance).unwrap_or(true) {
+                best = Some((i, hit));
+            }
+        }
+    }
+
+    best
+}
@@ -0,0 +1,180 @@
+//! Swept (continuous) collision.
+//!
+//! A static overlap test only looks at where a shape ends up, so a fast
+//! moving circle can skip over a thin wall entirely in a single step. These
+//! queries instead find the first point in time where a moving circle touches
+//! a shape.
+//!
+//! Every [`Geometry`] in this crate is the convex hull of its vertices,
+//! rounded off by some radius; for a [`Polygon`](super::Polygon) this radius
+//! is zero and for a [`Circle`] the hull is a single point. The moving circle
+//! is swept against the hull grown by both radii, which gives an exact time of
+//! impact for both.
+
+use crate::collide::{Circle, Geometry};
+use crate::math::*;
+
+/// The first contact between a moving circle and a static shape.
+#[derive(Clone, Copy, Debug)]
+pub struct Impact {
+    time: FLOAT,
+    normal: Vector2,
+}
+
+impl Impact {
+    /// The time of impact.
+    ///
+    /// This is a fraction of the velocity between `0.0` and `1.0`, so the
+    /// circle touches the shape when it has moved `velocity * time`.
+    pub fn time(&self) -> FLOAT {
+        self.time
+    }
+
+    /// The contact normal.
+    ///
+    /// This is normalized, and points away from the shape towards the circle.
+    pub fn normal(&self) -> Vector2 {
+        self.normal
+    }
+}
+
+/// Sweep a circle moving by `velocity` against a static shape, returning the
+/// first contact, if any.
+///
+/// If the circle already overlaps the shape, the impact happens at time
+/// `0.0` and the normal is the axis of the minimum translation vector.
+pub fn time_of_impact<T>(circle: &Circle, velocity: Vector2, other: &T) -> Option<Impact>
+where
+    T: Geometry,
+{
+    if let Some(pen) = circle.penetration(other) {
+        return Some(Impact {
+            time: 0.0,
+            normal: pen.axis(),
+        });
+    }
+
+    if velocity.magnitude2() == 0.0 {
+        return None;
+    }
+
+    let vertices = other.vertices();
+    let radius = circle.radius() + rounding(other);
+    let origin = circle.center();
+
+    let mut best: Option<Impact> = None;
+    let mut consider = |impact: Impact| {
+        if best.map(|best| impact.time < best.time).unwrap_or(true) {
+            best = Some(impact);
+        }
+    };
+
+    // the flat sides of the grown hull
+    if vertices.len() > 1 {
+        let centroid = vertices.iter().fold(Vector2::zero(), |acc, v| acc + v)
+            / vertices.len() as FLOAT;
+
+        for (a, b) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+            if let Some(impact) = sweep_edge(origin, velocity, radius, *a, *b, centroid) {
+                consider(impact);
+            }
+        }
+    }
+
+    // the rounded corners of the grown hull
+    for v in vertices {
+        if let Some(impact) = sweep_point(origin, velocity, radius, *v) {
+            consider(impact);
+        }
+    }
+
+    best
+}
+
+/// Get how far a shape is rounded off past its vertices.
+fn rounding<T>(shape: &T) -> FLOAT
+where
+    T: Geometry,
+{
+    let axis = Vector2::unit_x();
+    let furthest = shape
+        .vertices()
+        .iter()
+        .map(|v| axis.dot(*v))
+        .fold(FLOAT::NEG_INFINITY, FLOAT::max);
+
+    (shape.project(axis).end() - furthest).max(0.0)
+}
+
+/// Sweep a point against the edge `a -> b` pushed out by `radius`.
+fn sweep_edge(
+    origin: Vector2,
+    velocity: Vector2,
+    radius: FLOAT,
+    a: Vector2,
+    b: Vector2,
+    centroid: Vector2,
+) -> Option<Impact> {
+    let edge = b - a;
+
+    if edge.magnitude2() == 0.0 {
+        return None;
+    }
+
+    let mut normal = Vector2::new(-edge.y, edge.x).normalize();
+
+    // face the normal outwards; degenerate hulls (a single segment) have no
+    // outside, so face whichever side the circle is on
+    let side = (a - centroid).dot(normal);
+    if side < 0.0 || (side == 0.0 && (origin - a).dot(normal) < 0.0) {
+        normal = -normal;
+    }
+
+    let distance = (origin - a).dot(normal) - radius;
+    let speed = velocity.dot(normal);
+
+    if distance < 0.0 || speed >= 0.0 {
+        return None;
+    }
+
+    let time = distance / -speed;
+
+    if time > 1.0 {
+        return None;
+    }
+
+    // make sure the contact is actually on the edge and not past its ends
+    let along = (origin + velocity * time - a).dot(edge) / edge.magnitude2();
+
+    if (0.0..=1.0).contains(&along) {
+        Some(Impact { time, normal })
+    } else {
+        None
+    }
+}
+
+/// Sweep a point against a circle at `center`.
+fn sweep_point(origin: Vector2, velocity: Vector2, radius: FLOAT, center: Vector2) -> Option<Impact> {
+    let offset = origin - center;
+
+    let a = velocity.dot(velocity);
+    let b = 2.0 * offset.dot(velocity);
+    let c = offset.dot(offset) - radius * radius;
+
+    let discriminant = b * b - 4.0 * a * c;
+
+    if c < 0.0 || discriminant < 0.0 {
+        return None;
+    }
+
+    let time = (-b - discriminant.sqrt()) / (2.0 * a);
+
+    if (0.0..=1.0).contains(&time) {
+        Some(Impact {
+            time,
+            normal: (origin + velocity * time - center).normalize(),
+        })
+    } else {
+        None
+    }
+}
@@ -0,0 +1,203 @@
+//! Translating, rotating and scaling shapes.
+//!
+//! Shapes are defined around their own origin, and a [`Transform`] places
+//! them in the world. A [`Transformed`] shape keeps both the original shape
+//! and a copy in world space. Changing the transform rewrites the copy in
+//! place, so moving a door around every tick does not allocate.
+
+use crate::collide::ray::{Ray, RayHit, Raycast};
+use crate::collide::{Circle, Geometry, Polygon, Projection};
+use crate::math::*;
+
+/// A position, rotation and scale.
+///
+/// Shapes are scaled first, then rotated, then moved to the position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    position: Vector2,
+    rotation: FLOAT,
+    scale: FLOAT,
+}
+
+impl Transform {
+    /// Create a new transform.
+    ///
+    /// `rotation` is counter-clockwise, in radians.
+    pub fn new(position: Vector2, rotation: FLOAT, scale: FLOAT) -> Transform {
+        Transform {
+            position,
+            rotation,
+            scale,
+        }
+    }
+
+    /// The transform that does nothing.
+    pub fn identity() -> Transform {
+        Transform::new(Vector2::zero(), 0.0, 1.0)
+    }
+
+    /// Create a new transform that only moves.
+    pub fn translate(position: Vector2) -> Transform {
+        Transform::new(position, 0.0, 1.0)
+    }
+
+    /// The position.
+    pub fn position(&self) -> Vector2 {
+        self.position
+    }
+
+    /// The rotation, in radians.
+    pub fn rotation(&self) -> FLOAT {
+        self.rotation
+    }
+
+    /// The scale.
+    pub fn scale(&self) -> FLOAT {
+        self.scale
+    }
+
+    /// Set the position.
+    pub fn set_position(&mut self, position: Vector2) {
+        self.position = position;
+    }
+
+    /// Set the rotation, in radians.
+    pub fn set_rotation(&mut self, rotation: FLOAT) {
+        self.rotation = rotation;
+    }
+
+    /// Set the scale.
+    pub fn set_scale(&mut self, scale: FLOAT) {
+        self.scale = scale;
+    }
+
+    /// Transform a point.
+    pub fn apply(&self, point: Vector2) -> Vector2 {
+        self.rotate(point) * self.scale + self.position
+    }
+
+    /// Rotate a vector, without scaling or moving it.
+    pub fn rotate(&self, vector: Vector2) -> Vector2 {
+        let (sin, cos) = self.rotation.sin_cos();
+
+        Vector2::new(vector.x * cos - vector.y * sin, vector.x * sin + vector.y * cos)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+/// A shape that can be transformed.
+pub trait Transformable: Geometry + Clone {
+    /// Overwrite `self` with `local` transformed by `transform`.
+    ///
+    /// This should reuse any memory `self` already has.
+    fn transform_from(&mut self, local: &Self, transform: &Transform);
+
+    /// Wrap the shape with a transform.
+    fn with_transform(self, transform: Transform) -> Transformed<Self> {
+        Transformed::new(self, transform)
+    }
+}
+
+impl Transformable for Circle {
+    fn transform_from(&mut self, local: &Circle, transform: &Transform) {
+        self.center = transform.apply(local.center);
+        self.radius = local.radius * transform.scale.abs();
+    }
+}
+
+impl Transformable for Polygon {
+    fn transform_from(&mut self, local: &Polygon, transform: &Transform) {
+        self.0.clear();
+        self.0.extend(local.0.iter().map(|v| transform.apply(*v)));
+    }
+}
+
+/// A shape with a transform.
+///
+/// This is geometry too, in world space.
+#[derive(Clone, Debug)]
+pub struct Transformed<G>
+where
+    G: Transformable,
+{
+    local: G,
+    world: G,
+    transform: Transform,
+}
+
+impl<G> Transformed<G>
+where
+    G: Transformable,
+{
+    /// Create a new transformed shape.
+    pub fn new(shape: G, transform: Transform) -> Transformed<G> {
+        let mut world = shape.clone();
+        world.transform_from(&shape, &transform);
+
+        Transformed {
+            local: shape,
+            world,
+            transform,
+        }
+    }
+
+    /// The shape, before it was transformed.
+    pub fn local(&self) -> &G {
+        &self.local
+    }
+
+    /// The shape in world space.
+    pub fn world(&self) -> &G {
+        &self.world
+    }
+
+    /// The transform.
+    pub fn transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    /// Set the transform.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+        self.world.transform_from(&self.local, &self.transform);
+    }
+
+    /// Unwrap the shape, before it was transformed.
+    pub fn into_inner(self) -> G {
+        self.local
+    }
+}
+
+impl<G> Geometry for Transformed<G>
+where
+    G: Transformable,
+{
+    fn project(&self, axis: Vector2) -> Projection {
+        self.world.project(axis)
+    }
+
+    fn vertices(&self) -> &[Vector2] {
+        self.world.vertices()
+    }
+
+    fn axis<T>(&self, other: &T) -> Vec<Vector2>
+    where
+        T: Geometry,
+    {
+        self.world.axis(other)
+    }
+}
+
+impl<G> Raycast for Transformed<G>
+where
+    G: Transformable + Raycast,
+{
+    fn raycast(&self, ray: &Ray, max_distance: FLOAT) -> Option<RayHit> {
+        self.world.raycast(ray, max_distance)
+    }
+}
@@ -1,4 +1,201 @@
+pub mod movement;
 pub mod task;
+pub mod vision;
 
-/// A placeholder struct for game state.
-pub struct State;
+use std::collections::BTreeMap;
+
+use crate::collide::Polygon;
+use crate::game::movement::Controller;
+use crate::math::*;
+use crate::net::protocol::PlayerId;
+
+/// The most players a game can have.
+pub const MAX_PLAYERS: usize = 10;
+
+/// How far a player can walk in a second.
+pub const SPEED: FLOAT = 3.0;
+
+/// The radius of a player.
+pub const RADIUS: FLOAT = 0.35;
+
+/// A player in a game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    name: String,
+    color: u8,
+    position: Vector2,
+    velocity: Vector2,
+    alive: bool,
+}
+
+impl Player {
+    /// The name of the player.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The color of the player.
+    pub fn color(&self) -> u8 {
+        self.color
+    }
+
+    /// Where the player is.
+    pub fn position(&self) -> Vector2 {
+        self.position
+    }
+
+    /// How fast the player is walking.
+    pub fn velocity(&self) -> Vector2 {
+        self.velocity
+    }
+
+    /// Set how fast the player is walking.
+    ///
+    /// Nobody can walk faster than [`SPEED`], so faster velocities are
+    /// slowed down to it. Velocities that aren't finite stop the player.
+    pub fn set_velocity(&mut self, velocity: Vector2) {
+        if !velocity.x.is_finite() || !velocity.y.is_finite() {
+            self.velocity = Vector2::zero();
+            return;
+        }
+
+        let speed = velocity.magnitude();
+
+        self.velocity = if speed > SPEED { velocity * (SPEED / speed) } else { velocity };
+    }
+
+    /// Check if the player is alive.
+    pub fn is_alive(&self) -> bool {
+        self.alive
+    }
+}
+
+/// The state of a game.
+///
+/// The server owns the real state, and steps it at a fixed rate; clients
+/// only get told what happens in it.
+#[derive(Clone, Debug)]
+pub struct State {
+    players: BTreeMap<PlayerId, Player>,
+    max_players: usize,
+    started: bool,
+    tick: u64,
+}
+
+impl State {
+    /// Create a new game, with nobody in it.
+    pub fn new() -> State {
+        State {
+            players: BTreeMap::new(),
+            max_players: MAX_PLAYERS,
+            started: false,
+            tick: 0,
+        }
+    }
+
+    /// All of the players, in the order of their ids.
+    pub fn players(&self) -> impl Iterator<Item = (PlayerId, &Player)> {
+        self.players.iter().map(|(&id, player)| (id, player))
+    }
+
+    /// A player in the game.
+    pub fn player(&self, id: PlayerId) -> Option<&Player> {
+        self.players.get(&id)
+    }
+
+    /// A player in the game.
+    pub fn player_mut(&mut self, id: PlayerId) -> Option<&mut Player> {
+        self.players.get_mut(&id)
+    }
+
+    /// The number of players in the game.
+    pub fn len(&self) -> usize {
+        self.players.len()
+    }
+
+    /// Check if there is nobody in the game.
+    pub fn is_empty(&self) -> bool {
+        self.players.is_empty()
+    }
+
+    /// The most players the game can have.
+    pub fn max_players(&self) -> usize {
+        self.max_players
+    }
+
+    /// Set the most players the game can have.
+    ///
+    /// Players that are already in the game stay in it.
+    ///
+    /// # Panics
+    /// Panics if `max_players` is zero or more than [`MAX_PLAYERS`].
+    pub fn set_max_players(&mut self, max_players: usize) {
+        assert!((1..=MAX_PLAYERS).contains(&max_players), "a game can have between 1 and {} players", MAX_PLAYERS);
+        self.max_players = max_players;
+    }
+
+    /// Check if the game has as many players as it can.
+    pub fn is_full(&self) -> bool {
+        self.players.len() >= self.max_players
+    }
+
+    /// Check if the game has started.
+    pub fn is_started(&self) -> bool {
+        self.started
+    }
+
+    /// Start the game.
+    pub fn start(&mut self) {
+        self.started = true;
+    }
+
+    /// The number of steps taken so far.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// Add a player at `spawn`, returning their id.
+    ///
+    /// Players get the lowest id and color nobody else has. This fails if
+    /// the game is full.
+    pub fn join(&mut self, name: impl Into<String>, spawn: Vector2) -> Option<PlayerId> {
+        if self.is_full() {
+            return None;
+        }
+
+        let id = (0..=u8::MAX).map(PlayerId).find(|id| !self.players.contains_key(id))?;
+        let color = (0..=u8::MAX).find(|&color| self.players.values().all(|player| player.color != color))?;
+
+        let player = Player {
+            name: name.into(),
+            color,
+            position: spawn,
+            velocity: Vector2::new(0.0, 0.0),
+            alive: true,
+        };
+
+        self.players.insert(id, player);
+
+        Some(id)
+    }
+
+    /// Remove a player.
+    pub fn leave(&mut self, id: PlayerId) -> Option<Player> {
+        self.players.remove(&id)
+    }
+
+    /// Move everybody along by `dt` seconds.
+    pub fn step(&mut self, dt: FLOAT, controller: &Controller, walls: &[Polygon]) {
+        for player in self.players.values_mut() {
+            player.position = controller.step(player.position, player.velocity * dt, walls);
+        }
+
+        self.tick += 1;
+    }
+}
+
+impl Default for State {
+    fn default() -> State {
+        State::new()
+    }
+}
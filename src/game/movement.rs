@@ -0,0 +1,142 @@
+//! Player movement.
+//!
+//! Players are circles that slide along walls instead of stopping dead when
+//! they walk into them. The server and clients both run the same [`Controller`]
+//! on the same inputs, so as long as they agree on the walls they will agree
+//! on where everybody is standing.
+//!
+//! Movement is resolved by sweeping the player against every wall, moving up
+//! to the first contact, and then sliding the rest of the way along the wall.
+//! This is repeated a few times so that players can slide into corners.
+
+use crate::collide::sweep::{time_of_impact, Impact};
+use crate::collide::{Circle, Geometry as _, Polygon};
+use crate::math::*;
+
+/// The default number of slides a single movement can make.
+pub const DEFAULT_ITERATIONS: usize = 4;
+
+/// The default distance kept between a player and a wall.
+pub const DEFAULT_SKIN: FLOAT = 0.001;
+
+/// A character controller.
+///
+/// This moves a circular player through a set of walls. Every step is
+/// deterministic; the walls are always checked in the order they are given.
+#[derive(Clone, Copy, Debug)]
+pub struct Controller {
+    radius: FLOAT,
+    iterations: usize,
+    skin: FLOAT,
+}
+
+impl Controller {
+    /// Create a new controller for a player of the given radius.
+    pub fn new(radius: FLOAT) -> Controller {
+        Controller {
+            radius,
+            iterations: DEFAULT_ITERATIONS,
+            skin: DEFAULT_SKIN,
+        }
+    }
+
+    /// The radius of the player.
+    pub fn radius(&self) -> FLOAT {
+        self.radius
+    }
+
+    /// The maximum number of slides a single movement can make.
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Set the maximum number of slides a single movement can make.
+    ///
+    /// Any movement left over after the last slide is dropped.
+    pub fn set_iterations(&mut self, iterations: usize) {
+        self.iterations = iterations;
+    }
+
+    /// The distance kept between a player and a wall.
+    pub fn skin(&self) -> FLOAT {
+        self.skin
+    }
+
+    /// Set the distance kept between a player and a wall.
+    ///
+    /// Stopping just short of walls keeps players from getting stuck on the
+    /// wall they are sliding along due to rounding errors.
+    pub fn set_skin(&mut self, skin: FLOAT) {
+        self.skin = skin;
+    }
+
+    /// Move a player at `position` by `velocity`, returning the resolved
+    /// position.
+    pub fn step(&self, position: Vector2, velocity: Vector2, walls: &[Polygon]) -> Vector2 {
+        let mut position = self.depenetrate(position, walls);
+        let mut remaining = velocity;
+
+        for _ in 0..self.iterations {
+            if remaining.magnitude2() == 0.0 {
+                break;
+            }
+
+            let circle = Circle::new(position, self.radius);
+
+            // find the first wall that is hit, preferring earlier walls in a tie
+            let impact = walls
+                .iter()
+                .filter_map(|wall| time_of_impact(&circle, remaining, wall))
+                .fold(None, |best: Option<Impact>, impact| match best {
+                    Some(best) if best.time() <= impact.time() => Some(best),
+                    _ => Some(impact),
+                });
+
+            let impact = match impact {
+                Some(impact) => impact,
+                None => {
+                    position += remaining;
+                    break;
+                }
+            };
+
+            // move up to the wall, stopping a little short
+            let travel = remaining * impact.time();
+            let distance = travel.magnitude();
+
+            if distance > self.skin {
+                position += travel * ((distance - self.skin) / distance);
+            }
+
+            // slide along the wall with whatever movement is left
+            remaining -= travel;
+
+            let into = remaining.dot(impact.normal());
+            if into < 0.0 {
+                remaining -= impact.normal() * into;
+            }
+        }
+
+        self.depenetrate(position, walls)
+    }
+
+    /// Push a player at `position` out of any walls it is stuck in.
+    pub fn depenetrate(&self, mut position: Vector2, walls: &[Polygon]) -> Vector2 {
+        for _ in 0..self.iterations {
+            let mut resolved = true;
+
+            for wall in walls {
+                if let Some(pen) = Circle::new(position, self.radius).penetration(wall) {
+                    position += pen.axis() * (pen.depth() + self.skin);
+                    resolved = false;
+                }
+            }
+
+            if resolved {
+                break;
+            }
+        }
+
+        position
+    }
+}
@@ -18,15 +18,23 @@
 //! functionality themselves, except for helper functions for networking.
 
 use crate::game::State;
+use crate::net::binary::{decode::Decode, encode::Encode};
 
 /// A task.
 ///
 /// This is purely the data part of a task.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Task {
     name: String,
 }
 
 impl Task {
+    /// Create a new task.
+    pub fn new(name: impl Into<String>) -> Task {
+        Task { name: name.into() }
+    }
+
     /// The name of the task.
     pub fn name(&self) -> &str {
         &self.name
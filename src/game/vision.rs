@@ -0,0 +1,192 @@
+//! Player vision.
+//!
+//! Crewmates can only see so far, and can't see through walls. The area a
+//! player can see is found by casting rays out from their eyes towards every
+//! wall corner, which gives a polygon that hugs the shadows cast by the walls.
+//! Past the walls, the polygon follows the edge of the vision radius.
+//!
+//! The server uses this to avoid telling players about things they can't
+//! see, and the client uses it to draw the shadows.
+
+use crate::collide::broad::Aabb;
+use crate::collide::ray::{first_hit, Ray};
+use crate::collide::{contains_point, Circle, Geometry as _, Polygon};
+use crate::math::*;
+
+/// The default number of points used to draw the edge of the vision radius.
+pub const DEFAULT_RESOLUTION: usize = 64;
+
+/// How far past a wall corner to look, in radians.
+const CORNER_EPSILON: FLOAT = 1e-4;
+
+/// The area a player can see.
+#[derive(Clone, Debug)]
+pub struct Vision {
+    eye: Vector2,
+    radius: FLOAT,
+    points: Vec<Vector2>,
+}
+
+impl Vision {
+    /// Compute what can be seen from `eye`.
+    pub fn new(eye: Vector2, radius: FLOAT, walls: &[Polygon]) -> Vision {
+        Vision::with_resolution(eye, radius, walls, DEFAULT_RESOLUTION)
+    }
+
+    /// Compute what can be seen from `eye`, using `resolution` points to draw
+    /// the edge of the vision radius.
+    pub fn with_resolution(
+        eye: Vector2,
+        radius: FLOAT,
+        walls: &[Polygon],
+        resolution: usize,
+    ) -> Vision {
+        let bounds = Aabb::from_geometry(&Circle::new(eye, radius));
+        let walls = walls
+            .iter()
+            .filter(|wall| Aabb::from_geometry(*wall).overlap(&bounds))
+            .collect::<Vec<_>>();
+
+        let mut angles = (0..resolution)
+            .map(|i| i as FLOAT / resolution as FLOAT * 2.0 * consts::PI - consts::PI)
+            .collect::<Vec<_>>();
+
+        for wall in walls.iter() {
+            let vertices = wall.vertices();
+
+            // look just past every corner to find where the shadows start
+            for v in vertices {
+                let offset = v - eye;
+
+                if offset.magnitude2() <= radius * radius {
+                    let angle = offset.y.atan2(offset.x);
+
+                    angles.push(angle - CORNER_EPSILON);
+                    angles.push(angle);
+                    angles.push(angle + CORNER_EPSILON);
+                }
+            }
+
+            // and wherever a wall leaves the vision radius
+            for (a, b) in vertices.iter().zip(vertices.iter().cycle().skip(1)) {
+                for point in circle_intersections(eye, radius, *a, *b) {
+                    let offset = point - eye;
+                    angles.push(offset.y.atan2(offset.x));
+                }
+            }
+        }
+
+        angles.sort_by(|a, b| a.partial_cmp(b).expect("angles should never be NaN"));
+        angles.dedup_by(|a, b| (*a - *b).abs() < CORNER_EPSILON / 2.0);
+
+        let points = angles
+            .into_iter()
+            .map(|angle| {
+                let ray = Ray::new(eye, Vector2::new(angle.cos(), angle.sin()));
+                let distance = first_hit(&ray, radius, walls.iter().copied())
+                    .map(|(_, hit)| hit.distance())
+                    .unwrap_or(radius);
+
+                ray.at(distance)
+            })
+            .collect();
+
+        Vision {
+            eye,
+            radius,
+            points,
+        }
+    }
+
+    /// Where the player is looking from.
+    pub fn eye(&self) -> Vector2 {
+        self.eye
+    }
+
+    /// How far the player can see.
+    pub fn radius(&self) -> FLOAT {
+        self.radius
+    }
+
+    /// The outline of the visible area.
+    ///
+    /// The points are sorted counter-clockwise around the eye. The outline is
+    /// usually concave, but every point on it can be seen from the eye.
+    pub fn points(&self) -> &[Vector2] {
+        &self.points
+    }
+
+    /// Split the visible area into triangles fanning out from the eye.
+    ///
+    /// Unlike the outline, the triangles are convex, so they can be used as
+    /// [`Geometry`](crate::collide::Geometry) or drawn directly.
+    pub fn triangles(&self) -> impl Iterator<Item = Polygon> + '_ {
+        let eye = self.eye;
+
+        self.points
+            .iter()
+            .zip(self.points.iter().cycle().skip(1))
+            .map(move |(a, b)| vec![eye, *a, *b].into())
+    }
+
+    /// Check if a point can be seen.
+    pub fn contains(&self, point: Vector2) -> bool {
+        if (point - self.eye).magnitude2() > self.radius * self.radius {
+            return false;
+        }
+
+        contains_point(&self.points, point)
+    }
+
+    /// Check if any part of a circle can be seen.
+    pub fn sees(&self, circle: &Circle) -> bool {
+        if self.contains(circle.center()) {
+            return true;
+        }
+
+        let radius2 = circle.radius() * circle.radius();
+
+        self.points
+            .iter()
+            .zip(self.points.iter().cycle().skip(1))
+            .any(|(a, b)| distance2_to_segment(circle.center(), *a, *b) < radius2)
+    }
+}
+
+/// Find where the segment `a -> b` crosses the edge of a circle.
+fn circle_intersections(center: Vector2, radius: FLOAT, a: Vector2, b: Vector2) -> Vec<Vector2> {
+    let edge = b - a;
+    let offset = a - center;
+
+    let qa = edge.dot(edge);
+    let qb = 2.0 * offset.dot(edge);
+    let qc = offset.dot(offset) - radius * radius;
+
+    let discriminant = qb * qb - 4.0 * qa * qc;
+
+    if qa == 0.0 || discriminant < 0.0 {
+        return Vec::new();
+    }
+
+    let root = discriminant.sqrt();
+
+    [(-qb - root) / (2.0 * qa), (-qb + root) / (2.0 * qa)]
+        .iter()
+        .filter(|t| (0.0..=1.0).contains(*t))
+        .map(|t| a + edge * *t)
+        .collect()
+}
+
+/// Get the squared distance from a point to the segment `a -> b`.
+fn distance2_to_segment(point: Vector2, a: Vector2, b: Vector2) -> FLOAT {
+    let edge = b - a;
+    let length2 = edge.magnitude2();
+
+    let t = if length2 == 0.0 {
+        0.0
+    } else {
+        ((point - a).dot(edge) / length2).clamp(0.0, 1.0)
+    };
+
+    (a + edge * t - point).magnitude2()
+}
@@ -1,6 +1,10 @@
-#![feature(never_type)]
+// lets the derive macros refer to this crate by name from inside of it
+extern crate self as among_us;
 
 pub mod collide;
 pub mod game;
+pub mod map;
+pub mod nav;
 pub mod math;
 pub mod net;
+pub mod server;
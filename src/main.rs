@@ -1,3 +1,4 @@
+use among_us::collide::transform::{Transform, Transformable as _};
 use among_us::collide::{Circle, Geometry as _, Polygon};
 use among_us::math::*;
 
@@ -8,11 +9,11 @@ fn main() {
         Vector2::new(-4.0, -2.0),
         Vector2::new(-4.0, 2.0),
     ]
-    .iter()
-    .map(|v| v + Vector2::new(10.0, 10.0))
-    .collect::<Vec<Vector2>>()
+    .to_vec()
     .into();
 
+    let polygon = polygon.with_transform(Transform::translate(Vector2::new(10.0, 10.0)));
+
     if polygon.collide(&Circle::new(Vector2::new(15.0, 10.0), 5.000001)) {
         println!("collision");
     } else {
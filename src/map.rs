@@ -0,0 +1,411 @@
+//! Maps.
+//!
+//! A map is everything about a level that doesn't change during a game: the
+//! walls players bump into, the rooms they walk through, the vents impostors
+//! sneak around in, where the tasks are done, where the emergency button is
+//! and where everybody spawns.
+//!
+//! Maps are written by hand in [RON](https://github.com/ron-rs/ron) with the
+//! `ron` feature enabled, and can be sent from a host to its clients with
+//! the [`net::binary`](crate::net::binary) encoding. Either way, a map is
+//! [`validate`](Map::validate)d as it is loaded; maps built in code should be
+//! validated before they are used.
+
+use std::fmt;
+
+use crate::collide::{contains_point, decompose, Geometry as _, Polygon};
+use crate::game::task::Task;
+use crate::math::*;
+use crate::net::binary::decode::{self, Cursor, Decode};
+use crate::net::binary::encode::Encode;
+
+/// A map.
+#[derive(Clone, Debug, Encode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Map {
+    name: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    rooms: Vec<Room>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    walls: Vec<Polygon>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    vents: Vec<Vent>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    consoles: Vec<Console>,
+    button: Vector2,
+    spawns: Vec<Vector2>,
+}
+
+impl Map {
+    /// Create a new, empty map.
+    ///
+    /// Everybody spawns at the emergency button until spawn points are added.
+    pub fn new(name: impl Into<String>, button: Vector2) -> Map {
+        Map {
+            name: name.into(),
+            rooms: Vec::new(),
+            walls: Vec::new(),
+            vents: Vec::new(),
+            consoles: Vec::new(),
+            button,
+            spawns: vec![button],
+        }
+    }
+
+    /// Load a map from RON.
+    ///
+    /// The map is validated after it is loaded.
+    #[cfg(feature = "ron")]
+    pub fn from_ron(source: &str) -> Result<Map, Error> {
+        let map: Map = ron::from_str(source).map_err(|e| Error::Ron(e.to_string()))?;
+
+        map.validate()?;
+
+        Ok(map)
+    }
+
+    /// Write a map to RON.
+    #[cfg(feature = "ron")]
+    pub fn to_ron(&self) -> Result<String, Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| Error::Ron(e.to_string()))
+    }
+
+    /// Check that a map makes sense.
+    ///
+    /// Every position has to be finite, walls and rooms need at least three
+    /// vertices, walls have to be convex, vents can only connect to other
+    /// vents that exist, and there has to be somewhere to spawn.
+    pub fn validate(&self) -> Result<(), Error> {
+        self.validate_finite()?;
+
+        for (i, wall) in self.walls.iter().enumerate() {
+            if wall.vertices().len() < 3 {
+                return Err(Error::Wall(i));
+            }
+
+            if !is_convex(wall.vertices()) {
+                return Err(Error::Concave(i));
+            }
+        }
+
+        for room in self.rooms.iter() {
+            if room.bounds.vertices().len() < 3 {
+                return Err(Error::Room(room.name.clone()));
+            }
+        }
+
+        for (i, vent) in self.vents.iter().enumerate() {
+            if self.vents[..i].iter().any(|other| other.name == vent.name) {
+                return Err(Error::DuplicateVent(vent.name.clone()));
+            }
+
+            for connection in vent.connections.iter() {
+                if *connection == vent.name || self.vent(connection).is_none() {
+                    return Err(Error::Connection(vent.name.clone(), connection.clone()));
+                }
+            }
+        }
+
+        if self.spawns.is_empty() {
+            return Err(Error::NoSpawns);
+        }
+
+        Ok(())
+    }
+
+    fn validate_finite(&self) -> Result<(), Error> {
+        let not_finite = |what: String| Err(Error::NotFinite(what));
+
+        if !is_finite(self.button) {
+            return not_finite("the emergency button".into());
+        }
+
+        if let Some(i) = self.spawns.iter().position(|spawn| !is_finite(*spawn)) {
+            return not_finite(format!("spawn {}", i));
+        }
+
+        if let Some(i) = self.walls.iter().position(|wall| !wall.vertices().iter().all(|v| is_finite(*v))) {
+            return not_finite(format!("wall {}", i));
+        }
+
+        if let Some(room) = self.rooms.iter().find(|room| !room.bounds.vertices().iter().all(|v| is_finite(*v))) {
+            return not_finite(format!("room \"{}\"", room.name));
+        }
+
+        if let Some(vent) = self.vents.iter().find(|vent| !is_finite(vent.position)) {
+            return not_finite(format!("vent \"{}\"", vent.name));
+        }
+
+        if let Some(i) = self.consoles.iter().position(|console| !is_finite(console.position)) {
+            return not_finite(format!("console {}", i));
+        }
+
+        Ok(())
+    }
+
+    /// The name of the map.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The rooms.
+    pub fn rooms(&self) -> &[Room] {
+        &self.rooms
+    }
+
+    /// Get a room by name.
+    pub fn room(&self, name: &str) -> Option<&Room> {
+        self.rooms.iter().find(|room| room.name == name)
+    }
+
+    /// Find the room a point is in.
+    pub fn room_at(&self, point: Vector2) -> Option<&Room> {
+        self.rooms.iter().find(|room| room.contains(point))
+    }
+
+    /// Add a room.
+    pub fn push_room(&mut self, room: Room) {
+        self.rooms.push(room);
+    }
+
+    /// The collision polygons.
+    pub fn walls(&self) -> &[Polygon] {
+        &self.walls
+    }
+
+    /// Add a collision polygon.
+    pub fn push_wall(&mut self, wall: Polygon) {
+        self.walls.push(wall);
+    }
+
+    /// The vents.
+    pub fn vents(&self) -> &[Vent] {
+        &self.vents
+    }
+
+    /// Get a vent by name.
+    pub fn vent(&self, name: &str) -> Option<&Vent> {
+        self.vents.iter().find(|vent| vent.name == name)
+    }
+
+    /// Add a vent.
+    pub fn push_vent(&mut self, vent: Vent) {
+        self.vents.push(vent);
+    }
+
+    /// The task consoles.
+    pub fn consoles(&self) -> &[Console] {
+        &self.consoles
+    }
+
+    /// Add a task console.
+    pub fn push_console(&mut self, console: Console) {
+        self.consoles.push(console);
+    }
+
+    /// Where the emergency button is.
+    pub fn button(&self) -> Vector2 {
+        self.button
+    }
+
+    /// Where players spawn.
+    pub fn spawns(&self) -> &[Vector2] {
+        &self.spawns
+    }
+
+    /// Set where players spawn.
+    pub fn set_spawns(&mut self, spawns: Vec<Vector2>) {
+        self.spawns = spawns;
+    }
+}
+
+impl Decode for Map {
+    fn decode<T>(cursor: &mut Cursor<T>) -> Result<Self, decode::Error>
+    where T: decode::Source {
+        let map = Map {
+            name: cursor.decode()?,
+            rooms: cursor.decode()?,
+            walls: cursor.decode()?,
+            vents: cursor.decode()?,
+            consoles: cursor.decode()?,
+            button: cursor.decode()?,
+            spawns: cursor.decode()?,
+        };
+
+        map.validate().map_err(decode::Error::invalid_because)?;
+
+        Ok(map)
+    }
+}
+
+fn is_finite(point: Vector2) -> bool {
+    point.x.is_finite() && point.y.is_finite()
+}
+
+/// Check if a wall is a simple convex polygon with an area, in either winding
+/// order.
+fn is_convex(vertices: &[Vector2]) -> bool {
+    if decompose::validate(vertices, &[]).is_err() {
+        return false;
+    }
+
+    let len = vertices.len();
+    let turns = (0..len).map(|i| {
+        let (a, b, c) = (vertices[i], vertices[(i + 1) % len], vertices[(i + 2) % len]);
+        (b - a).perp_dot(c - b)
+    });
+
+    let (mut left, mut right) = (false, false);
+
+    for turn in turns {
+        left |= turn > 0.0;
+        right |= turn < 0.0;
+    }
+
+    !(left && right)
+}
+
+/// A named area of the map.
+///
+/// Rooms don't collide with anything; they tell players where they are.
+#[derive(Clone, Debug, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Room {
+    name: String,
+    bounds: Polygon,
+}
+
+impl Room {
+    /// Create a new room.
+    pub fn new(name: impl Into<String>, bounds: Polygon) -> Room {
+        Room {
+            name: name.into(),
+            bounds,
+        }
+    }
+
+    /// The name of the room.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The area the room covers.
+    pub fn bounds(&self) -> &Polygon {
+        &self.bounds
+    }
+
+    /// Check if a point is in the room.
+    pub fn contains(&self, point: Vector2) -> bool {
+        contains_point(self.bounds.vertices(), point)
+    }
+}
+
+/// A vent.
+///
+/// Vents are connected to other vents by name. Connections only go one way,
+/// so two vents that lead to each other both have to list the other.
+#[derive(Clone, Debug, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vent {
+    name: String,
+    position: Vector2,
+    #[cfg_attr(feature = "serde", serde(default))]
+    connections: Vec<String>,
+}
+
+impl Vent {
+    /// Create a new vent.
+    pub fn new(name: impl Into<String>, position: Vector2) -> Vent {
+        Vent {
+            name: name.into(),
+            position,
+            connections: Vec::new(),
+        }
+    }
+
+    /// The name of the vent.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Where the vent is.
+    pub fn position(&self) -> Vector2 {
+        self.position
+    }
+
+    /// The names of the vents this vent leads to.
+    pub fn connections(&self) -> &[String] {
+        &self.connections
+    }
+
+    /// Connect this vent to another vent.
+    pub fn connect(&mut self, name: impl Into<String>) {
+        self.connections.push(name.into());
+    }
+}
+
+/// Somewhere a task is done.
+#[derive(Clone, Debug, Encode, Decode)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Console {
+    task: Task,
+    position: Vector2,
+}
+
+impl Console {
+    /// Create a new console.
+    pub fn new(task: Task, position: Vector2) -> Console {
+        Console { task, position }
+    }
+
+    /// The task done at this console.
+    pub fn task(&self) -> &Task {
+        &self.task
+    }
+
+    /// Where the console is.
+    pub fn position(&self) -> Vector2 {
+        self.position
+    }
+}
+
+/// An error that can occur when loading a map.
+#[derive(Clone, Debug)]
+pub enum Error {
+    /// The RON could not be parsed.
+    Ron(String),
+    /// A wall has less than three vertices.
+    Wall(usize),
+    /// A wall is not convex, crosses itself or has no area.
+    Concave(usize),
+    /// A room has less than three vertices.
+    Room(String),
+    /// Two vents have the same name.
+    DuplicateVent(String),
+    /// A vent is connected to itself, or a vent that doesn't exist.
+    Connection(String, String),
+    /// There are no spawn points.
+    NoSpawns,
+    /// Something is somewhere that isn't a finite number.
+    NotFinite(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Ron(e) => write!(f, "invalid map file: {}", e),
+            Error::Wall(i) => write!(f, "wall {} has less than three vertices", i),
+            Error::Concave(i) => write!(f, "wall {} is not convex", i),
+            Error::Room(name) => write!(f, "room \"{}\" has less than three vertices", name),
+            Error::DuplicateVent(name) => write!(f, "there is more than one vent named \"{}\"", name),
+            Error::Connection(from, to) => {
+                write!(f, "vent \"{}\" can't be connected to vent \"{}\"", from, to)
+            }
+            Error::NoSpawns => write!(f, "there are no spawn points"),
+            Error::NotFinite(what) => write!(f, "{} has a position that isn't finite", what),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
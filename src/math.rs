@@ -1,6 +1,7 @@
 pub use cgmath::prelude::*;
 
 pub type FLOAT = f64;
+pub use std::f64::consts;
 
 pub type Vector2 = cgmath::Vector2<FLOAT>;
 pub type Vector3 = cgmath::Vector3<FLOAT>;
@@ -0,0 +1,88 @@
+//! The funnel algorithm.
+//!
+//! A* finds which cells to walk through, but walking between the middles of
+//! every portal looks drunk. The funnel algorithm pulls the path tight like a
+//! string, keeping a funnel of everywhere that can still be seen from the last
+//! corner. When a portal narrows the funnel so much that one side crosses
+//! over the other, that side becomes the next corner.
+//!
+//! See [Simple Stupid Funnel Algorithm](http://digestingduck.blogspot.com/2010/03/simple-stupid-funnel-algorithm.html).
+
+use crate::math::*;
+
+/// Get twice the signed area of the triangle `a, b, c`.
+///
+/// This is positive if `c` is to the left of `a -> b`.
+fn area2(a: Vector2, b: Vector2, c: Vector2) -> FLOAT {
+    (b - a).perp_dot(c - a)
+}
+
+/// Pull a path from `from` to `to` tight through a list of portals.
+///
+/// Every portal is a `(right, left)` pair of points, as seen when walking
+/// through it. The path starts at `from`, ends at `to`, and has a point at
+/// every corner it bends around.
+pub fn string_pull(from: Vector2, to: Vector2, portals: &[(Vector2, Vector2)]) -> Vec<Vector2> {
+    let portals = std::iter::once((from, from))
+        .chain(portals.iter().copied())
+        .chain(std::iter::once((to, to)))
+        .collect::<Vec<_>>();
+
+    let mut path = vec![from];
+
+    let mut apex = from;
+    let mut right = from;
+    let mut left = from;
+    let (mut right_index, mut left_index) = (0, 0);
+
+    let mut i = 1;
+    while i < portals.len() {
+        let (next_right, next_left) = portals[i];
+
+        // try to narrow the right side of the funnel
+        if area2(apex, right, next_right) >= 0.0 {
+            if apex == right || area2(apex, left, next_right) < 0.0 {
+                right = next_right;
+                right_index = i;
+            } else {
+                // the right side crossed the left side, so the left side is a
+                // corner
+                path.push(left);
+
+                apex = left;
+                right = apex;
+                right_index = left_index;
+
+                i = left_index + 1;
+                continue;
+            }
+        }
+
+        // try to narrow the left side of the funnel
+        if area2(apex, left, next_left) <= 0.0 {
+            if apex == left || area2(apex, right, next_left) > 0.0 {
+                left = next_left;
+                left_index = i;
+            } else {
+                // the left side crossed the right side, so the right side is
+                // a corner
+                path.push(right);
+
+                apex = right;
+                left = apex;
+                left_index = right_index;
+
+                i = right_index + 1;
+                continue;
+            }
+        }
+
+        i += 1;
+    }
+
+    if path.last() != Some(&to) {
+        path.push(to);
+    }
+
+    path
+}
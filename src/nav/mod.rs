@@ -0,0 +1,410 @@
+//! Navigation.
+//!
+//! A [`NavMesh`] covers everywhere a player can stand on a map. It is built
+//! for players of a certain radius, so every point on the mesh is at least
+//! that far away from a wall. Paths are found across the mesh with A*, and
+//! then pulled tight with a [funnel](funnel) so they hug corners instead of
+//! zig-zagging through the middle of every cell.
+//!
+//! The mesh is made by laying a grid over the map and keeping the cells that
+//! are inside of a room and far enough from every wall. Runs of cells in the
+//! same row are merged into a single rectangle, and rectangles in neighbouring
+//! rows are linked wherever they touch.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::fmt;
+
+use crate::collide::broad::{Aabb, Grid};
+use crate::collide::{contains_point, Geometry as _, Polygon};
+use crate::map::Map;
+use crate::math::*;
+
+pub mod funnel;
+
+/// The most cells the grid laid over a map can have, before they are merged.
+pub const MAX_GRID_CELLS: usize = 1 << 20;
+
+/// An error that can occur when building a navigation mesh.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// A floor polygon has a vertex that is not a finite number.
+    NotFinite(usize),
+    /// The floor is too big for the cell size, and would need more than
+    /// [`MAX_GRID_CELLS`] cells.
+    TooBig,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotFinite(i) => write!(f, "floor {} has a vertex that is not a finite number", i),
+            Error::TooBig => write!(f, "the floor needs more than {} cells", MAX_GRID_CELLS),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A rectangle of the navigation mesh.
+#[derive(Clone, Debug)]
+pub struct Cell {
+    min: Vector2,
+    max: Vector2,
+    links: Vec<Link>,
+}
+
+impl Cell {
+    /// The bottom-left corner.
+    pub fn min(&self) -> Vector2 {
+        self.min
+    }
+
+    /// The top-right corner.
+    pub fn max(&self) -> Vector2 {
+        self.max
+    }
+
+    /// The cell as a polygon, for drawing.
+    pub fn polygon(&self) -> Polygon {
+        vec![
+            self.min,
+            Vector2::new(self.max.x, self.min.y),
+            self.max,
+            Vector2::new(self.min.x, self.max.y),
+        ]
+        .into()
+    }
+
+    /// Check if a point is in the cell.
+    pub fn contains(&self, point: Vector2) -> bool {
+        point.x >= self.min.x && point.x <= self.max.x && point.y >= self.min.y && point.y <= self.max.y
+    }
+
+    /// Move a point to the closest point in the cell.
+    pub fn clamp(&self, point: Vector2) -> Vector2 {
+        Vector2::new(
+            point.x.clamp(self.min.x, self.max.x),
+            point.y.clamp(self.min.y, self.max.y),
+        )
+    }
+}
+
+/// Where one cell leads into another.
+#[derive(Clone, Copy, Debug)]
+struct Link {
+    to: usize,
+    portal: (Vector2, Vector2),
+}
+
+/// A navigation mesh.
+#[derive(Clone, Debug)]
+pub struct NavMesh {
+    cells: Vec<Cell>,
+    radius: FLOAT,
+}
+
+impl NavMesh {
+    /// Build a navigation mesh for a map.
+    ///
+    /// The walkable area is every room on the map.
+    pub fn from_map(map: &Map, radius: FLOAT, cell_size: FLOAT) -> Result<NavMesh, Error> {
+        let floor = map.rooms().iter().map(|room| room.bounds().clone()).collect::<Vec<_>>();
+
+        NavMesh::new(&floor, map.walls(), radius, cell_size)
+    }
+
+    /// Build a navigation mesh.
+    ///
+    /// `floor` is the walkable area, and can be concave. `radius` is the
+    /// radius of the players that will walk on the mesh, and `cell_size` is how
+    /// finely the mesh is cut; smaller cells can squeeze through tighter
+    /// gaps, but take longer to build and search.
+    ///
+    /// Fails if the floor isn't finite, or is so big for the cell size that
+    /// it would need more than [`MAX_GRID_CELLS`] cells.
+    ///
+    /// # Panics
+    /// Panics if `cell_size` is not a positive, finite number, or `radius` is
+    /// negative or not finite.
+    pub fn new(floor: &[Polygon], walls: &[Polygon], radius: FLOAT, cell_size: FLOAT) -> Result<NavMesh, Error> {
+        assert!(
+            cell_size > 0.0 && cell_size.is_finite(),
+            "navigation cells must have a positive size!"
+        );
+        assert!(radius >= 0.0 && radius.is_finite(), "players must have a finite radius!");
+
+        let not_finite = |polygon: &Polygon| polygon.vertices().iter().any(|v| !v.x.is_finite() || !v.y.is_finite());
+
+        if let Some(i) = floor.iter().position(not_finite) {
+            return Err(Error::NotFinite(i));
+        }
+
+        let bounds = match floor
+            .iter()
+            .filter(|polygon| !polygon.vertices().is_empty())
+            .map(Aabb::from_geometry)
+            .reduce(|a, b| Aabb::new(
+                Vector2::new(a.min().x.min(b.min().x), a.min().y.min(b.min().y)),
+                Vector2::new(a.max().x.max(b.max().x), a.max().y.max(b.max().y)),
+            )) {
+            Some(bounds) => bounds,
+            None => return Ok(NavMesh { cells: Vec::new(), radius }),
+        };
+
+        // checked before converting, since a huge float saturates instead of
+        // failing, and one side on its own can be huge on a floor with no area
+        let columns = ((bounds.max().x - bounds.min().x) / cell_size).ceil();
+        let rows = ((bounds.max().y - bounds.min().y) / cell_size).ceil();
+        let max = MAX_GRID_CELLS as FLOAT;
+
+        if columns > max || rows > max || columns * rows > max {
+            return Err(Error::TooBig);
+        }
+
+        let (columns, rows) = (columns as usize, rows as usize);
+
+        let mut broad = Grid::new(cell_size.max(radius) * 4.0);
+        for (i, wall) in walls.iter().enumerate() {
+            broad.insert(wall, i);
+        }
+
+        let walkable = |x: usize, y: usize| {
+            let min = bounds.min() + Vector2::new(x as FLOAT, y as FLOAT) * cell_size;
+            let max = min + Vector2::new(cell_size, cell_size);
+            let center = (min + max) / 2.0;
+
+            if !floor.iter().any(|polygon| contains_point(polygon.vertices(), center)) {
+                return false;
+            }
+
+            // the whole cell has to be clear, so grow it by the radius
+            let grown: Polygon = vec![
+                min - Vector2::new(radius, radius),
+                Vector2::new(max.x + radius, min.y - radius),
+                max + Vector2::new(radius, radius),
+                Vector2::new(min.x - radius, max.y + radius),
+            ]
+            .into();
+
+            broad
+                .query(&Aabb::from_geometry(&grown))
+                .into_iter()
+                .all(|handle| !grown.collide(&walls[*broad.get(handle).unwrap()]))
+        };
+
+        // merge runs of walkable cells in every row
+        let mut cells = Vec::new();
+        let mut row_starts = Vec::with_capacity(rows + 1);
+
+        for y in 0..rows {
+            row_starts.push(cells.len());
+
+            let row: Vec<bool> = (0..columns).map(|x| walkable(x, y)).collect();
+
+            let mut x = 0;
+            while x < columns {
+                if !row[x] {
+                    x += 1;
+                    continue;
+                }
+
+                let start = x;
+                while x < columns && row[x] {
+                    x += 1;
+                }
+
+                let min = bounds.min() + Vector2::new(start as FLOAT, y as FLOAT) * cell_size;
+                let max = bounds.min() + Vector2::new(x as FLOAT, (y + 1) as FLOAT) * cell_size;
+
+                cells.push(Cell {
+                    min,
+                    max,
+                    links: Vec::new(),
+                });
+            }
+        }
+
+        row_starts.push(cells.len());
+
+        // link runs that touch in neighbouring rows
+        for y in 1..rows {
+            for below in row_starts[y - 1]..row_starts[y] {
+                for above in row_starts[y]..row_starts[y + 1] {
+                    let left = cells[below].min.x.max(cells[above].min.x);
+                    let right = cells[below].max.x.min(cells[above].max.x);
+
+                    if right <= left {
+                        continue;
+                    }
+
+                    let height = cells[above].min.y;
+                    let a = Vector2::new(left, height);
+                    let b = Vector2::new(right, height);
+
+                    // walking up, the right side of the portal is the
+                    // right end of it
+                    cells[below].links.push(Link {
+                        to: above,
+                        portal: (b, a),
+                    });
+                    cells[above].links.push(Link {
+                        to: below,
+                        portal: (a, b),
+                    });
+                }
+            }
+        }
+
+        Ok(NavMesh { cells, radius })
+    }
+
+    /// The radius of the players this mesh was built for.
+    pub fn radius(&self) -> FLOAT {
+        self.radius
+    }
+
+    /// The cells of the mesh.
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    /// Find the cell a point is in.
+    pub fn cell_at(&self, point: Vector2) -> Option<usize> {
+        self.cells.iter().position(|cell| cell.contains(point))
+    }
+
+    /// Find the closest point on the mesh to `point`, and the cell it is in.
+    pub fn nearest(&self, point: Vector2) -> Option<(usize, Vector2)> {
+        self.cells
+            .iter()
+            .enumerate()
+            .map(|(i, cell)| (i, cell.clamp(point)))
+            .fold(None, |best: Option<(usize, Vector2)>, (i, p)| match best {
+                Some((_, b)) if (b - point).magnitude2() <= (p - point).magnitude2() => best,
+                _ => Some((i, p)),
+            })
+    }
+
+    /// Find a path from `from` to `to`.
+    ///
+    /// Points that are off of the mesh are moved to the closest point on it
+    /// first. The path starts and ends at those points, and includes every
+    /// corner in between. Returns `None` if there is no way to get there.
+    pub fn find_path(&self, from: Vector2, to: Vector2) -> Option<Vec<Vector2>> {
+        let (start, from) = self.nearest(from)?;
+        let (goal, to) = self.nearest(to)?;
+
+        let corridor = self.search(start, goal, from, to)?;
+
+        // every portal is oriented so the first point is on the right of the
+        // direction of travel
+        let portals = corridor
+            .windows(2)
+            .map(|pair| {
+                self.cells[pair[0]]
+                    .links
+                    .iter()
+                    .find(|link| link.to == pair[1])
+                    .expect("corridors only go through linked cells")
+                    .portal
+            })
+            .collect::<Vec<_>>();
+
+        Some(funnel::string_pull(from, to, &portals))
+    }
+
+    /// Get the length of the shortest path from `from` to `to`.
+    ///
+    /// This is useful to check that a player could have actually walked
+    /// somewhere in the time they say they did.
+    pub fn distance(&self, from: Vector2, to: Vector2) -> Option<FLOAT> {
+        self.find_path(from, to).map(|path| {
+            path.windows(2).map(|pair| (pair[1] - pair[0]).magnitude()).sum()
+        })
+    }
+
+    /// Find the cells to walk through with A*.
+    fn search(&self, start: usize, goal: usize, from: Vector2, to: Vector2) -> Option<Vec<usize>> {
+        let mut cost = vec![FLOAT::INFINITY; self.cells.len()];
+        let mut position = vec![from; self.cells.len()];
+        let mut parent = vec![usize::MAX; self.cells.len()];
+        let mut open = BinaryHeap::new();
+
+        cost[start] = 0.0;
+        open.push(Node {
+            estimate: (to - from).magnitude(),
+            cell: start,
+        });
+
+        while let Some(Node { cell, estimate }) = open.pop() {
+            if cell == goal {
+                let mut corridor = vec![goal];
+
+                while let Some(&last) = corridor.last() {
+                    if last == start {
+                        break;
+                    }
+
+                    corridor.push(parent[last]);
+                }
+
+                corridor.reverse();
+                return Some(corridor);
+            }
+
+            // skip stale entries
+            if estimate > cost[cell] + (to - position[cell]).magnitude() {
+                continue;
+            }
+
+            for link in self.cells[cell].links.iter() {
+                let entry = (link.portal.0 + link.portal.1) / 2.0;
+                let next = cost[cell] + (entry - position[cell]).magnitude();
+
+                if next < cost[link.to] {
+                    cost[link.to] = next;
+                    position[link.to] = entry;
+                    parent[link.to] = cell;
+
+                    open.push(Node {
+                        estimate: next + (to - entry).magnitude(),
+                        cell: link.to,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// A cell waiting to be searched.
+struct Node {
+    estimate: FLOAT,
+    cell: usize,
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Node) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Node) -> Ordering {
+        // the heap pops the biggest node, so the cheapest node is the biggest
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.cell.cmp(&self.cell))
+    }
+}
@@ -0,0 +1,261 @@
+//! Bit packing.
+//!
+//! Most of a movement update is small numbers and flags, and byte-aligned
+//! integers waste most of their bits on zeroes. A [`BitWriter`] writes
+//! integers with exactly as many bits as they need, and floats
+//! [`Quantize`]d to a range and precision, one after the other with no
+//! padding between them. A [`BitReader`] reads them back in the same order.
+//!
+//! Bits are written lowest first, filling each byte from its lowest bit.
+//! The bytes of a `BitWriter` can be written with
+//! [`CursorMut::write`](super::encode::CursorMut::write), and read back out
+//! of a cursor with [`Cursor::take`](super::decode::Cursor::take), to mix
+//! bit-packed and byte-aligned data in one message.
+
+use crate::math::*;
+use crate::net::binary::{decode, encode};
+
+/// How a float is quantized to an integer.
+///
+/// Floats are clamped to the range `min..=max`, which is split into
+/// `2^bits - 1` even steps, so both ends of the range are exact.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quantize {
+    min: FLOAT,
+    max: FLOAT,
+    bits: u32,
+}
+
+impl Quantize {
+    /// Quantize to `bits` bits between `min` and `max`.
+    ///
+    /// # Panics
+    /// Panics if `bits` is not between 1 and 32, or `min` isn't less than
+    /// `max`.
+    pub fn new(min: FLOAT, max: FLOAT, bits: u32) -> Quantize {
+        assert!((1..=32).contains(&bits), "can only quantize to between 1 and 32 bits");
+        assert!(min < max, "the range to quantize to is empty");
+
+        Quantize { min, max, bits }
+    }
+
+    /// Quantize between `min` and `max` with steps no bigger than
+    /// `precision`, using as few bits as possible.
+    ///
+    /// # Panics
+    /// Panics if that would take more than 32 bits.
+    pub fn with_precision(min: FLOAT, max: FLOAT, precision: FLOAT) -> Quantize {
+        let steps = ((max - min) / precision).ceil() as u64;
+        let bits = 64 - steps.leading_zeros();
+
+        Quantize::new(min, max, bits.max(1))
+    }
+
+    /// The smallest value.
+    pub fn min(&self) -> FLOAT {
+        self.min
+    }
+
+    /// The biggest value.
+    pub fn max(&self) -> FLOAT {
+        self.max
+    }
+
+    /// The number of bits a value takes.
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    /// The size of a step between two quantized values.
+    pub fn precision(&self) -> FLOAT {
+        (self.max - self.min) / self.steps() as FLOAT
+    }
+
+    /// Quantize a value, rounding to the nearest step.
+    pub fn quantize(&self, value: FLOAT) -> u64 {
+        let value = value.clamp(self.min, self.max);
+
+        ((value - self.min) / self.precision()).round() as u64
+    }
+
+    /// Turn a quantized value back into a float.
+    pub fn dequantize(&self, value: u64) -> FLOAT {
+        self.min + value.min(self.steps()) as FLOAT * self.precision()
+    }
+
+    fn steps(&self) -> u64 {
+        (1 << self.bits) - 1
+    }
+}
+
+/// Writes values bit by bit.
+#[derive(Clone, Debug, Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    /// Create a new, empty `BitWriter`.
+    pub fn new() -> BitWriter {
+        BitWriter::default()
+    }
+
+    /// The number of bits written so far.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if nothing has been written yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Write the lowest `bits` bits of `value`.
+    ///
+    /// This fails if `value` doesn't fit in `bits` bits.
+    pub fn write_bits(&mut self, value: u64, bits: u32) -> Result<(), encode::Error> {
+        if bits > 64 || (bits < 64 && value >> bits != 0) {
+            return Err(encode::Error::bit_overflow(bits));
+        }
+
+        for i in 0..bits {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+
+            if value >> i & 1 == 1 {
+                self.bytes[self.len / 8] |= 1 << (self.len % 8);
+            }
+
+            self.len += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Write a signed value in `bits` bits, as two's complement.
+    ///
+    /// This fails if `value` doesn't fit in `bits` bits.
+    pub fn write_signed(&mut self, value: i64, bits: u32) -> Result<(), encode::Error> {
+        if bits == 0 && value == 0 {
+            return Ok(());
+        }
+
+        if bits == 0 || bits > 64 {
+            return Err(encode::Error::bit_overflow(bits));
+        }
+
+        let (min, max) = (-1i64 << (bits - 1), !(-1i64 << (bits - 1)));
+
+        if value < min || value > max {
+            return Err(encode::Error::bit_overflow(bits));
+        }
+
+        let mask = u64::MAX >> (64 - bits);
+        self.write_bits(value as u64 & mask, bits)
+    }
+
+    /// Write a single bit.
+    pub fn write_bool(&mut self, value: bool) {
+        // a bool always fits in a bit
+        let _ = self.write_bits(value as u64, 1);
+    }
+
+    /// Write a quantized float.
+    ///
+    /// Values outside the range of `quantize` are clamped to it.
+    pub fn write_float(&mut self, value: FLOAT, quantize: Quantize) {
+        // the quantized value always fits
+        let _ = self.write_bits(quantize.quantize(value), quantize.bits);
+    }
+
+    /// Write a vector with both axes quantized the same way.
+    pub fn write_vector2(&mut self, value: Vector2, quantize: Quantize) {
+        self.write_float(value.x, quantize);
+        self.write_float(value.y, quantize);
+    }
+
+    /// Get the bytes written, padded with zeroes to a whole byte.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl From<BitWriter> for Vec<u8> {
+    fn from(writer: BitWriter) -> Vec<u8> {
+        writer.into_bytes()
+    }
+}
+
+/// Reads values bit by bit.
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    /// Create a new `BitReader`.
+    pub fn new(bytes: &'a [u8]) -> BitReader<'a> {
+        BitReader { bytes, position: 0 }
+    }
+
+    /// The number of bits read so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The number of bits left to read, including padding.
+    pub fn remaining(&self) -> usize {
+        self.bytes.len() * 8 - self.position
+    }
+
+    /// Read `bits` bits.
+    pub fn read_bits(&mut self, bits: u32) -> Result<u64, decode::Error> {
+        if bits > 64 {
+            return Err(decode::Error::overflow().at(self.position / 8));
+        }
+
+        if bits as usize > self.remaining() {
+            return Err(decode::Error::unexpected_end(bits as usize, self.remaining()).at(self.position / 8));
+        }
+
+        let mut value = 0;
+
+        for i in 0..bits {
+            let bit = self.bytes[self.position / 8] >> (self.position % 8) & 1;
+            value |= (bit as u64) << i;
+            self.position += 1;
+        }
+
+        Ok(value)
+    }
+
+    /// Read a signed value from `bits` bits of two's complement.
+    pub fn read_signed(&mut self, bits: u32) -> Result<i64, decode::Error> {
+        if bits == 0 {
+            return Ok(0);
+        }
+
+        let value = self.read_bits(bits)?;
+
+        // sign extend
+        let shift = 64 - bits;
+        Ok(((value << shift) as i64) >> shift)
+    }
+
+    /// Read a single bit.
+    pub fn read_bool(&mut self) -> Result<bool, decode::Error> {
+        Ok(self.read_bits(1)? == 1)
+    }
+
+    /// Read a quantized float.
+    pub fn read_float(&mut self, quantize: Quantize) -> Result<FLOAT, decode::Error> {
+        Ok(quantize.dequantize(self.read_bits(quantize.bits)?))
+    }
+
+    /// Read a vector with both axes quantized the same way.
+    pub fn read_vector2(&mut self, quantize: Quantize) -> Result<Vector2, decode::Error> {
+        Ok(Vector2::new(self.read_float(quantize)?, self.read_float(quantize)?))
+    }
+}
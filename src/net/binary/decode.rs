@@ -1,69 +1,669 @@
 use std::cmp::min;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+pub use among_us_derive::{BorrowDecode, Decode};
+
+/// Limits on what a [`Cursor`] will decode.
+///
+/// Lengths are sent over the wire, so without limits a malicious peer can
+/// tell a client to allocate as much memory as it wants. Every string and
+/// sequence decoded is checked against these limits before anything is
+/// allocated for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    max_alloc: usize,
+    max_string: usize,
+    max_len: usize,
+}
+
+impl Limits {
+    /// Create the default limits.
+    ///
+    /// This allows 16MiB to be allocated in total, strings up to 64KiB long
+    /// and sequences up to 65536 items long.
+    pub fn new() -> Limits {
+        Limits {
+            max_alloc: 16 * 1024 * 1024,
+            max_string: 64 * 1024,
+            max_len: 64 * 1024,
+        }
+    }
+
+    /// Create limits that allow anything.
+    ///
+    /// Don't use these on untrusted data.
+    pub fn unlimited() -> Limits {
+        Limits {
+            max_alloc: usize::MAX,
+            max_string: usize::MAX,
+            max_len: usize::MAX,
+        }
+    }
+
+    /// The maximum number of bytes that can be allocated in total.
+    pub fn max_alloc(&self) -> usize {
+        self.max_alloc
+    }
+
+    /// The maximum length of a string, in bytes.
+    pub fn max_string(&self) -> usize {
+        self.max_string
+    }
+
+    /// The maximum number of items in a sequence.
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Set the maximum number of bytes that can be allocated in total.
+    pub fn set_max_alloc(&mut self, max_alloc: usize) {
+        self.max_alloc = max_alloc;
+    }
+
+    /// Set the maximum length of a string, in bytes.
+    pub fn set_max_string(&mut self, max_string: usize) {
+        self.max_string = max_string;
+    }
+
+    /// Set the maximum number of items in a sequence.
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits::new()
+    }
+}
+
+/// Where a [`Cursor`] reads its bytes from.
+///
+/// This is implemented for anything that is bytes in memory, and for
+/// [`Reader`], which reads from any [`io::Read`].
+pub trait Source {
+    /// Read bytes from `position` into `buf`, returning how many were read.
+    ///
+    /// Fewer bytes than `buf` can hold are only read at the end of the input.
+    fn read_at(&mut self, position: usize, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// How many bytes are left after `position`, if that is known.
+    fn remaining(&self, position: usize) -> Option<usize>;
+}
+
+impl<T> Source for T
+where T: AsRef<[u8]> {
+    fn read_at(&mut self, position: usize, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = self.as_ref();
+
+        // get the end point of the buffer
+        let end = min(position + buf.len(), inner.len());
+
+        // get the slice
+        let slice = &inner[position..end];
+
+        // copy the slice
+        buf[..slice.len()].copy_from_slice(slice);
+
+        // return the length
+        Ok(slice.len())
+    }
+
+    fn remaining(&self, position: usize) -> Option<usize> {
+        Some(self.as_ref().len() - position)
+    }
+}
+
+/// A [`Source`] that reads straight from an [`io::Read`], like a file or a
+/// `TcpStream`.
+///
+/// Nothing is buffered, so wrap slow readers in an [`io::BufReader`]. How
+/// much input is left is never known, so only the [`Limits`] of the cursor
+/// stop a peer from making it allocate too much.
+pub struct Reader<R>
+where R: io::Read {
+    inner: R,
+}
+
+impl<R> Reader<R>
+where R: io::Read {
+    /// Create a new reader.
+    pub fn new(inner: R) -> Reader<R> {
+        Reader { inner }
+    }
+
+    /// Get the [`io::Read`] back.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> Source for Reader<R>
+where R: io::Read {
+    fn read_at(&mut self, _position: usize, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+
+        // the reader only moves forward, so keep reading until the buffer is
+        // full or the reader ends
+        while read < buf.len() {
+            match self.inner.read(&mut buf[read..]) {
+                Ok(0) => break,
+                Ok(n) => read += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(read)
+    }
+
+    fn remaining(&self, _position: usize) -> Option<usize> {
+        None
+    }
+}
 
 /// The binary cursor.
 ///
-/// The `Cursor` is designed to read a sequence of bytes sequentially.
+/// The `Cursor` is designed to read a sequence of bytes sequentially. It
+/// keeps track of how much has been allocated by what it has decoded, and
+/// stops decoding once its [`Limits`] are reached.
+///
+/// Cursors over bytes in memory can also move around their input and borrow
+/// from it. Cursors over a [`Reader`] can only move forward.
 pub struct Cursor<T>
-where T: AsRef<[u8]> {
+where T: Source {
     inner: T,
     cursor: usize,
+    limits: Limits,
+    allocated: usize,
+}
+
+impl<R> Cursor<Reader<R>>
+where R: io::Read {
+    /// Create a new binary cursor that reads from `reader`, with the default
+    /// [`Limits`].
+    pub fn from_reader(reader: R) -> Cursor<Reader<R>> {
+        Cursor::new(Reader::new(reader))
+    }
 }
 
 impl<T> Cursor<T>
-where T: AsRef<[u8]> {
-    /// Create a new binary cursor.
+where T: Source {
+    /// Create a new binary cursor with the default [`Limits`].
     pub fn new(inner: T) -> Cursor<T> {
+        Cursor::with_limits(inner, Limits::default())
+    }
+
+    /// Create a new binary cursor.
+    pub fn with_limits(inner: T, limits: Limits) -> Cursor<T> {
         Cursor {
             inner,
             cursor: 0,
+            limits,
+            allocated: 0,
+        }
+    }
+
+    /// Get what the cursor is reading from.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// The limits of the cursor.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Set the limits of the cursor.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// How many bytes have been allocated so far.
+    pub fn allocated(&self) -> usize {
+        self.allocated
+    }
+
+    /// Forget everything that has been allocated so far.
+    ///
+    /// A cursor that reads one message after another from a stream should
+    /// call this between messages, so the limits apply to each message.
+    pub fn reset_allocated(&mut self) {
+        self.allocated = 0;
+    }
+
+    /// Charge an allocation of `bytes` against the limits.
+    pub fn allocate(&mut self, bytes: usize) -> Result<(), Error> {
+        let allocated = self.allocated.saturating_add(bytes);
+
+        if allocated > self.limits.max_alloc {
+            return Err(Error::alloc_limit(allocated, self.limits.max_alloc).at(self.cursor));
+        }
+
+        self.allocated = allocated;
+
+        Ok(())
+    }
+
+    /// Check that a string of `len` bytes can be decoded, and charge it
+    /// against the limits.
+    pub fn reserve_string(&mut self, len: usize) -> Result<(), Error> {
+        if len > self.limits.max_string {
+            return Err(Error::string_limit(len, self.limits.max_string).at(self.cursor));
+        }
+
+        if let Some(remaining) = self.inner.remaining(self.cursor) {
+            if len > remaining {
+                return Err(Error::unexpected_end(len, remaining).at(self.cursor));
+            }
         }
+
+        self.allocate(len)
+    }
+
+    /// Check that a sequence of `len` items of `U` can be decoded, and charge
+    /// it against the limits.
+    ///
+    /// This returns how many items it is safe to allocate up front. Any more
+    /// than that should only be allocated as they are decoded, as there might
+    /// not be enough input left for all of them.
+    pub fn reserve_seq<U>(&mut self, len: usize) -> Result<usize, Error> {
+        if len > self.limits.max_len {
+            return Err(Error::len_limit(len, self.limits.max_len).at(self.cursor));
+        }
+
+        self.allocate(len.saturating_mul(std::mem::size_of::<U>()))?;
+
+        Ok(min(len, self.inner.remaining(self.cursor).unwrap_or(0)))
+    }
+
+    /// Where the cursor is, in bytes from the start of the input.
+    pub fn position(&self) -> usize {
+        self.cursor
     }
 
     /// Reads a sequence of bytes.
     ///
-    /// This returns how many bytes were read from the cursor. In a networking
+    /// This returns how many bytes were read from the cursor, which is only
+    /// less than the length of `buf` at the end of the input. In a networking
     /// scenario, it is implied that all source data will be destructed after
     /// the deserialize functions are called.
-    pub fn read(&mut self, buf: &mut [u8]) -> usize {
-        let inner = self.inner.as_ref();
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        let read = self.inner.read_at(self.cursor, buf).map_err(|e| Error::io(e.kind()).at(self.cursor))?;
 
-        // get the end point of the buffer
-        let end = min(self.cursor + buf.len(), inner.len());
+        // advance past what was read
+        self.cursor += read;
 
-        // get the slice
+        Ok(read)
+    }
+
+    /// Decode a type from the `Cursor`.
+    pub fn decode<U>(&mut self) -> Result<U, Error>
+    where U: Decode {
+        let offset = self.cursor;
+
+        U::decode(self).map_err(|e| e.at(offset).of::<U>())
+    }
+}
+
+impl<T> Cursor<T>
+where T: AsRef<[u8]> {
+    /// The number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.inner.as_ref().len() - self.cursor
+    }
+
+    /// Move the cursor to `position` bytes from the start of the input.
+    ///
+    /// Seeking to the very end is fine, but not past it.
+    pub fn seek(&mut self, position: usize) -> Result<(), Error> {
+        let len = self.inner.as_ref().len();
+
+        if position > len {
+            return Err(Error::unexpected_end(position, len).at(self.cursor));
+        }
+
+        self.cursor = position;
+
+        Ok(())
+    }
+
+    /// Skip over `len` bytes.
+    pub fn skip(&mut self, len: usize) -> Result<(), Error> {
+        if len > self.remaining() {
+            return Err(Error::unexpected_end(len, self.remaining()).at(self.cursor));
+        }
+
+        self.cursor += len;
+
+        Ok(())
+    }
+
+    /// Read a sequence of bytes without advancing the cursor.
+    ///
+    /// This returns how many bytes were read.
+    pub fn peek(&self, buf: &mut [u8]) -> usize {
+        let inner = self.inner.as_ref();
+        let end = min(self.cursor + buf.len(), inner.len());
         let slice = &inner[self.cursor..end];
 
-        // copy the slice
-        (&mut buf[..slice.len()]).copy_from_slice(slice);
+        buf[..slice.len()].copy_from_slice(slice);
 
-        // return the length
         slice.len()
     }
 
-    /// Decode a type from the `Cursor`.
-    pub fn decode<U>(&mut self) -> Result<U, Error> 
+    /// Decode a type without advancing the cursor.
+    ///
+    /// Nothing that is allocated by the type is charged against the limits.
+    pub fn peek_decode<U>(&mut self) -> Result<U, Error>
     where U: Decode {
-        U::decode(self)
+        let (cursor, allocated) = (self.cursor, self.allocated);
+        let value = self.decode();
+
+        self.cursor = cursor;
+        self.allocated = allocated;
+
+        value
+    }
+
+    /// Decode something from the next `len` bytes with a sub-cursor.
+    ///
+    /// The sub-cursor can't read past those `len` bytes, and shares the limits
+    /// of this cursor. Afterwards, this cursor is moved past all `len` bytes,
+    /// no matter how many of them `f` read or whether it failed, so it stays
+    /// in sync with the messages after it.
+    pub fn nested<U, F>(&mut self, len: usize, f: F) -> Result<U, Error>
+    where F: FnOnce(&mut Cursor<&[u8]>) -> Result<U, Error> {
+        if len > self.remaining() {
+            return Err(Error::unexpected_end(len, self.remaining()).at(self.cursor));
+        }
+
+        let start = self.cursor;
+        self.cursor += len;
+
+        let mut sub = Cursor {
+            inner: &self.inner.as_ref()[start..start + len],
+            cursor: 0,
+            limits: self.limits,
+            allocated: self.allocated,
+        };
+
+        let value = f(&mut sub);
+        self.allocated = sub.allocated;
+
+        // offsets in the sub-cursor are from the start of the sub-cursor
+        value.map_err(|mut e| {
+            e.offset = Some(start + e.offset.unwrap_or(0));
+            e
+        })
+    }
+
+    /// Read a Hazel message.
+    ///
+    /// Hazel messages are framed as `[len: u16][tag: u8][payload]`, where
+    /// `len` is the length of the payload. `f` is given the tag and a
+    /// sub-cursor over the payload, and can ignore the payload of a message
+    /// it doesn't know to skip it.
+    pub fn message<U, F>(&mut self, f: F) -> Result<U, Error>
+    where F: FnOnce(u8, &mut Cursor<&[u8]>) -> Result<U, Error> {
+        let len = self.decode::<u16>()?;
+        let tag = self.decode::<u8>()?;
+
+        self.nested(len as usize, |sub| f(tag, sub))
+    }
+
+    /// Skip over a Hazel message, returning its tag.
+    pub fn skip_message(&mut self) -> Result<u8, Error> {
+        self.message(|tag, _| Ok(tag))
+    }
+}
+
+impl<'de> Cursor<&'de [u8]> {
+    /// Take `len` bytes straight out of the input, without copying them.
+    pub fn take(&mut self, len: usize) -> Result<&'de [u8], Error> {
+        if len > self.remaining() {
+            return Err(Error::unexpected_end(len, self.remaining()).at(self.cursor));
+        }
+
+        let slice = &self.inner[self.cursor..self.cursor + len];
+        self.cursor += len;
+
+        Ok(slice)
+    }
+
+    /// Decode a type that borrows from the input of the `Cursor`.
+    pub fn borrow_decode<U>(&mut self) -> Result<U, Error>
+    where U: BorrowDecode<'de> {
+        let offset = self.cursor;
+
+        U::borrow_decode(self).map_err(|e| e.at(offset).of::<U>())
     }
 }
 
 /// An error that can occur during decoding.
-pub enum Error {
+///
+/// Along with what went wrong, errors that came from a [`Cursor`] know where
+/// in the input it went wrong and what type was being decoded.
+#[derive(Clone, Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: Option<usize>,
+    type_name: Option<&'static str>,
+    reason: Option<Arc<dyn std::error::Error + Send + Sync>>,
+}
+
+/// What went wrong while decoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
     /// An unexpected end to the bytes was reached.
-    UnexpectedEnd,
+    ///
+    /// For a [`BitReader`](super::bits::BitReader), these are in bits
+    /// instead of bytes.
+    UnexpectedEnd {
+        /// How many bytes were needed.
+        expected: usize,
+        /// How many bytes were left.
+        available: usize,
+    },
     /// A Utf-8 error was found.
     Utf8(std::str::Utf8Error),
+    /// A tag did not match any variant of an enum, `Option` or `bool`.
+    InvalidTag,
+    /// A packed integer was too big for its type.
+    Overflow,
+    /// The value was decoded, but doesn't make sense, like a map that fails
+    /// validation.
+    Invalid,
+    /// The reader failed.
+    Io(io::ErrorKind),
+    /// Decoding would allocate more than the limit.
+    AllocLimit {
+        /// How many bytes would have been allocated in total.
+        requested: usize,
+        /// The limit.
+        limit: usize,
+    },
+    /// A string was longer than the limit.
+    StringLimit {
+        /// The length of the string, in bytes.
+        len: usize,
+        /// The limit.
+        limit: usize,
+    },
+    /// A sequence was longer than the limit.
+    LenLimit {
+        /// The length of the sequence.
+        len: usize,
+        /// The limit.
+        limit: usize,
+    },
 }
 
 impl Error {
+    /// Create a new error.
+    pub fn new(kind: ErrorKind) -> Error {
+        Error {
+            kind,
+            offset: None,
+            type_name: None,
+            reason: None,
+        }
+    }
+
     /// Create a new unexpected end error.
-    pub fn unexpected_end() -> Error {
-        Error::UnexpectedEnd
+    pub fn unexpected_end(expected: usize, available: usize) -> Error {
+        Error::new(ErrorKind::UnexpectedEnd { expected, available })
     }
 
     /// Create a new Utf8 error.
     pub fn utf8(error: std::str::Utf8Error) -> Error {
-        Error::Utf8(error)
+        Error::new(ErrorKind::Utf8(error))
+    }
+
+    /// Create a new invalid tag error.
+    pub fn invalid_tag() -> Error {
+        Error::new(ErrorKind::InvalidTag)
+    }
+
+    /// Create a new overflow error.
+    pub fn overflow() -> Error {
+        Error::new(ErrorKind::Overflow)
+    }
+
+    /// Create a new invalid value error.
+    pub fn invalid() -> Error {
+        Error::new(ErrorKind::Invalid)
+    }
+
+    /// Create a new invalid value error that says why the value is invalid.
+    ///
+    /// The reason is the error's [`source`](std::error::Error::source).
+    pub fn invalid_because<E>(reason: E) -> Error
+    where E: std::error::Error + Send + Sync + 'static {
+        Error {
+            reason: Some(Arc::new(reason)),
+            ..Error::invalid()
+        }
+    }
+
+    /// Create a new io error.
+    pub fn io(kind: io::ErrorKind) -> Error {
+        Error::new(ErrorKind::Io(kind))
+    }
+
+    /// Create a new allocation limit error.
+    pub fn alloc_limit(requested: usize, limit: usize) -> Error {
+        Error::new(ErrorKind::AllocLimit { requested, limit })
+    }
+
+    /// Create a new string limit error.
+    pub fn string_limit(len: usize, limit: usize) -> Error {
+        Error::new(ErrorKind::StringLimit { len, limit })
+    }
+
+    /// Create a new sequence length limit error.
+    pub fn len_limit(len: usize, limit: usize) -> Error {
+        Error::new(ErrorKind::LenLimit { len, limit })
+    }
+
+    /// What went wrong.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Where in the input it went wrong, in bytes from the start.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// The name of the type that was being decoded.
+    pub fn type_name(&self) -> Option<&'static str> {
+        self.type_name
+    }
+
+    /// Set where the error happened, unless something more specific already
+    /// has.
+    pub(super) fn at(mut self, offset: usize) -> Error {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    /// Set the type that was being decoded, unless a more specific one
+    /// already has been.
+    fn of<U>(mut self) -> Error
+    where U: ?Sized {
+        self.type_name.get_or_insert(std::any::type_name::<U>());
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to decode")?;
+
+        if let Some(type_name) = self.type_name {
+            write!(f, " `{}`", type_name)?;
+        }
+
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
+        }
+
+        write!(f, ": {}", self.kind)?;
+
+        if let Some(reason) = &self.reason {
+            write!(f, ": {}", reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        let reason = |error: &Error| error.reason.as_ref().map(|reason| reason.to_string());
+
+        self.kind == other.kind
+            && self.offset == other.offset
+            && self.type_name == other.type_name
+            && reason(self) == reason(other)
+    }
+}
+
+impl Eq for Error {}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnexpectedEnd { expected, available } => {
+                write!(f, "expected {} bytes, but only {} were left", expected, available)
+            }
+            ErrorKind::Utf8(e) => write!(f, "invalid utf-8: {}", e),
+            ErrorKind::InvalidTag => write!(f, "invalid tag"),
+            ErrorKind::Overflow => write!(f, "packed integer is too big"),
+            ErrorKind::Invalid => write!(f, "invalid value"),
+            ErrorKind::Io(kind) => write!(f, "io error: {}", kind),
+            ErrorKind::AllocLimit { requested, limit } => {
+                write!(f, "allocating {} bytes would go over the limit of {}", requested, limit)
+            }
+            ErrorKind::StringLimit { len, limit } => {
+                write!(f, "string of {} bytes is longer than the limit of {}", len, limit)
+            }
+            ErrorKind::LenLimit { len, limit } => {
+                write!(f, "sequence of {} items is longer than the limit of {}", len, limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Utf8(e) => Some(e),
+            _ => self.reason.as_ref().map(|reason| &**reason as _),
+        }
     }
 }
 
@@ -71,5 +671,22 @@ impl Error {
 pub trait Decode: Sized {
     /// Begin the deserialization.
     fn decode<T>(cursor: &mut Cursor<T>) -> Result<Self, Error>
-    where T: AsRef<[u8]>;
+    where T: Source;
+}
+
+/// A type that can be decoded from a [`Cursor`] by borrowing from its input.
+///
+/// This lets strings and bytes be decoded as `&'de str` and `&'de [u8]`
+/// without allocating. Every [`Decode`] type can be borrow decoded too, so
+/// borrowed and owned fields can be mixed.
+pub trait BorrowDecode<'de>: Sized {
+    /// Begin the deserialization.
+    fn borrow_decode(cursor: &mut Cursor<&'de [u8]>) -> Result<Self, Error>;
+}
+
+impl<'de, U> BorrowDecode<'de> for U
+where U: Decode {
+    fn borrow_decode(cursor: &mut Cursor<&'de [u8]>) -> Result<Self, Error> {
+        U::decode(cursor)
+    }
 }
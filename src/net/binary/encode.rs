@@ -1,42 +1,230 @@
-/// A newtype struct that encapsulates a `Vec<u8>`.
-pub struct CursorMut {
-    inner: Vec<u8>,
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+
+pub use among_us_derive::Encode;
+
+/// The binary cursor for writing.
+///
+/// By default, a `CursorMut` writes to a `Vec<u8>`. It can write straight to
+/// any [`io::Write`] too, like a file or a `TcpStream`, without buffering
+/// anything itself.
+#[derive(Default)]
+pub struct CursorMut<W = Vec<u8>>
+where W: io::Write {
+    inner: W,
+    written: usize,
 }
 
 impl CursorMut {
     /// Create a new, empty `CursorMut`.
     pub fn new() -> CursorMut {
+        CursorMut::from_writer(Vec::new())
+    }
+
+    /// Write a Hazel message.
+    ///
+    /// The payload is written by `f`, then framed as
+    /// `[len: u16][tag: u8][payload]`. This fails if the payload is longer
+    /// than a `u16`.
+    pub fn message<F>(&mut self, tag: u8, f: F) -> Result<(), Error>
+    where F: FnOnce(&mut CursorMut) -> Result<(), Error> {
+        let start = self.inner.len();
+
+        // the length is patched in once the payload has been written
+        self.write(&[0, 0, tag])?;
+        f(self)?;
+
+        let len = self.inner.len() - start - 3;
+        let len = match u16::try_from(len) {
+            Ok(len) => len,
+            Err(_) => return Err(Error::length_overflow::<u16>(len).at(start)),
+        };
+
+        self.inner[start..start + 2].copy_from_slice(&len.to_le_bytes());
+
+        Ok(())
+    }
+}
+
+impl<W> CursorMut<W>
+where W: io::Write {
+    /// Create a new `CursorMut` that writes to `writer`.
+    pub fn from_writer(writer: W) -> CursorMut<W> {
         CursorMut {
-            inner: Vec::new(),
+            inner: writer,
+            written: 0,
         }
     }
 
+    /// Get what the `CursorMut` is writing to.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
     /// Write a series of bytes to the `CursorMut`.
-    pub fn write(&mut self, buf: &[u8]) {
-        self.inner.extend(buf);
+    pub fn write(&mut self, buf: &[u8]) -> Result<(), Error> {
+        self.inner.write_all(buf).map_err(|e| Error::io(e.kind()).at(self.written))?;
+        self.written += buf.len();
+
+        Ok(())
+    }
+
+    /// Flush what has been written.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.inner.flush().map_err(|e| Error::io(e.kind()).at(self.written))
+    }
+
+    /// The number of bytes written so far.
+    pub fn len(&self) -> usize {
+        self.written
+    }
+
+    /// Check if nothing has been written yet.
+    pub fn is_empty(&self) -> bool {
+        self.written == 0
     }
 
     /// Encodes a type into the `CursorMut`.
-    pub fn encode<T>(&mut self, ty: &T) -> Result<(), Error> 
+    pub fn encode<T>(&mut self, ty: &T) -> Result<(), Error>
     where T: Encode + ?Sized {
-        ty.encode(self)
+        let offset = self.written;
+
+        ty.encode(self).map_err(|e| e.at(offset).of::<T>())
     }
 }
 
-impl Into<Vec<u8>> for CursorMut {
-    fn into(self) -> Vec<u8> {
-        self.inner
+impl From<CursorMut> for Vec<u8> {
+    fn from(cursor: CursorMut) -> Vec<u8> {
+        cursor.inner
     }
 }
 
-/// An error type.
+/// An error that can occur during encoding.
 ///
-/// There isn't really anything that can go wrong, as bytes are a superset of
-/// Rust types in this sense. This is only here for easy additions if it is
-/// needed.
-pub struct Error;
+/// Bytes are a superset of Rust types, so the only things that can go wrong
+/// are a length or value that is too big for its prefix or bits, or the
+/// writer failing. Errors that came from a [`CursorMut`] know where in the
+/// output it went wrong and what type was being encoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: Option<usize>,
+    type_name: Option<&'static str>,
+}
+
+/// What went wrong while encoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A length didn't fit in the integer type of its prefix.
+    LengthOverflow {
+        /// The length.
+        len: usize,
+        /// The name of the prefix type.
+        prefix: &'static str,
+    },
+    /// The writer failed.
+    Io(io::ErrorKind),
+    /// A value didn't fit in the number of bits it was given.
+    BitOverflow {
+        /// The number of bits.
+        bits: u32,
+    },
+}
+
+impl Error {
+    /// Create a new error.
+    pub fn new(kind: ErrorKind) -> Error {
+        Error {
+            kind,
+            offset: None,
+            type_name: None,
+        }
+    }
+
+    /// Create a new length overflow error, for a length that doesn't fit in
+    /// an `L`.
+    pub fn length_overflow<L>(len: usize) -> Error {
+        Error::new(ErrorKind::LengthOverflow {
+            len,
+            prefix: std::any::type_name::<L>(),
+        })
+    }
+
+    /// Create a new io error.
+    pub fn io(kind: io::ErrorKind) -> Error {
+        Error::new(ErrorKind::Io(kind))
+    }
+
+    /// Create a new bit overflow error, for a value that doesn't fit in
+    /// `bits` bits.
+    pub fn bit_overflow(bits: u32) -> Error {
+        Error::new(ErrorKind::BitOverflow { bits })
+    }
+
+    /// What went wrong.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Where in the output it went wrong, in bytes from the start.
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    /// The name of the type that was being encoded.
+    pub fn type_name(&self) -> Option<&'static str> {
+        self.type_name
+    }
+
+    /// Set where the error happened, unless something more specific already
+    /// has.
+    fn at(mut self, offset: usize) -> Error {
+        self.offset.get_or_insert(offset);
+        self
+    }
+
+    /// Set the type that was being encoded, unless a more specific one
+    /// already has been.
+    fn of<T>(mut self) -> Error
+    where T: ?Sized {
+        self.type_name.get_or_insert(std::any::type_name::<T>());
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to encode")?;
+
+        if let Some(type_name) = self.type_name {
+            write!(f, " `{}`", type_name)?;
+        }
+
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
+        }
+
+        write!(f, ": {}", self.kind)
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::LengthOverflow { len, prefix } => {
+                write!(f, "length {} doesn't fit in a `{}` prefix", len, prefix)
+            }
+            ErrorKind::Io(kind) => write!(f, "io error: {}", kind),
+            ErrorKind::BitOverflow { bits } => write!(f, "value doesn't fit in {} bits", bits),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
 
 /// A type that can be encoded to a [`CursorMut`].
 pub trait Encode {
-    fn encode(&self, cursor: &mut CursorMut) -> Result<(), Error>;
+    fn encode<W>(&self, cursor: &mut CursorMut<W>) -> Result<(), Error>
+    where W: io::Write;
 }
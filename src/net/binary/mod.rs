@@ -1,15 +1,22 @@
+pub mod bits;
 pub mod encode;
 pub mod decode;
+pub mod packed;
+#[cfg(feature = "serde")]
+pub mod serde;
+
+pub use packed::Packed;
 
 macro_rules! impl_num_decode {
     ($N:ty) => {
         impl decode::Decode for $N {
             fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error> 
-            where T: AsRef<[u8]> {
+            where T: decode::Source {
                 let mut buf = [0; ::std::mem::size_of::<$N>()];
+                let read = cursor.read(&mut buf)?;
 
-                if cursor.read(&mut buf) < ::std::mem::size_of::<$N>() {
-                    Err(decode::Error::unexpected_end())
+                if read < buf.len() {
+                    Err(decode::Error::unexpected_end(buf.len(), read))
                 } else {
                     Ok(<$N>::from_le_bytes(buf))
                 }
@@ -30,12 +37,15 @@ impl_num_decode!(i32);
 impl_num_decode!(i64);
 impl_num_decode!(i128);
 
+impl_num_decode!(f32);
+impl_num_decode!(f64);
+
 macro_rules! impl_num_encode {
     ($N:ty) => {
         impl encode::Encode for $N {
-            fn encode(&self, cursor: &mut encode::CursorMut) -> Result<(), encode::Error> {
-                cursor.write(&self.to_le_bytes());
-                Ok(())
+            fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+            where W: io::Write {
+                cursor.write(&self.to_le_bytes())
             }
         }
     }
@@ -53,37 +63,346 @@ impl_num_encode!(i32);
 impl_num_encode!(i64);
 impl_num_encode!(i128);
 
-use std::iter::FromIterator as _;
+impl_num_encode!(f32);
+impl_num_encode!(f64);
+
+impl decode::Decode for bool {
+    fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+    where T: decode::Source {
+        match cursor.decode::<u8>()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(decode::Error::invalid_tag()),
+        }
+    }
+}
+
+impl encode::Encode for bool {
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        cursor.encode(&(*self as u8))
+    }
+}
 
 use std::convert::TryInto as _;
+use std::io;
 
-// TODO: fix a memory allocation security flaw here. It is possible to tell
-// clients to allocate 65535 bytes in memory, which isn't too much of a problem,
-// but with many, many packets, this could easily overflow memory.
-impl decode::Decode for String {
-    fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error> 
-    where T: AsRef<[u8]> {
-        let count = cursor.decode::<u16>()? as usize;
-        let mut buf = Vec::from_iter((0..count).map(|_| 0));
+/// An integer type that can be used as the length prefix of a sequence.
+pub trait Length: encode::Encode + decode::Decode {
+    /// Convert a length to this type, returning `None` if it doesn't fit.
+    fn from_len(len: usize) -> Option<Self>;
 
-        if cursor.read(&mut buf[..]) < count {
-            Err(decode::Error::unexpected_end())
+    /// Convert this to a length, returning `None` if it doesn't fit.
+    fn to_len(self) -> Option<usize>;
+}
+
+macro_rules! impl_length {
+    ($N:ty) => {
+        impl Length for $N {
+            fn from_len(len: usize) -> Option<Self> {
+                len.try_into().ok()
+            }
+
+            fn to_len(self) -> Option<usize> {
+                self.try_into().ok()
+            }
+        }
+    }
+}
+
+impl_length!(u8);
+impl_length!(u16);
+impl_length!(u32);
+impl_length!(u64);
+
+/// Encode an `L` length.
+fn encode_len<L, W>(cursor: &mut encode::CursorMut<W>, len: usize) -> Result<(), encode::Error>
+where L: Length, W: io::Write {
+    match L::from_len(len) {
+        Some(count) => cursor.encode(&count),
+        None => Err(encode::Error::length_overflow::<L>(len)),
+    }
+}
+
+/// Decode an `L` length.
+///
+/// Lengths that don't fit in a `usize` are saturated, so they are caught by
+/// the limits of the cursor.
+fn decode_len<L, T>(cursor: &mut decode::Cursor<T>) -> Result<usize, decode::Error>
+where L: Length, T: decode::Source {
+    Ok(cursor.decode::<L>()?.to_len().unwrap_or(usize::MAX))
+}
+
+/// A sequence that is encoded with its length in front of it.
+///
+/// The [`Encode`](encode::Encode) impl of a sequence prefixes it with a
+/// [`Packed`] `u32` length; this lets it be encoded with any other [`Length`]
+/// type.
+pub trait EncodePrefixed {
+    /// Encode the sequence with an `L` length prefix.
+    fn encode_prefixed<L, W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where L: Length, W: io::Write;
+}
+
+/// A sequence that is decoded with its length in front of it.
+///
+/// This is the other half of [`EncodePrefixed`].
+pub trait DecodePrefixed: Sized {
+    /// Decode the sequence with an `L` length prefix.
+    fn decode_prefixed<L, T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+    where L: Length, T: decode::Source;
+}
+
+impl EncodePrefixed for String {
+    fn encode_prefixed<L, W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where L: Length, W: io::Write {
+        encode_len::<L, W>(cursor, self.len())?;
+        cursor.write(self.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+impl DecodePrefixed for String {
+    fn decode_prefixed<L, T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+    where L: Length, T: decode::Source {
+        let count = decode_len::<L, T>(cursor)?;
+        cursor.reserve_string(count)?;
+
+        let mut buf = vec![0; count];
+        let read = cursor.read(&mut buf[..])?;
+
+        if read < count {
+            Err(decode::Error::unexpected_end(count, read))
         } else {
             String::from_utf8(buf).map_err(|e| decode::Error::utf8(e.utf8_error()))
         }
     }
 }
 
+impl decode::Decode for String {
+    fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error> 
+    where T: decode::Source {
+        String::decode_prefixed::<Packed<u32>, T>(cursor)
+    }
+}
+
 impl encode::Encode for String {
-    fn encode(&self, cursor: &mut encode::CursorMut) -> Result<(), encode::Error> {
-        let count: u16 = match self.len().try_into() {
-            Ok(count) => count,
-            Err(_) => return Err(encode::Error),
-        };
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        self.encode_prefixed::<Packed<u32>, W>(cursor)
+    }
+}
+
+impl encode::Encode for str {
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        encode_len::<Packed<u32>, W>(cursor, self.len())?;
+        cursor.write(self.as_bytes())?;
+
+        Ok(())
+    }
+}
+
+impl<'de> decode::BorrowDecode<'de> for &'de str {
+    fn borrow_decode(cursor: &mut decode::Cursor<&'de [u8]>) -> Result<Self, decode::Error> {
+        let bytes = cursor.borrow_decode::<&[u8]>()?;
+
+        std::str::from_utf8(bytes).map_err(decode::Error::utf8)
+    }
+}
+
+impl<'de> decode::BorrowDecode<'de> for &'de [u8] {
+    fn borrow_decode(cursor: &mut decode::Cursor<&'de [u8]>) -> Result<Self, decode::Error> {
+        let count = decode_len::<Packed<u32>, _>(cursor)?;
+
+        cursor.take(count)
+    }
+}
+
+impl<U> encode::Encode for &U
+where U: encode::Encode + ?Sized {
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        (**self).encode(cursor)
+    }
+}
+
+/// Encode a sequence of `len` items with an `L` length prefix.
+fn encode_seq<'a, L, U, I, W>(cursor: &mut encode::CursorMut<W>, len: usize, items: I) -> Result<(), encode::Error>
+where L: Length, U: encode::Encode + 'a, I: IntoIterator<Item = &'a U>, W: io::Write {
+    encode_len::<L, W>(cursor, len)?;
+
+    for item in items {
+        cursor.encode(item)?;
+    }
+
+    Ok(())
+}
+
+impl<U> EncodePrefixed for Vec<U>
+where U: encode::Encode {
+    fn encode_prefixed<L, W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where L: Length, W: io::Write {
+        encode_seq::<L, _, _, _>(cursor, self.len(), self)
+    }
+}
+
+impl<U> DecodePrefixed for Vec<U>
+where U: decode::Decode {
+    fn decode_prefixed<L, T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+    where L: Length, T: decode::Source {
+        let count = decode_len::<L, T>(cursor)?;
+        let mut items = Vec::with_capacity(cursor.reserve_seq::<U>(count)?);
+
+        for _ in 0..count {
+            items.push(cursor.decode()?);
+        }
 
-        cursor.encode(&count)?;
-        cursor.write(self.as_bytes());
+        Ok(items)
+    }
+}
+
+impl<U> decode::Decode for Vec<U>
+where U: decode::Decode {
+    fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+    where T: decode::Source {
+        Vec::decode_prefixed::<Packed<u32>, T>(cursor)
+    }
+}
+
+impl<U> encode::Encode for Vec<U>
+where U: encode::Encode {
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        encode_seq::<Packed<u32>, _, _, _>(cursor, self.len(), self)
+    }
+}
+
+impl<U> encode::Encode for [U]
+where U: encode::Encode {
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        encode_seq::<Packed<u32>, _, _, _>(cursor, self.len(), self)
+    }
+}
+
+impl<U> decode::Decode for Option<U>
+where U: decode::Decode {
+    fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+    where T: decode::Source {
+        match cursor.decode::<u8>()? {
+            0 => Ok(None),
+            1 => Ok(Some(cursor.decode()?)),
+            _ => Err(decode::Error::invalid_tag()),
+        }
+    }
+}
+
+impl<U> encode::Encode for Option<U>
+where U: encode::Encode {
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        match self {
+            None => cursor.encode(&0u8),
+            Some(value) => {
+                cursor.encode(&1u8)?;
+                cursor.encode(value)
+            }
+        }
+    }
+}
+
+impl<U, const N: usize> decode::Decode for [U; N]
+where U: decode::Decode {
+    fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+    where T: decode::Source {
+        let items = (0..N).map(|_| cursor.decode()).collect::<Result<Vec<U>, _>>()?;
+
+        match items.try_into() {
+            Ok(items) => Ok(items),
+            Err(_) => unreachable!("exactly N items were decoded"),
+        }
+    }
+}
+
+impl<U, const N: usize> encode::Encode for [U; N]
+where U: encode::Encode {
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        for item in self.iter() {
+            cursor.encode(item)?;
+        }
 
         Ok(())
     }
 }
+
+macro_rules! impl_tuple {
+    ($($N:ident),*) => {
+        impl<$($N),*> decode::Decode for ($($N,)*)
+        where $($N: decode::Decode),* {
+            #[allow(unused_variables)]
+            fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+            where T: decode::Source {
+                Ok(($(cursor.decode::<$N>()?,)*))
+            }
+        }
+
+        impl<$($N),*> encode::Encode for ($($N,)*)
+        where $($N: encode::Encode),* {
+            #[allow(non_snake_case, unused_variables)]
+            fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+            where W: io::Write {
+                let ($($N,)*) = self;
+                $(cursor.encode($N)?;)*
+                Ok(())
+            }
+        }
+    }
+}
+
+impl_tuple!();
+impl_tuple!(A);
+impl_tuple!(A, B);
+impl_tuple!(A, B, C);
+impl_tuple!(A, B, C, D);
+impl_tuple!(A, B, C, D, E);
+impl_tuple!(A, B, C, D, E, F);
+impl_tuple!(A, B, C, D, E, F, G);
+impl_tuple!(A, B, C, D, E, F, G, H);
+
+impl<S> decode::Decode for cgmath::Vector2<S>
+where S: decode::Decode {
+    fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+    where T: decode::Source {
+        Ok(cgmath::Vector2::new(cursor.decode()?, cursor.decode()?))
+    }
+}
+
+impl<S> encode::Encode for cgmath::Vector2<S>
+where S: encode::Encode {
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        cursor.encode(&self.x)?;
+        cursor.encode(&self.y)
+    }
+}
+
+impl<S> decode::Decode for cgmath::Vector3<S>
+where S: decode::Decode {
+    fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+    where T: decode::Source {
+        Ok(cgmath::Vector3::new(cursor.decode()?, cursor.decode()?, cursor.decode()?))
+    }
+}
+
+impl<S> encode::Encode for cgmath::Vector3<S>
+where S: encode::Encode {
+    fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        cursor.encode(&self.x)?;
+        cursor.encode(&self.y)?;
+        cursor.encode(&self.z)
+    }
+}
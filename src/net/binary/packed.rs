@@ -0,0 +1,116 @@
+//! Packed integers.
+//!
+//! Most integers sent over the network are small, like lengths and IDs, and
+//! a fixed-width integer wastes most of its bytes on zeroes. A [`Packed`]
+//! integer is written seven bits at a time, lowest bits first, with the top
+//! bit of every byte set if there is another byte after it. Anything under
+//! 128 fits in a single byte.
+//!
+//! This is the same packing Among Us uses. Like Among Us, signed integers are
+//! packed as their unsigned two's complement, so negative numbers always take
+//! the most bytes.
+
+use crate::net::binary::{decode, encode, Length};
+
+use std::convert::TryInto as _;
+use std::io;
+
+/// A packed integer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Packed<T>(pub T);
+
+impl<T> Packed<T> {
+    /// Unwrap the integer.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Packed<T> {
+    fn from(value: T) -> Packed<T> {
+        Packed(value)
+    }
+}
+
+fn encode_packed<W>(mut value: u64, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+where W: io::Write {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            return cursor.write(&[byte]);
+        }
+
+        cursor.write(&[byte | 0x80])?;
+    }
+}
+
+fn decode_packed<T>(cursor: &mut decode::Cursor<T>, bits: u32) -> Result<u64, decode::Error>
+where T: decode::Source {
+    let mut value = 0u64;
+    let mut shift = 0;
+
+    loop {
+        let byte = cursor.decode::<u8>()?;
+        let part = (byte & 0x7f) as u64;
+
+        // anything past the width of the integer has to be zero
+        if shift >= bits || (bits - shift < 7 && part >> (bits - shift) != 0) {
+            return Err(decode::Error::overflow());
+        }
+
+        value |= part << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+}
+
+macro_rules! impl_packed {
+    ($N:ty, $U:ty) => {
+        impl decode::Decode for Packed<$N> {
+            fn decode<T>(cursor: &mut decode::Cursor<T>) -> Result<Self, decode::Error>
+            where T: decode::Source {
+                let value = decode_packed(cursor, <$U>::BITS)?;
+
+                Ok(Packed(value as $U as $N))
+            }
+        }
+
+        impl encode::Encode for Packed<$N> {
+            fn encode<W>(&self, cursor: &mut encode::CursorMut<W>) -> Result<(), encode::Error>
+            where W: io::Write {
+                encode_packed(self.0 as $U as u64, cursor)
+            }
+        }
+    }
+}
+
+impl_packed!(u16, u16);
+impl_packed!(u32, u32);
+impl_packed!(u64, u64);
+
+impl_packed!(i16, u16);
+impl_packed!(i32, u32);
+impl_packed!(i64, u64);
+
+macro_rules! impl_packed_length {
+    ($N:ty) => {
+        impl Length for Packed<$N> {
+            fn from_len(len: usize) -> Option<Self> {
+                len.try_into().ok().map(Packed)
+            }
+
+            fn to_len(self) -> Option<usize> {
+                self.0.try_into().ok()
+            }
+        }
+    }
+}
+
+impl_packed_length!(u16);
+impl_packed_length!(u32);
+impl_packed_length!(u64);
@@ -0,0 +1,567 @@
+//! A serde data format for `net::binary`.
+//!
+//! Anything that derives `Serialize` and `Deserialize` can be sent with the
+//! same bytes [`CursorMut`] and [`Cursor`] would use for it, so types that
+//! already have serde impls don't need [`Encode`](encode::Encode) and
+//! [`Decode`](decode::Decode) impls too.
+//!
+//! Serde types are mapped to the binary encoding like so:
+//! * numbers and `bool` are encoded like their binary impls, and `char` is
+//!   encoded as a `u32`.
+//! * strings, bytes and sequences have a [`Packed`] `u32` length prefix.
+//! * maps are encoded like a sequence of `(key, value)` tuples.
+//! * options have a `u8` tag.
+//! * tuples, structs and arrays are encoded one field after the other.
+//! * enums have a `u8` tag, which is the index of the variant. This is what
+//!   `#[derive(Encode)]` does for enums without any `#[binary(tag)]`
+//!   attributes.
+//!
+//! The format isn't self describing, so `deserialize_any` isn't supported.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+
+use ::serde::de::{self, DeserializeOwned, IntoDeserializer as _};
+use ::serde::ser::{self, Serialize};
+
+use super::decode::{self, Cursor};
+use super::encode::{self, CursorMut};
+use super::{Length as _, Packed};
+
+/// Serialize a value to bytes.
+pub fn to_bytes<T>(value: &T) -> Result<Vec<u8>, Error>
+where T: Serialize + ?Sized {
+    let mut cursor = CursorMut::new();
+    value.serialize(&mut Serializer::new(&mut cursor))?;
+
+    Ok(cursor.into())
+}
+
+/// Serialize a value straight to a writer.
+pub fn to_writer<T, W>(value: &T, writer: W) -> Result<W, Error>
+where T: Serialize + ?Sized, W: io::Write {
+    let mut cursor = CursorMut::from_writer(writer);
+    value.serialize(&mut Serializer::new(&mut cursor))?;
+
+    Ok(cursor.into_inner())
+}
+
+/// Deserialize a value from bytes.
+///
+/// All of the bytes have to be used.
+pub fn from_bytes<T>(bytes: &[u8]) -> Result<T, Error>
+where T: DeserializeOwned {
+    let mut cursor = Cursor::new(bytes);
+    let value = T::deserialize(&mut Deserializer::new(&mut cursor))?;
+
+    if cursor.remaining() > 0 {
+        return Err(Error::TrailingBytes);
+    }
+
+    Ok(value)
+}
+
+/// Deserialize a value straight from a reader.
+///
+/// Only the bytes of the value are read, so more values can be read after
+/// it.
+pub fn from_reader<T, R>(reader: R) -> Result<T, Error>
+where T: DeserializeOwned, R: io::Read {
+    let mut cursor = Cursor::from_reader(reader);
+
+    T::deserialize(&mut Deserializer::new(&mut cursor))
+}
+
+/// A serializer that writes to a [`CursorMut`].
+pub struct Serializer<'a, W = Vec<u8>>
+where W: io::Write {
+    cursor: &'a mut CursorMut<W>,
+}
+
+impl<'a, W> Serializer<'a, W>
+where W: io::Write {
+    /// Create a new serializer.
+    pub fn new(cursor: &'a mut CursorMut<W>) -> Serializer<'a, W> {
+        Serializer { cursor }
+    }
+
+    fn encode<T>(&mut self, value: &T) -> Result<(), Error>
+    where T: encode::Encode + ?Sized {
+        self.cursor.encode(value).map_err(Error::Encode)
+    }
+
+    fn len(&mut self, len: Option<usize>) -> Result<(), Error> {
+        let len = len.ok_or(Error::UnknownLength)?;
+
+        match Packed::<u32>::from_len(len) {
+            Some(len) => self.encode(&len),
+            None => Err(Error::Encode(encode::Error::length_overflow::<Packed<u32>>(len))),
+        }
+    }
+
+    fn variant(&mut self, index: u32) -> Result<(), Error> {
+        let tag = u8::try_from(index).map_err(|_| Error::TooManyVariants)?;
+
+        self.encode(&tag)
+    }
+}
+
+macro_rules! serialize_num {
+    ($($method:ident: $N:ty,)*) => {
+        $(
+            fn $method(self, v: $N) -> Result<(), Error> {
+                self.encode(&v)
+            }
+        )*
+    }
+}
+
+impl<'a, 'b, W> ser::Serializer for &'b mut Serializer<'a, W>
+where W: io::Write {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    serialize_num! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_str: &str,
+        serialize_bytes: &[u8],
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.encode(&(v as u32))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.encode(&0u8)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized {
+        self.encode(&1u8)?;
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, index: u32, _variant: &'static str) -> Result<(), Error> {
+        self.variant(index)
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(self, _name: &'static str, index: u32, _variant: &'static str, value: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized {
+        self.variant(index)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self, Error> {
+        self.len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, index: u32, _variant: &'static str, _len: usize) -> Result<Self, Error> {
+        self.variant(index)?;
+        Ok(self)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, Error> {
+        self.len(len)?;
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, index: u32, _variant: &'static str, _len: usize) -> Result<Self, Error> {
+        self.variant(index)?;
+        Ok(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+macro_rules! serialize_compound {
+    ($($Trait:ident: $method:ident $(($key:ident))?,)*) => {
+        $(
+            impl<'a, 'b, W> ser::$Trait for &'b mut Serializer<'a, W>
+            where W: io::Write {
+                type Ok = ();
+                type Error = Error;
+
+                fn $method<T>(&mut self, $($key: &'static str,)? value: &T) -> Result<(), Error>
+                where T: Serialize + ?Sized {
+                    value.serialize(&mut **self)
+                }
+
+                fn end(self) -> Result<(), Error> {
+                    Ok(())
+                }
+            }
+        )*
+    }
+}
+
+serialize_compound! {
+    SerializeSeq: serialize_element,
+    SerializeTuple: serialize_element,
+    SerializeTupleStruct: serialize_field,
+    SerializeTupleVariant: serialize_field,
+    SerializeStruct: serialize_field(_key),
+    SerializeStructVariant: serialize_field(_key),
+}
+
+impl<'a, 'b, W> ser::SerializeMap for &'b mut Serializer<'a, W>
+where W: io::Write {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized {
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where T: Serialize + ?Sized {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// A deserializer that reads from a [`Cursor`].
+///
+/// Strings and bytes are always handed to the visitor as owned values.
+pub struct Deserializer<'a, T>
+where T: decode::Source {
+    cursor: &'a mut Cursor<T>,
+}
+
+impl<'a, T> Deserializer<'a, T>
+where T: decode::Source {
+    /// Create a new deserializer.
+    pub fn new(cursor: &'a mut Cursor<T>) -> Deserializer<'a, T> {
+        Deserializer { cursor }
+    }
+
+    fn decode<U>(&mut self) -> Result<U, Error>
+    where U: decode::Decode {
+        self.cursor.decode().map_err(Error::Decode)
+    }
+
+    fn len(&mut self) -> Result<usize, Error> {
+        let len = self.decode::<Packed<u32>>()?.0 as usize;
+
+        // serde only preallocates a little, so only the length is checked
+        self.cursor.reserve_seq::<()>(len).map_err(Error::Decode)?;
+
+        Ok(len)
+    }
+}
+
+macro_rules! deserialize_num {
+    ($($method:ident: $visit:ident $N:ty,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Error>
+            where V: de::Visitor<'de> {
+                visitor.$visit(self.decode::<$N>()?)
+            }
+        )*
+    }
+}
+
+impl<'de, 'a, 'b, T> de::Deserializer<'de> for &'b mut Deserializer<'a, T>
+where T: decode::Source {
+    type Error = Error;
+
+    deserialize_num! {
+        deserialize_bool: visit_bool bool,
+        deserialize_i8: visit_i8 i8,
+        deserialize_i16: visit_i16 i16,
+        deserialize_i32: visit_i32 i32,
+        deserialize_i64: visit_i64 i64,
+        deserialize_i128: visit_i128 i128,
+        deserialize_u8: visit_u8 u8,
+        deserialize_u16: visit_u16 u16,
+        deserialize_u32: visit_u32 u32,
+        deserialize_u64: visit_u64 u64,
+        deserialize_u128: visit_u128 u128,
+        deserialize_f32: visit_f32 f32,
+        deserialize_f64: visit_f64 f64,
+        deserialize_str: visit_string String,
+        deserialize_string: visit_string String,
+        deserialize_bytes: visit_byte_buf Vec<u8>,
+        deserialize_byte_buf: visit_byte_buf Vec<u8>,
+    }
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        Err(Error::NotSelfDescribing)
+    }
+
+    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        Err(Error::NotSelfDescribing)
+    }
+
+    fn deserialize_identifier<V>(self, _visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        Err(Error::NotSelfDescribing)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        match char::from_u32(self.decode()?) {
+            Some(c) => visitor.visit_char(c),
+            None => Err(Error::Decode(decode::Error::invalid_tag())),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        match self.decode::<u8>()? {
+            0 => visitor.visit_none(),
+            1 => visitor.visit_some(self),
+            _ => Err(Error::Decode(decode::Error::invalid_tag())),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        let len = self.len()?;
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn deserialize_tuple_struct<V>(self, _name: &'static str, len: usize, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        visitor.visit_seq(Access { de: self, len })
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        let len = self.len()?;
+        visitor.visit_map(Access { de: self, len })
+    }
+
+    fn deserialize_struct<V>(self, _name: &'static str, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        visitor.visit_seq(Access { de: self, len: fields.len() })
+    }
+
+    fn deserialize_enum<V>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        visitor.visit_enum(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// Access to the items of a sequence or map.
+struct Access<'b, 'a, T>
+where T: decode::Source {
+    de: &'b mut Deserializer<'a, T>,
+    len: usize,
+}
+
+impl<'de, 'a, 'b, T> de::SeqAccess<'de> for Access<'b, 'a, T>
+where T: decode::Source {
+    type Error = Error;
+
+    fn next_element_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, Error>
+    where S: de::DeserializeSeed<'de> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de, 'a, 'b, T> de::MapAccess<'de> for Access<'b, 'a, T>
+where T: decode::Source {
+    type Error = Error;
+
+    fn next_key_seed<S>(&mut self, seed: S) -> Result<Option<S::Value>, Error>
+    where S: de::DeserializeSeed<'de> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<S>(&mut self, seed: S) -> Result<S::Value, Error>
+    where S: de::DeserializeSeed<'de> {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de, 'a, 'b, T> de::EnumAccess<'de> for &'b mut Deserializer<'a, T>
+where T: decode::Source {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<S>(self, seed: S) -> Result<(S::Value, Self), Error>
+    where S: de::DeserializeSeed<'de> {
+        let tag = self.decode::<u8>()? as u32;
+        let value = seed.deserialize(tag.into_deserializer())?;
+
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a, 'b, T> de::VariantAccess<'de> for &'b mut Deserializer<'a, T>
+where T: decode::Source {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S>(self, seed: S) -> Result<S::Value, Error>
+    where S: de::DeserializeSeed<'de> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V>(self, fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error>
+    where V: de::Visitor<'de> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+/// An error that can occur when serializing or deserializing.
+#[derive(Debug)]
+pub enum Error {
+    /// The value could not be encoded.
+    Encode(encode::Error),
+    /// The value could not be decoded.
+    Decode(decode::Error),
+    /// A sequence or map didn't know its length before it was serialized.
+    UnknownLength,
+    /// An enum had more variants than fit in a `u8` tag.
+    TooManyVariants,
+    /// A type asked what was next, which this format can't tell it.
+    NotSelfDescribing,
+    /// Not all of the bytes were used.
+    TrailingBytes,
+    /// An error from a `Serialize` or `Deserialize` impl.
+    Custom(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Encode(e) => e.fmt(f),
+            Error::Decode(e) => e.fmt(f),
+            Error::UnknownLength => write!(f, "sequences must know their length"),
+            Error::TooManyVariants => write!(f, "enums can't have more than 256 variants"),
+            Error::NotSelfDescribing => write!(f, "the binary format isn't self describing"),
+            Error::TrailingBytes => write!(f, "there were bytes left over"),
+            Error::Custom(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Encode(e) => e.source(),
+            Error::Decode(e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T>(msg: T) -> Error
+    where T: fmt::Display {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T>(msg: T) -> Error
+    where T: fmt::Display {
+        Error::Custom(msg.to_string())
+    }
+}
@@ -1 +1,3 @@
 pub mod binary;
+pub mod protocol;
+pub mod transport;
@@ -0,0 +1,495 @@
+//! The packet protocol.
+//!
+//! Everything a client and a server say to each other is a [`Packet`],
+//! encoded with [`net::binary`](crate::net::binary). A connection starts with
+//! a handshake: the client sends a [`Hello`] with its protocol [`VERSION`],
+//! and the server either accepts it silently or tells the client why it was
+//! [`Reject`]ed. A client that got through the handshake asks for a lobby
+//! with a [`LobbyRequest`]: it can create one, join one by its [`RoomCode`],
+//! or list the public ones. Once it's in a lobby, it's welcomed with its
+//! [`PlayerId`], and both sides mostly send [`Message`]s.
+//!
+//! Tags are given explicitly so they don't change when variants are added.
+//! The tag of [`Packet::Hello`] and the version at the start of a `Hello`
+//! must never change, so a server can always tell an old or new client that
+//! its version is wrong instead of failing to decode it; see [`accept`].
+
+use std::fmt;
+use std::io;
+
+use crate::math::*;
+use crate::net::binary::decode::{self, Cursor, Decode};
+use crate::net::binary::encode::{self, CursorMut, Encode};
+
+/// The version of the protocol.
+///
+/// This has to be bumped whenever the encoding of a packet changes.
+pub const VERSION: u32 = 2;
+
+/// A player in a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode)]
+pub struct PlayerId(pub u8);
+
+/// Everything that is sent over a connection.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub enum Packet {
+    /// A client starting the handshake.
+    #[binary(tag = 0)]
+    Hello(Hello),
+    /// The server letting a client into a lobby.
+    #[binary(tag = 1)]
+    Welcome(Welcome),
+    /// The server rejecting a client.
+    #[binary(tag = 2)]
+    Reject(Reject),
+    /// Something happening in the game.
+    #[binary(tag = 3)]
+    Message(Message),
+    /// A client asking for a lobby, or the host managing theirs.
+    #[binary(tag = 4)]
+    Lobby(LobbyRequest),
+    /// The public lobbies, in answer to [`LobbyRequest::List`].
+    #[binary(tag = 5)]
+    LobbyList(Vec<LobbyInfo>),
+    /// The lobby a client is in changed.
+    #[binary(tag = 6)]
+    LobbyState(LobbyInfo),
+}
+
+impl Packet {
+    /// Encode the packet.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, encode::Error> {
+        let mut cursor = CursorMut::new();
+        cursor.encode(self)?;
+
+        Ok(cursor.into())
+    }
+
+    /// Decode a packet.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Packet, decode::Error> {
+        Cursor::new(bytes).decode()
+    }
+}
+
+/// The first packet a client sends.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct Hello {
+    version: u32,
+    #[binary(len = u8)]
+    name: String,
+}
+
+impl Hello {
+    /// Create a new hello for the current protocol [`VERSION`].
+    pub fn new(name: impl Into<String>) -> Hello {
+        Hello {
+            version: VERSION,
+            name: name.into(),
+        }
+    }
+
+    /// The protocol version of the client.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The name the player wants to use.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Check that the client speaks the same protocol as us.
+    pub fn check(&self) -> Result<(), Reject> {
+        if self.version != VERSION {
+            return Err(Reject::Version {
+                client: self.version,
+                server: VERSION,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// The server letting a client into a lobby.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct Welcome {
+    player: PlayerId,
+}
+
+impl Welcome {
+    /// Create a new welcome.
+    pub fn new(player: PlayerId) -> Welcome {
+        Welcome { player }
+    }
+
+    /// The player the client is in the lobby.
+    pub fn player(&self) -> PlayerId {
+        self.player
+    }
+}
+
+/// Why a client was rejected.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum Reject {
+    /// The client and server speak different versions of the protocol.
+    #[binary(tag = 0)]
+    Version {
+        /// The version of the client.
+        client: u32,
+        /// The version of the server.
+        server: u32,
+    },
+    /// The client didn't start with a [`Hello`], or it couldn't be decoded.
+    #[binary(tag = 1)]
+    Malformed,
+    /// There is no room for another player.
+    #[binary(tag = 2)]
+    Full,
+    /// The game has already started.
+    #[binary(tag = 3)]
+    Started,
+    /// There is no lobby with the code.
+    #[binary(tag = 4)]
+    NotFound,
+    /// The client was banned from the lobby.
+    #[binary(tag = 5)]
+    Banned,
+    /// The server can't open any more lobbies.
+    #[binary(tag = 6)]
+    TooManyLobbies,
+}
+
+impl fmt::Display for Reject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reject::Version { client, server } if client < server => write!(
+                f,
+                "the client is on protocol version {}, but the server is on version {}; update the client",
+                client, server
+            ),
+            Reject::Version { client, server } => write!(
+                f,
+                "the client is on protocol version {}, but the server is only on version {}",
+                client, server
+            ),
+            Reject::Malformed => write!(f, "the client didn't start with a valid handshake"),
+            Reject::Full => write!(f, "the game is full"),
+            Reject::Started => write!(f, "the game has already started"),
+            Reject::NotFound => write!(f, "there is no game with that code"),
+            Reject::Banned => write!(f, "you are banned from the game"),
+            Reject::TooManyLobbies => write!(f, "the server can't host any more games"),
+        }
+    }
+}
+
+impl std::error::Error for Reject {}
+
+/// Read the handshake of a client.
+///
+/// The version is checked before the rest of the [`Hello`] is decoded, so
+/// clients on any version are told why they were rejected.
+pub fn accept(bytes: &[u8]) -> Result<Hello, Reject> {
+    let mut cursor = Cursor::new(bytes);
+
+    // the tag of a hello and the version are always the same
+    match cursor.decode::<u8>() {
+        Ok(0) => {}
+        _ => return Err(Reject::Malformed),
+    }
+
+    let version = cursor.peek_decode::<u32>().map_err(|_| Reject::Malformed)?;
+
+    if version != VERSION {
+        return Err(Reject::Version {
+            client: version,
+            server: VERSION,
+        });
+    }
+
+    match cursor.decode::<Hello>() {
+        Ok(hello) if cursor.remaining() == 0 => Ok(hello),
+        _ => Err(Reject::Malformed),
+    }
+}
+
+/// The code a lobby is joined with.
+///
+/// Codes are four or six letters from `A` to `Z`. They are encoded as a
+/// `u32`, with the letters as a number in base 26 and the highest bit set
+/// for six letter codes.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RoomCode(u32);
+
+impl RoomCode {
+    /// The bit that is set for six letter codes.
+    const LONG: u32 = 1 << 31;
+
+    /// Parse a code, ignoring case.
+    ///
+    /// This fails if the code isn't four or six letters.
+    pub fn parse(code: &str) -> Option<RoomCode> {
+        if code.len() != 4 && code.len() != 6 {
+            return None;
+        }
+
+        let mut value = 0;
+
+        for letter in code.bytes().map(|c| c.to_ascii_uppercase()) {
+            if !letter.is_ascii_uppercase() {
+                return None;
+            }
+
+            value = value * 26 + (letter - b'A') as u32;
+        }
+
+        if code.len() == 6 {
+            value |= RoomCode::LONG;
+        }
+
+        Some(RoomCode(value))
+    }
+
+    /// Check if the code has six letters instead of four.
+    pub fn is_long(&self) -> bool {
+        self.0 & RoomCode::LONG != 0
+    }
+
+    /// The biggest number that fits in the letters of a code.
+    fn max(long: bool) -> u32 {
+        if long { 26u32.pow(6) } else { 26u32.pow(4) }
+    }
+}
+
+impl fmt::Display for RoomCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let len = if self.is_long() { 6 } else { 4 };
+        let mut value = self.0 & !RoomCode::LONG;
+        let mut letters = [0; 6];
+
+        for letter in letters[..len].iter_mut().rev() {
+            *letter = b'A' + (value % 26) as u8;
+            value /= 26;
+        }
+
+        f.write_str(std::str::from_utf8(&letters[..len]).expect("letters are ascii"))
+    }
+}
+
+impl fmt::Debug for RoomCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "RoomCode({})", self)
+    }
+}
+
+impl Encode for RoomCode {
+    fn encode<W>(&self, cursor: &mut CursorMut<W>) -> Result<(), encode::Error>
+    where W: io::Write {
+        cursor.encode(&self.0)
+    }
+}
+
+impl Decode for RoomCode {
+    fn decode<T>(cursor: &mut Cursor<T>) -> Result<Self, decode::Error>
+    where T: decode::Source {
+        let value = cursor.decode::<u32>()?;
+
+        if value & !RoomCode::LONG >= RoomCode::max(value & RoomCode::LONG != 0) {
+            return Err(decode::Error::invalid_tag());
+        }
+
+        Ok(RoomCode(value))
+    }
+}
+
+/// A client asking for a lobby, or the host managing theirs.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum LobbyRequest {
+    /// Create a new lobby, and join it as the host.
+    #[binary(tag = 0)]
+    Create {
+        /// Whether the lobby is listed for anybody to join.
+        public: bool,
+        /// The most players the lobby can have.
+        max_players: u8,
+    },
+    /// Join a lobby.
+    #[binary(tag = 1)]
+    Join {
+        /// The code of the lobby.
+        code: RoomCode,
+    },
+    /// List the public lobbies.
+    #[binary(tag = 2)]
+    List,
+    /// Kick a player out of the lobby. Only the host can do this.
+    #[binary(tag = 3)]
+    Kick {
+        /// The player.
+        player: PlayerId,
+    },
+    /// Kick a player out of the lobby, and don't let them back in. Only the
+    /// host can do this.
+    #[binary(tag = 4)]
+    Ban {
+        /// The player.
+        player: PlayerId,
+    },
+}
+
+/// What a client needs to show a lobby.
+#[derive(Clone, Debug, PartialEq, Eq, Encode, Decode)]
+pub struct LobbyInfo {
+    /// The code to join with.
+    pub code: RoomCode,
+    /// Whether the lobby is listed for anybody to join.
+    pub public: bool,
+    /// The player that runs the lobby.
+    pub host: PlayerId,
+    /// The name of the host.
+    #[binary(len = u8)]
+    pub host_name: String,
+    /// How many players are in the lobby.
+    pub players: u8,
+    /// The most players the lobby can have.
+    pub max_players: u8,
+    /// Whether the game has started.
+    pub started: bool,
+}
+
+/// Something happening in a game.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub enum Message {
+    /// A player joined.
+    #[binary(tag = 0)]
+    Join {
+        /// The player.
+        player: PlayerId,
+        /// Their name.
+        #[binary(len = u8)]
+        name: String,
+        /// Their color.
+        color: u8,
+    },
+    /// A player left.
+    #[binary(tag = 1)]
+    Leave {
+        /// The player.
+        player: PlayerId,
+        /// Why they left.
+        reason: LeaveReason,
+    },
+    /// A player moved.
+    #[binary(tag = 2)]
+    Movement {
+        /// The player.
+        player: PlayerId,
+        /// Counts up with every movement of the player, so old movements
+        /// that arrive late can be ignored.
+        sequence: u16,
+        /// Where they are.
+        position: Vector2,
+        /// How fast they are going.
+        velocity: Vector2,
+    },
+    /// A player said something.
+    #[binary(tag = 3)]
+    Chat {
+        /// The player.
+        player: PlayerId,
+        /// What they said.
+        #[binary(len = u8)]
+        text: String,
+    },
+    /// A player did a step of a task.
+    #[binary(tag = 4)]
+    TaskProgress {
+        /// The player.
+        player: PlayerId,
+        /// The index of the task in the map.
+        task: u16,
+        /// How many steps are done.
+        step: u8,
+        /// How many steps there are.
+        steps: u8,
+    },
+    /// An impostor killed somebody.
+    #[binary(tag = 5)]
+    Kill {
+        /// The impostor.
+        killer: PlayerId,
+        /// Who they killed.
+        victim: PlayerId,
+    },
+    /// A player reported a body or pressed the emergency button.
+    #[binary(tag = 6)]
+    Report {
+        /// The player.
+        reporter: PlayerId,
+        /// The body they found, or `None` for the emergency button.
+        body: Option<PlayerId>,
+    },
+    /// A meeting started.
+    #[binary(tag = 7)]
+    Meeting {
+        /// Who called the meeting.
+        caller: PlayerId,
+        /// The body that was reported, or `None` for the emergency button.
+        body: Option<PlayerId>,
+        /// How long there is to talk and vote, in seconds.
+        duration: u16,
+    },
+    /// A player voted in a meeting.
+    #[binary(tag = 8)]
+    Vote {
+        /// The player.
+        voter: PlayerId,
+        /// Who they voted for, or `None` to skip.
+        target: Option<PlayerId>,
+    },
+    /// The game is over.
+    #[binary(tag = 9)]
+    GameOver {
+        /// The team that won.
+        winner: Team,
+        /// How they won.
+        reason: GameOverReason,
+    },
+}
+
+/// Why a player left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum LeaveReason {
+    /// They left by themselves.
+    Quit,
+    /// Their connection was lost.
+    Disconnected,
+    /// The host kicked them.
+    Kicked,
+    /// The host banned them.
+    Banned,
+}
+
+/// A side in a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum Team {
+    /// The crewmates.
+    Crewmates,
+    /// The impostors.
+    Impostors,
+}
+
+/// How a game was won.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode)]
+pub enum GameOverReason {
+    /// The crewmates finished all of their tasks.
+    Tasks,
+    /// The other team was voted out.
+    Vote,
+    /// The impostors killed enough crewmates.
+    Kills,
+    /// A sabotage wasn't fixed in time.
+    Sabotage,
+    /// The other team left.
+    Disconnect,
+}
@@ -0,0 +1,471 @@
+//! Connections.
+
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::net::binary::decode::{BorrowDecode, Cursor};
+use crate::net::binary::encode::{CursorMut, Encode};
+use crate::net::transport::{Channel, Config, DisconnectReason, Error, Event};
+
+/// The most bytes the header of a datagram takes.
+pub(super) const HEADER: usize = 8;
+
+/// How many reliable datagrams can be in flight at once.
+///
+/// This has to be well under half of the `u16` ids, so it's always clear
+/// whether an id is old or new.
+const WINDOW: usize = 512;
+
+/// How long a round trip is assumed to take before it has been measured.
+const INITIAL_RTT: Duration = Duration::from_millis(100);
+
+/// How many times a disconnect is sent, since it isn't acked.
+const DISCONNECTS: usize = 3;
+
+/// Everything that is sent in a datagram.
+#[derive(Debug, Encode, BorrowDecode)]
+pub(super) enum Datagram<'a> {
+    /// A client wanting to connect.
+    #[binary(tag = 0)]
+    Connect,
+    /// The server accepting a client.
+    #[binary(tag = 1)]
+    Accept,
+    /// A message that isn't resent if it's lost.
+    #[binary(tag = 2)]
+    Unreliable { payload: &'a [u8] },
+    /// A fragment of a message that is resent until it's acked.
+    #[binary(tag = 3)]
+    Reliable {
+        id: u16,
+        fragment: u8,
+        fragments: u8,
+        payload: &'a [u8],
+    },
+    /// Reliable datagrams that were received: `id`, and every id up to 32
+    /// before it with its bit set in `bits`.
+    #[binary(tag = 4)]
+    Ack { id: u16, bits: u32 },
+    /// Nothing, to keep the connection alive.
+    #[binary(tag = 5)]
+    Ping,
+    /// The other side closing the connection.
+    #[binary(tag = 6)]
+    Disconnect,
+}
+
+impl<'a> Datagram<'a> {
+    /// Decode a datagram, failing if there is anything after it.
+    pub(super) fn from_bytes(bytes: &'a [u8]) -> Option<Datagram<'a>> {
+        let mut cursor = Cursor::new(bytes);
+
+        match cursor.borrow_decode() {
+            Ok(datagram) if cursor.remaining() == 0 => Some(datagram),
+            _ => None,
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut cursor = CursorMut::new();
+        cursor.encode(self).expect("datagrams are never too long to encode");
+
+        cursor.into()
+    }
+}
+
+/// A reliable datagram that hasn't been acked yet.
+struct Sent {
+    bytes: Vec<u8>,
+    sent: Instant,
+    resent: bool,
+}
+
+/// A fragment of a reliable message that was received.
+struct Fragment {
+    fragment: u8,
+    fragments: u8,
+    payload: Vec<u8>,
+}
+
+/// A connection to another endpoint.
+///
+/// Reliable messages are split into fragments that fit in a datagram, and
+/// every fragment gets the next `u16` id. Fragments are resent until they
+/// are acked, and are delivered in the order of their ids, so whole messages
+/// come out in the order they went in.
+pub struct Connection {
+    addr: SocketAddr,
+    config: Config,
+    connected: bool,
+    /// The id of the first datagram in `window`.
+    send_base: u16,
+    /// The id the next fragment that is sent gets.
+    next_id: u16,
+    /// The datagrams that were sent, `None` once they are acked.
+    window: VecDeque<Option<Sent>>,
+    /// The datagrams waiting for room in the window.
+    pending: VecDeque<Vec<u8>>,
+    /// The id of the first fragment in `received`.
+    receive_base: u16,
+    /// The fragments that were received out of order.
+    received: VecDeque<Option<Fragment>>,
+    /// The bytes of payload in `received`.
+    received_bytes: usize,
+    /// The message the fragments are put back together in.
+    message: Vec<u8>,
+    fragments: u8,
+    rtt: Option<Duration>,
+    last_received: Instant,
+    last_sent: Instant,
+    outbox: Vec<Vec<u8>>,
+}
+
+impl Connection {
+    /// Create a new connection to `addr`.
+    ///
+    /// A connection that isn't `connected` yet keeps asking to connect until
+    /// it gets an answer.
+    pub(super) fn new(addr: SocketAddr, config: Config, connected: bool, now: Instant) -> Connection {
+        let mut connection = Connection {
+            addr,
+            config,
+            connected,
+            send_base: 0,
+            next_id: 0,
+            window: VecDeque::new(),
+            pending: VecDeque::new(),
+            receive_base: 0,
+            received: VecDeque::new(),
+            received_bytes: 0,
+            message: Vec::new(),
+            fragments: 0,
+            rtt: None,
+            last_received: now,
+            last_sent: now,
+            outbox: Vec::new(),
+        };
+
+        if !connected {
+            connection.push(&Datagram::Connect, now);
+        }
+
+        connection
+    }
+
+    /// The address of the other endpoint.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Check if the other endpoint has accepted the connection.
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    /// The round trip time, once it has been measured.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    /// The number of reliable datagrams that haven't been acked yet.
+    pub fn unacked(&self) -> usize {
+        self.window.iter().flatten().count() + self.pending.len()
+    }
+
+    /// How long to wait for an ack before resending a datagram.
+    pub fn resend_timeout(&self) -> Duration {
+        let rtt = self.rtt.unwrap_or(INITIAL_RTT);
+
+        (rtt * 2).max(self.config.min_resend()).min(self.config.max_resend())
+    }
+
+    /// Send a message.
+    pub(super) fn send(&mut self, channel: Channel, payload: &[u8], now: Instant) -> Result<(), Error> {
+        let max = self.config.mtu() - HEADER;
+
+        match channel {
+            Channel::Unreliable => {
+                if payload.len() > max {
+                    return Err(Error::TooLong {
+                        len: payload.len(),
+                        max,
+                    });
+                }
+
+                // there is no point in queueing a message that can be lost
+                if self.connected {
+                    self.push(&Datagram::Unreliable { payload }, now);
+                }
+            }
+            Channel::Reliable => {
+                let fragments = payload.len().div_ceil(max).max(1);
+
+                if fragments > u8::MAX as usize {
+                    return Err(Error::TooLong {
+                        len: payload.len(),
+                        max: max * u8::MAX as usize,
+                    });
+                }
+
+                for fragment in 0..fragments {
+                    let payload = &payload[(fragment * max).min(payload.len())..((fragment + 1) * max).min(payload.len())];
+                    let datagram = Datagram::Reliable {
+                        id: self.next_id,
+                        fragment: fragment as u8,
+                        fragments: fragments as u8,
+                        payload,
+                    };
+
+                    self.pending.push_back(datagram.to_bytes());
+                    self.next_id = self.next_id.wrapping_add(1);
+                }
+
+                self.pump(now);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handle a datagram from the other endpoint.
+    pub(super) fn receive(&mut self, datagram: Datagram, now: Instant, events: &mut Vec<Event>) {
+        self.last_received = now;
+
+        match datagram {
+            Datagram::Connect => {
+                // the accept was lost, so the client is still asking
+                if self.connected {
+                    self.push(&Datagram::Accept, now);
+                }
+
+                return;
+            }
+            Datagram::Disconnect => {
+                events.push(Event::Disconnected(self.addr, DisconnectReason::Closed));
+                return;
+            }
+            _ => {}
+        }
+
+        // anything else means the other side accepted us, even if the accept
+        // itself was lost
+        if !self.connected {
+            self.connected = true;
+            events.push(Event::Connected(self.addr));
+            self.pump(now);
+        }
+
+        match datagram {
+            Datagram::Unreliable { payload } => {
+                events.push(Event::Message(self.addr, Channel::Unreliable, payload.to_vec()));
+            }
+            Datagram::Reliable {
+                id,
+                fragment,
+                fragments,
+                payload,
+            } => self.receive_reliable(id, fragment, fragments, payload, now, events),
+            Datagram::Ack { id, bits } => {
+                self.ack(id, now);
+
+                for i in 0..32 {
+                    if bits >> i & 1 == 1 {
+                        self.ack(id.wrapping_sub(i + 1), now);
+                    }
+                }
+
+                while let Some(None) = self.window.front() {
+                    self.window.pop_front();
+                    self.send_base = self.send_base.wrapping_add(1);
+                }
+
+                self.pump(now);
+            }
+            Datagram::Connect | Datagram::Accept | Datagram::Ping | Datagram::Disconnect => {}
+        }
+    }
+
+    fn receive_reliable(&mut self, id: u16, fragment: u8, fragments: u8, payload: &[u8], now: Instant, events: &mut Vec<Event>) {
+        // a well-behaved endpoint never sends a fragment past the end of its
+        // message
+        if fragment >= fragments {
+            return;
+        }
+
+        let index = id.wrapping_sub(self.receive_base) as usize;
+
+        if index < WINDOW {
+            if !matches!(self.received.get(index), Some(Some(_))) {
+                // the next fragment in order only has to fit with the message
+                // it's part of, so the fragments after it can't stop it
+                let buffered = if index == 0 {
+                    self.message.len() + payload.len()
+                } else {
+                    self.received_bytes + self.message.len() + payload.len()
+                };
+
+                // without an ack it's resent, by which time there might be
+                // room for it
+                if buffered > self.config.max_reassembly() {
+                    return;
+                }
+
+                if self.received.len() <= index {
+                    self.received.resize_with(index + 1, || None);
+                }
+
+                self.received_bytes += payload.len();
+                self.received[index] = Some(Fragment {
+                    fragment,
+                    fragments,
+                    payload: payload.to_vec(),
+                });
+            }
+        } else if index < 1 << 15 {
+            // too far ahead to have been sent by a well-behaved endpoint
+            return;
+        }
+
+        // ids behind the base were already delivered, but the ack for them
+        // must have been lost
+        let bits = self.ack_bits(id);
+        self.push(&Datagram::Ack { id, bits }, now);
+
+        while let Some(Some(_)) = self.received.front() {
+            let fragment = self.received.pop_front().flatten().expect("the front was just checked");
+            self.receive_base = self.receive_base.wrapping_add(1);
+            self.received_bytes -= fragment.payload.len();
+
+            if fragment.fragment != self.fragments {
+                // the fragments of a message are always sent in order, so
+                // the other side is broken
+                self.message.clear();
+                self.fragments = 0;
+                continue;
+            }
+
+            self.message.extend_from_slice(&fragment.payload);
+            self.fragments += 1;
+
+            if self.fragments == fragment.fragments {
+                let message = std::mem::take(&mut self.message);
+                events.push(Event::Message(self.addr, Channel::Reliable, message));
+                self.fragments = 0;
+            }
+        }
+    }
+
+    /// Which of the 32 ids before `id` were received.
+    fn ack_bits(&self, id: u16) -> u32 {
+        let mut bits = 0;
+
+        for i in 0..32 {
+            let index = id.wrapping_sub(i + 1).wrapping_sub(self.receive_base) as usize;
+            let received = index >= 1 << 15 || matches!(self.received.get(index), Some(Some(_)));
+
+            if received {
+                bits |= 1 << i;
+            }
+        }
+
+        bits
+    }
+
+    fn ack(&mut self, id: u16, now: Instant) {
+        let index = id.wrapping_sub(self.send_base) as usize;
+
+        if let Some(sent) = self.window.get_mut(index).and_then(Option::take) {
+            // a resent datagram could have been acked for any of its sends
+            if !sent.resent {
+                let sample = now - sent.sent;
+
+                self.rtt = Some(match self.rtt {
+                    Some(rtt) => rtt.mul_f64(0.875) + sample.mul_f64(0.125),
+                    None => sample,
+                });
+            }
+        }
+    }
+
+    /// Send datagrams that are waiting while there is room in the window.
+    fn pump(&mut self, now: Instant) {
+        if !self.connected {
+            return;
+        }
+
+        while self.window.len() < WINDOW {
+            let bytes = match self.pending.pop_front() {
+                Some(bytes) => bytes,
+                None => break,
+            };
+
+            self.push_bytes(bytes.clone(), now);
+            self.window.push_back(Some(Sent {
+                bytes,
+                sent: now,
+                resent: false,
+            }));
+        }
+    }
+
+    /// Resend what wasn't acked and keep the connection alive.
+    ///
+    /// This returns why the connection was lost, if it was.
+    pub(super) fn update(&mut self, now: Instant) -> Option<DisconnectReason> {
+        if now - self.last_received >= self.config.timeout() {
+            return Some(DisconnectReason::Timeout);
+        }
+
+        let timeout = self.resend_timeout();
+
+        if !self.connected {
+            if now - self.last_sent >= timeout {
+                self.push(&Datagram::Connect, now);
+            }
+
+            return None;
+        }
+
+        let mut resends = Vec::new();
+
+        for sent in self.window.iter_mut().flatten() {
+            if now - sent.sent >= timeout {
+                resends.push(sent.bytes.clone());
+                sent.sent = now;
+                sent.resent = true;
+            }
+        }
+
+        for bytes in resends {
+            self.push_bytes(bytes, now);
+        }
+
+        if now - self.last_sent >= self.config.keepalive() {
+            self.push(&Datagram::Ping, now);
+        }
+
+        None
+    }
+
+    /// Tell the other endpoint the connection is closed.
+    pub(super) fn close(&mut self, now: Instant) {
+        for _ in 0..DISCONNECTS {
+            self.push(&Datagram::Disconnect, now);
+        }
+    }
+
+    /// Take the datagrams that are waiting to be sent.
+    pub(super) fn drain(&mut self) -> std::vec::Drain<'_, Vec<u8>> {
+        self.outbox.drain(..)
+    }
+
+    fn push(&mut self, datagram: &Datagram, now: Instant) {
+        self.push_bytes(datagram.to_bytes(), now);
+    }
+
+    fn push_bytes(&mut self, bytes: Vec<u8>, now: Instant) {
+        self.outbox.push(bytes);
+        self.last_sent = now;
+    }
+}
@@ -0,0 +1,421 @@
+//! Reliable messages over UDP.
+//!
+//! Like Hazel, every message is sent on a [`Channel`]. Unreliable messages
+//! are sent once and can be lost, duplicated or arrive out of order, which
+//! is fine for things like movement that are sent all the time anyway.
+//! Reliable messages are resent until they are acked and arrive exactly
+//! once, in the order they were sent, and can be bigger than a datagram.
+//!
+//! An [`Endpoint`] owns a [`Socket`] and all of its [`Connection`]s. Nothing
+//! happens in the background: the endpoint has to be [`poll`](Endpoint::poll)ed
+//! regularly to receive datagrams, resend what was lost and keep connections
+//! alive. Every call takes the current time instead of reading the clock, so
+//! an endpoint can be run on simulated time too, like on a simulated
+//! [`Network`](sim::Network).
+//!
+//! A connection that hasn't received anything for
+//! [`Config::timeout`] is disconnected. Endpoints ping each other when they
+//! have nothing else to send, so that only happens when the other side is
+//! really gone.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+pub mod connection;
+pub mod sim;
+pub mod socket;
+
+pub use self::connection::Connection;
+pub use self::socket::{Lossy, Socket};
+
+use self::connection::{Datagram, HEADER};
+
+/// How a message is sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Sent once, and might not arrive.
+    Unreliable,
+    /// Resent until it arrives, and delivered in order.
+    Reliable,
+}
+
+/// Something that happened on an [`Endpoint`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A connection was made, either by us or by the other side.
+    Connected(SocketAddr),
+    /// A message was received.
+    Message(SocketAddr, Channel, Vec<u8>),
+    /// A connection was lost.
+    Disconnected(SocketAddr, DisconnectReason),
+}
+
+/// Why a connection was lost.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DisconnectReason {
+    /// The other side closed the connection.
+    Closed,
+    /// Nothing was heard from the other side for too long.
+    Timeout,
+}
+
+/// The configuration of an [`Endpoint`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    mtu: usize,
+    keepalive: Duration,
+    timeout: Duration,
+    min_resend: Duration,
+    max_resend: Duration,
+    max_connections: usize,
+    max_reassembly: usize,
+}
+
+impl Config {
+    /// Create the default configuration.
+    pub fn new() -> Config {
+        Config {
+            mtu: 1200,
+            keepalive: Duration::from_secs(1),
+            timeout: Duration::from_secs(10),
+            min_resend: Duration::from_millis(20),
+            max_resend: Duration::from_secs(1),
+            max_connections: 1024,
+            max_reassembly: 256 * 1024,
+        }
+    }
+
+    /// The biggest datagram that is sent, in bytes.
+    pub fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    /// Set the biggest datagram that is sent.
+    ///
+    /// # Panics
+    /// Panics if `mtu` doesn't leave room for a payload after the header.
+    pub fn set_mtu(&mut self, mtu: usize) {
+        assert!(mtu > HEADER, "the mtu must be bigger than the header of a datagram");
+        self.mtu = mtu;
+    }
+
+    /// How long to wait without sending anything before sending a ping.
+    pub fn keepalive(&self) -> Duration {
+        self.keepalive
+    }
+
+    /// Set how long to wait without sending anything before sending a ping.
+    pub fn set_keepalive(&mut self, keepalive: Duration) {
+        self.keepalive = keepalive;
+    }
+
+    /// How long to wait without receiving anything before disconnecting.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Set how long to wait without receiving anything before disconnecting.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// The shortest time to wait for an ack before resending.
+    pub fn min_resend(&self) -> Duration {
+        self.min_resend
+    }
+
+    /// Set the shortest time to wait for an ack before resending.
+    pub fn set_min_resend(&mut self, min_resend: Duration) {
+        self.min_resend = min_resend;
+    }
+
+    /// The longest time to wait for an ack before resending.
+    pub fn max_resend(&self) -> Duration {
+        self.max_resend
+    }
+
+    /// Set the longest time to wait for an ack before resending.
+    pub fn set_max_resend(&mut self, max_resend: Duration) {
+        self.max_resend = max_resend;
+    }
+
+    /// The most connections an endpoint accepts.
+    ///
+    /// Anyone asking to connect past this is ignored.
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    /// Set the most connections an endpoint accepts.
+    pub fn set_max_connections(&mut self, max_connections: usize) {
+        self.max_connections = max_connections;
+    }
+
+    /// The most bytes of reliable messages a connection holds on to while
+    /// putting them back together.
+    ///
+    /// Fragments that would go past this are dropped without being acked, so
+    /// the other side resends them later. The next fragment in order only
+    /// counts against the message it's part of, so it always fits unless
+    /// that message is bigger than this, which is never received.
+    pub fn max_reassembly(&self) -> usize {
+        self.max_reassembly
+    }
+
+    /// Set the most bytes of reliable messages a connection holds on to while
+    /// putting them back together.
+    pub fn set_max_reassembly(&mut self, max_reassembly: usize) {
+        self.max_reassembly = max_reassembly;
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
+}
+
+/// An error that can occur on an [`Endpoint`].
+#[derive(Debug)]
+pub enum Error {
+    /// The socket failed.
+    Io(io::Error),
+    /// There is no connection to the address.
+    NotConnected(SocketAddr),
+    /// A message is too long for its channel.
+    TooLong {
+        /// The length of the message.
+        len: usize,
+        /// The longest message the channel can send.
+        max: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "socket error: {}", e),
+            Error::NotConnected(addr) => write!(f, "not connected to {}", addr),
+            Error::TooLong { len, max } => write!(f, "a message of {} bytes is longer than the maximum of {}", len, max),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// One end of any number of connections.
+pub struct Endpoint<S = UdpSocket>
+where S: Socket {
+    socket: S,
+    config: Config,
+    accept: bool,
+    connections: BTreeMap<SocketAddr, Connection>,
+    buf: Vec<u8>,
+}
+
+impl Endpoint {
+    /// Bind a UDP socket to `addr` and create an endpoint on it.
+    pub fn bind(addr: impl ToSocketAddrs, config: Config) -> io::Result<Endpoint> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Endpoint::new(socket, config))
+    }
+}
+
+impl<S> Endpoint<S>
+where S: Socket {
+    /// Create an endpoint on a socket.
+    ///
+    /// The socket must never block when receiving. New endpoints don't
+    /// accept connections; see [`set_accept`](Endpoint::set_accept).
+    pub fn new(socket: S, config: Config) -> Endpoint<S> {
+        Endpoint {
+            socket,
+            config,
+            accept: false,
+            connections: BTreeMap::new(),
+            buf: vec![0; u16::MAX as usize],
+        }
+    }
+
+    /// The socket of the endpoint.
+    pub fn socket(&self) -> &S {
+        &self.socket
+    }
+
+    /// The configuration of the endpoint.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The address of the socket.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Check if the endpoint accepts new connections.
+    pub fn accepts(&self) -> bool {
+        self.accept
+    }
+
+    /// Set if the endpoint accepts new connections, like a server.
+    ///
+    /// No more than [`Config::max_connections`] are accepted.
+    pub fn set_accept(&mut self, accept: bool) {
+        self.accept = accept;
+    }
+
+    /// The connection to `addr`, if there is one.
+    pub fn connection(&self, addr: SocketAddr) -> Option<&Connection> {
+        self.connections.get(&addr)
+    }
+
+    /// All of the connections.
+    pub fn connections(&self) -> impl Iterator<Item = &Connection> {
+        self.connections.values()
+    }
+
+    /// Start connecting to `addr`.
+    ///
+    /// [`Event::Connected`] is returned by [`poll`](Endpoint::poll) once the
+    /// other side accepts. Reliable messages can be sent right away, and are
+    /// held back until then.
+    pub fn connect(&mut self, addr: SocketAddr, now: Instant) -> Result<(), Error> {
+        if !self.connections.contains_key(&addr) {
+            let connection = Connection::new(addr, self.config.clone(), false, now);
+            self.connections.insert(addr, connection);
+        }
+
+        self.flush(addr)
+    }
+
+    /// Send a message to `addr`.
+    pub fn send(&mut self, addr: SocketAddr, channel: Channel, payload: &[u8], now: Instant) -> Result<(), Error> {
+        match self.connections.get_mut(&addr) {
+            Some(connection) => connection.send(channel, payload, now)?,
+            None => return Err(Error::NotConnected(addr)),
+        }
+
+        self.flush(addr)
+    }
+
+    /// Close the connection to `addr`.
+    ///
+    /// Messages that haven't been acked yet are dropped.
+    pub fn disconnect(&mut self, addr: SocketAddr, now: Instant) -> Result<(), Error> {
+        match self.connections.get_mut(&addr) {
+            Some(connection) => connection.close(now),
+            None => return Err(Error::NotConnected(addr)),
+        }
+
+        let result = self.flush(addr);
+        self.connections.remove(&addr);
+
+        result
+    }
+
+    /// Receive everything that arrived, resend what was lost and keep the
+    /// connections alive.
+    ///
+    /// Events are pushed onto `events` as they happen, so the ones from
+    /// before an error aren't lost. Only receiving can fail: a datagram that
+    /// can't be sent to one peer is treated like any other lost datagram, and
+    /// doesn't stop the others from being sent to. Even when receiving fails,
+    /// everything else is still done before the error is returned.
+    pub fn poll(&mut self, now: Instant, events: &mut Vec<Event>) -> Result<(), Error> {
+        let mut error = None;
+
+        loop {
+            let (len, addr) = match self.socket.recv_from(&mut self.buf) {
+                Ok(Some(received)) => received,
+                Ok(None) => break,
+                // the connections still have to be kept alive, so the error
+                // is returned once they are
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            };
+
+            let datagram = match Datagram::from_bytes(&self.buf[..len]) {
+                Some(datagram) => datagram,
+                None => continue,
+            };
+
+            if !self.connections.contains_key(&addr) {
+                match datagram {
+                    Datagram::Connect if self.accept && self.connections.len() < self.config.max_connections() => {
+                        let connection = Connection::new(addr, self.config.clone(), true, now);
+                        self.connections.insert(addr, connection);
+                        events.push(Event::Connected(addr));
+                    }
+                    _ => continue,
+                }
+            }
+
+            let start = events.len();
+            let connection = self.connections.get_mut(&addr).expect("the connection was just checked");
+            connection.receive(datagram, now, events);
+
+            if events[start..].iter().any(|e| matches!(e, Event::Disconnected(..))) {
+                self.connections.remove(&addr);
+            }
+        }
+
+        let mut lost = Vec::new();
+
+        for connection in self.connections.values_mut() {
+            if let Some(reason) = connection.update(now) {
+                lost.push(connection.addr());
+                events.push(Event::Disconnected(connection.addr(), reason));
+            }
+        }
+
+        for addr in lost {
+            self.connections.remove(&addr);
+        }
+
+        let addrs: Vec<_> = self.connections.keys().copied().collect();
+
+        for addr in addrs {
+            // reliable datagrams are resent anyway, and a peer that can never
+            // be sent to times out
+            let _ = self.flush(addr);
+        }
+
+        match error {
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self, addr: SocketAddr) -> Result<(), Error> {
+        let connection = match self.connections.get_mut(&addr) {
+            Some(connection) => connection,
+            None => return Ok(()),
+        };
+
+        for bytes in connection.drain() {
+            self.socket.send_to(&bytes, addr)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,285 @@
+//! Simulated networks.
+//!
+//! A [`Network`] connects [`SimSocket`]s in memory, and makes the datagrams
+//! between them late, lost, duplicated or out of order according to its
+//! [`Conditions`]. Time on the network only moves when it's
+//! [`advance`](Network::advance)d, and everything random comes from an
+//! [`Rng`] with a seed, so a test on a simulated network does exactly the
+//! same thing every time it's run.
+
+use std::cell::RefCell;
+use std::io;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::net::transport::Socket;
+
+/// A small random number generator that can be seeded.
+///
+/// This is xorshift, which is fast and good enough to simulate a network,
+/// but not for anything that has to be unpredictable.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// Create a new generator from a seed.
+    pub fn new(seed: u64) -> Rng {
+        // xorshift gets stuck on zero
+        Rng { state: seed | 1 }
+    }
+
+    /// Get a random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        self.state
+    }
+
+    /// Get a random number from `0.0` up to, but not including, `1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Return `true` with a probability of `p`, from `0.0` to `1.0`.
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+
+    /// Get a random duration from zero up to `max`.
+    pub fn duration(&mut self, max: Duration) -> Duration {
+        max.mul_f64(self.next_f64())
+    }
+}
+
+/// How bad a simulated network is.
+///
+/// Probabilities are from `0.0` to `1.0`. The default is a perfect network.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Conditions {
+    latency: Duration,
+    jitter: Duration,
+    loss: f64,
+    duplicate: f64,
+    reorder: f64,
+}
+
+impl Conditions {
+    /// Create the conditions of a perfect network.
+    pub fn new() -> Conditions {
+        Conditions::default()
+    }
+
+    /// How long every datagram takes to arrive.
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Set how long every datagram takes to arrive.
+    pub fn set_latency(&mut self, latency: Duration) {
+        self.latency = latency;
+    }
+
+    /// The most extra time a datagram can take, on top of the latency.
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Set the most extra time a datagram can take.
+    pub fn set_jitter(&mut self, jitter: Duration) {
+        self.jitter = jitter;
+    }
+
+    /// The probability that a datagram is lost.
+    pub fn loss(&self) -> f64 {
+        self.loss
+    }
+
+    /// Set the probability that a datagram is lost.
+    pub fn set_loss(&mut self, loss: f64) {
+        self.loss = loss;
+    }
+
+    /// The probability that a datagram arrives twice.
+    pub fn duplicate(&self) -> f64 {
+        self.duplicate
+    }
+
+    /// Set the probability that a datagram arrives twice.
+    pub fn set_duplicate(&mut self, duplicate: f64) {
+        self.duplicate = duplicate;
+    }
+
+    /// The probability that a datagram is held back, so it arrives after
+    /// datagrams that were sent after it.
+    pub fn reorder(&self) -> f64 {
+        self.reorder
+    }
+
+    /// Set the probability that a datagram is held back.
+    pub fn set_reorder(&mut self, reorder: f64) {
+        self.reorder = reorder;
+    }
+
+    /// How long a datagram takes to arrive.
+    fn delay(&self, rng: &mut Rng) -> Duration {
+        let mut delay = self.latency + rng.duration(self.jitter);
+
+        if rng.chance(self.reorder) {
+            // held back by at least a full latency, so later datagrams
+            // overtake it
+            delay += (self.latency + self.jitter).max(Duration::from_millis(1));
+        }
+
+        delay
+    }
+}
+
+/// A datagram on its way.
+struct InFlight {
+    arrives: Instant,
+    /// Keeps datagrams that arrive at the same time in the order they were
+    /// sent.
+    sequence: u64,
+    from: SocketAddr,
+    to: SocketAddr,
+    bytes: Vec<u8>,
+}
+
+struct Inner {
+    conditions: Conditions,
+    rng: Rng,
+    now: Instant,
+    sequence: u64,
+    in_flight: Vec<InFlight>,
+}
+
+/// A simulated network.
+///
+/// Cloning a `Network` gives another handle to the same network.
+#[derive(Clone)]
+pub struct Network {
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl Network {
+    /// Create a new network, with everything random coming from `seed`.
+    pub fn new(conditions: Conditions, seed: u64) -> Network {
+        Network {
+            inner: Rc::new(RefCell::new(Inner {
+                conditions,
+                rng: Rng::new(seed),
+                now: Instant::now(),
+                sequence: 0,
+                in_flight: Vec::new(),
+            })),
+        }
+    }
+
+    /// The conditions of the network.
+    pub fn conditions(&self) -> Conditions {
+        self.inner.borrow().conditions.clone()
+    }
+
+    /// Change the conditions of the network.
+    ///
+    /// Datagrams that are already on their way aren't affected.
+    pub fn set_conditions(&self, conditions: Conditions) {
+        self.inner.borrow_mut().conditions = conditions;
+    }
+
+    /// The time on the network.
+    ///
+    /// This is what should be passed to endpoints on the network.
+    pub fn now(&self) -> Instant {
+        self.inner.borrow().now
+    }
+
+    /// Move time forward.
+    pub fn advance(&self, duration: Duration) {
+        self.inner.borrow_mut().now += duration;
+    }
+
+    /// The number of datagrams on their way.
+    pub fn in_flight(&self) -> usize {
+        self.inner.borrow().in_flight.len()
+    }
+
+    /// Create a socket on the network with the address `addr`.
+    pub fn socket(&self, addr: SocketAddr) -> SimSocket {
+        SimSocket {
+            addr,
+            network: self.clone(),
+        }
+    }
+}
+
+/// A socket on a simulated [`Network`].
+pub struct SimSocket {
+    addr: SocketAddr,
+    network: Network,
+}
+
+impl SimSocket {
+    /// The network the socket is on.
+    pub fn network(&self) -> &Network {
+        &self.network
+    }
+}
+
+impl Socket for SimSocket {
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<()> {
+        let inner = &mut *self.network.inner.borrow_mut();
+
+        if inner.rng.chance(inner.conditions.loss) {
+            return Ok(());
+        }
+
+        let copies = if inner.rng.chance(inner.conditions.duplicate) { 2 } else { 1 };
+
+        for _ in 0..copies {
+            let arrives = inner.now + inner.conditions.delay(&mut inner.rng);
+
+            inner.in_flight.push(InFlight {
+                arrives,
+                sequence: inner.sequence,
+                from: self.addr,
+                to: addr,
+                bytes: buf.to_vec(),
+            });
+            inner.sequence += 1;
+        }
+
+        Ok(())
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        let inner = &mut *self.network.inner.borrow_mut();
+
+        let next = inner
+            .in_flight
+            .iter()
+            .enumerate()
+            .filter(|(_, datagram)| datagram.to == self.addr && datagram.arrives <= inner.now)
+            .min_by_key(|(_, datagram)| (datagram.arrives, datagram.sequence))
+            .map(|(i, _)| i);
+
+        let datagram = match next {
+            Some(i) => inner.in_flight.remove(i),
+            None => return Ok(None),
+        };
+
+        // like a real socket, whatever doesn't fit in the buffer is lost
+        let len = datagram.bytes.len().min(buf.len());
+        buf[..len].copy_from_slice(&datagram.bytes[..len]);
+
+        Ok(Some((len, datagram.from)))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        Ok(self.addr)
+    }
+}
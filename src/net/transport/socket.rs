@@ -0,0 +1,102 @@
+//! Sockets.
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::net::transport::sim::Rng;
+
+/// Something datagrams can be sent and received with.
+///
+/// This is implemented for [`UdpSocket`], and can be implemented by anything
+/// else to test an [`Endpoint`](super::Endpoint) without a real network.
+pub trait Socket {
+    /// Send a datagram to `addr`.
+    ///
+    /// Datagrams can be lost, so a datagram that can't be sent right now can
+    /// be dropped without an error.
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<()>;
+
+    /// Receive a datagram, returning its length and who sent it.
+    ///
+    /// This never blocks, and returns `None` if nothing has been received.
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>>;
+
+    /// The address of the socket.
+    fn local_addr(&self) -> io::Result<SocketAddr>;
+}
+
+impl Socket for UdpSocket {
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<()> {
+        match UdpSocket::send_to(self, buf, addr) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        loop {
+            match UdpSocket::recv_from(self, buf) {
+                Ok(received) => return Ok(Some(received)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+                // some platforms report a datagram that couldn't be delivered
+                // on the next receive, which isn't a problem for us
+                Err(e) if e.kind() == io::ErrorKind::ConnectionReset => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        UdpSocket::local_addr(self)
+    }
+}
+
+/// A socket that loses some of the datagrams it sends.
+///
+/// Which datagrams are lost only depends on the seed, so tests that use it
+/// lose the same datagrams every time they are run. For more than loss, see
+/// [`sim`](super::sim).
+pub struct Lossy<S>
+where S: Socket {
+    inner: S,
+    loss: f64,
+    rng: Rng,
+}
+
+impl<S> Lossy<S>
+where S: Socket {
+    /// Wrap a socket, losing `loss` of the datagrams it sends, from `0.0` to
+    /// `1.0`.
+    pub fn new(inner: S, loss: f64, seed: u64) -> Lossy<S> {
+        Lossy {
+            inner,
+            loss,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Get the socket back.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S> Socket for Lossy<S>
+where S: Socket {
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<()> {
+        if self.rng.chance(self.loss) {
+            return Ok(());
+        }
+
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        self.inner.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
@@ -0,0 +1,140 @@
+//! Lobbies.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::game::State;
+use crate::math::*;
+use crate::net::protocol::{LobbyInfo, PlayerId, Reject, RoomCode};
+
+/// A game, and the players in it.
+///
+/// The player that creates a lobby is its host, and is the only one that
+/// can kick or ban players. When the host leaves, the player with the lowest
+/// id becomes the host instead.
+pub struct Lobby {
+    code: RoomCode,
+    public: bool,
+    host: PlayerId,
+    state: State,
+    addrs: BTreeMap<PlayerId, SocketAddr>,
+    /// Banned players can't come back, even with a new connection.
+    banned: BTreeSet<IpAddr>,
+}
+
+impl Lobby {
+    /// Create a new, empty lobby.
+    ///
+    /// # Panics
+    /// Panics if `max_players` is zero or more than
+    /// [`MAX_PLAYERS`](crate::game::MAX_PLAYERS).
+    pub fn new(code: RoomCode, public: bool, max_players: usize) -> Lobby {
+        let mut state = State::new();
+        state.set_max_players(max_players);
+
+        Lobby {
+            code,
+            public,
+            host: PlayerId(0),
+            state,
+            addrs: BTreeMap::new(),
+            banned: BTreeSet::new(),
+        }
+    }
+
+    /// The code to join the lobby with.
+    pub fn code(&self) -> RoomCode {
+        self.code
+    }
+
+    /// Check if the lobby is listed for anybody to join.
+    pub fn is_public(&self) -> bool {
+        self.public
+    }
+
+    /// The player that runs the lobby.
+    pub fn host(&self) -> PlayerId {
+        self.host
+    }
+
+    /// The state of the game.
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    /// The state of the game.
+    pub fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
+
+    /// The address of a player.
+    pub fn addr(&self, player: PlayerId) -> Option<SocketAddr> {
+        self.addrs.get(&player).copied()
+    }
+
+    /// The addresses of every player.
+    pub fn addrs(&self) -> impl Iterator<Item = SocketAddr> + '_ {
+        self.addrs.values().copied()
+    }
+
+    /// Check if an address is banned from the lobby.
+    pub fn is_banned(&self, ip: IpAddr) -> bool {
+        self.banned.contains(&ip)
+    }
+
+    /// What a client needs to show the lobby.
+    pub fn info(&self) -> LobbyInfo {
+        LobbyInfo {
+            code: self.code,
+            public: self.public,
+            host: self.host,
+            host_name: self.state.player(self.host).map(|player| player.name().to_string()).unwrap_or_default(),
+            players: self.state.len() as u8,
+            max_players: self.state.max_players() as u8,
+            started: self.state.is_started(),
+        }
+    }
+
+    /// Add a player at `spawn`, returning their id.
+    ///
+    /// The first player to join becomes the host.
+    pub fn join(&mut self, addr: SocketAddr, name: &str, spawn: Vector2) -> Result<PlayerId, Reject> {
+        if self.is_banned(addr.ip()) {
+            return Err(Reject::Banned);
+        }
+
+        if self.state.is_started() {
+            return Err(Reject::Started);
+        }
+
+        let player = self.state.join(name, spawn).ok_or(Reject::Full)?;
+        self.addrs.insert(player, addr);
+
+        if self.state.len() == 1 {
+            self.host = player;
+        }
+
+        Ok(player)
+    }
+
+    /// Remove a player, moving the host to somebody else if it was them.
+    pub fn leave(&mut self, player: PlayerId) {
+        self.state.leave(player);
+        self.addrs.remove(&player);
+
+        if player == self.host {
+            if let Some((next, _)) = self.state.players().next() {
+                self.host = next;
+            }
+        }
+    }
+
+    /// Remove a player, and don't let them back in.
+    pub fn ban(&mut self, player: PlayerId) {
+        if let Some(addr) = self.addr(player) {
+            self.banned.insert(addr.ip());
+        }
+
+        self.leave(player);
+    }
+}
@@ -0,0 +1,666 @@
+//! The dedicated server.
+//!
+//! A [`Server`] hosts any number of [`Lobby`]s, up to
+//! [`Config::max_lobbies`], and every lobby owns the real [`State`] of its
+//! game. Clients only ever send what they want to do, like which way they
+//! are walking; the server steps every game at a fixed tick rate and tells
+//! everybody in a lobby where everyone ended up.
+//!
+//! Everything is sent as a [`Packet`] over a reliable
+//! [`transport`](crate::net::transport). Movement is sent unreliably, since
+//! it's sent every tick anyway, and everything else is sent reliably.
+//!
+//! [`State`]: crate::game::State
+
+use std::collections::hash_map::RandomState;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub mod lobby;
+
+pub use self::lobby::Lobby;
+
+use crate::game::movement::Controller;
+use crate::game::{MAX_PLAYERS, RADIUS};
+use crate::map::Map;
+use crate::math::*;
+use crate::net::protocol::{self, LeaveReason, LobbyRequest, Message, Packet, PlayerId, Reject, RoomCode, Welcome};
+use crate::net::transport::sim::Rng;
+use crate::net::transport::{self, Channel, DisconnectReason, Endpoint, Event, Socket};
+
+/// How many random codes are tried for a new lobby before giving up.
+///
+/// At most half of the codes can be taken, so running out of tries is
+/// vanishingly unlikely.
+const CODE_TRIES: usize = 32;
+
+/// The configuration of a [`Server`].
+///
+/// With the `serde` feature, this can be loaded from a config file. Anything
+/// that is missing from it is left at its default.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Config {
+    port: u16,
+    max_lobbies: usize,
+    tick_rate: u32,
+    code_length: usize,
+}
+
+impl Config {
+    /// Create the default configuration.
+    pub fn new() -> Config {
+        Config {
+            port: 22023,
+            max_lobbies: 16,
+            tick_rate: 30,
+            code_length: 6,
+        }
+    }
+
+    /// The UDP port to listen on.
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Set the UDP port to listen on.
+    pub fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// The most lobbies that can be open at once.
+    ///
+    /// This can't be more than half of the room codes there are, so a new
+    /// lobby can always find a code quickly.
+    pub fn max_lobbies(&self) -> usize {
+        self.max_lobbies
+    }
+
+    /// Set the most lobbies that can be open at once.
+    pub fn set_max_lobbies(&mut self, max_lobbies: usize) {
+        self.max_lobbies = max_lobbies;
+    }
+
+    /// How many times a second games are stepped.
+    pub fn tick_rate(&self) -> u32 {
+        self.tick_rate
+    }
+
+    /// Set how many times a second games are stepped.
+    ///
+    /// # Panics
+    /// Panics if `tick_rate` is zero.
+    pub fn set_tick_rate(&mut self, tick_rate: u32) {
+        assert!(tick_rate > 0, "the tick rate can't be zero");
+        self.tick_rate = tick_rate;
+    }
+
+    /// How long a tick is.
+    pub fn tick(&self) -> Duration {
+        Duration::from_secs(1) / self.tick_rate.max(1)
+    }
+
+    /// How many letters the codes of new lobbies have.
+    pub fn code_length(&self) -> usize {
+        self.code_length
+    }
+
+    /// Set how many letters the codes of new lobbies have.
+    ///
+    /// # Panics
+    /// Panics if `code_length` isn't 4 or 6.
+    pub fn set_code_length(&mut self, code_length: usize) {
+        assert!(code_length == 4 || code_length == 6, "room codes have 4 or 6 letters");
+        self.code_length = code_length;
+    }
+
+    /// Check that a configuration makes sense.
+    ///
+    /// The setters already check what they can on their own, but a config
+    /// file can have anything in it, and the limit on lobbies depends on the
+    /// length of room codes.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.tick_rate == 0 {
+            return Err(ConfigError::TickRate);
+        }
+
+        if self.code_length != 4 && self.code_length != 6 {
+            return Err(ConfigError::CodeLength(self.code_length));
+        }
+
+        let limit = 26usize.pow(self.code_length as u32) / 2;
+
+        if self.max_lobbies > limit {
+            return Err(ConfigError::MaxLobbies(limit));
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
+}
+
+/// An error in the configuration of a [`Server`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The tick rate is zero.
+    TickRate,
+    /// Room codes don't have 4 or 6 letters.
+    CodeLength(usize),
+    /// There can be more lobbies than the limit for the length of room codes.
+    MaxLobbies(usize),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::TickRate => write!(f, "the tick rate can't be zero"),
+            ConfigError::CodeLength(len) => write!(f, "room codes have 4 or 6 letters, not {}", len),
+            ConfigError::MaxLobbies(limit) => {
+                write!(f, "there can't be more than {} lobbies with room codes this long", limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A client that couldn't be sent to.
+///
+/// This only ever affects that one client, so the server keeps running, and
+/// whoever runs it decides what to do about it.
+#[derive(Debug)]
+pub struct ClientError {
+    addr: SocketAddr,
+    error: transport::Error,
+}
+
+impl ClientError {
+    /// The address of the client.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// What went wrong.
+    pub fn error(&self) -> &transport::Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to send to {}: {}", self.addr, self.error)
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// A client of the server.
+enum Client {
+    /// Connected, but hasn't sent a hello yet.
+    Handshake,
+    /// Through the handshake, but not in a lobby yet.
+    Menu {
+        /// The name the player wants to use.
+        name: String,
+    },
+    /// In a lobby.
+    Playing {
+        lobby: RoomCode,
+        player: PlayerId,
+        /// The sequence of the last movement, so older ones are ignored.
+        sequence: Option<u16>,
+    },
+}
+
+/// A dedicated server.
+pub struct Server<S = UdpSocket>
+where S: Socket {
+    endpoint: Endpoint<S>,
+    config: Config,
+    map: Map,
+    controller: Controller,
+    lobbies: BTreeMap<RoomCode, Lobby>,
+    clients: BTreeMap<SocketAddr, Client>,
+    /// Clients that were rejected or kicked, and are disconnected once they
+    /// have been told why.
+    closing: Vec<SocketAddr>,
+    /// Clients that couldn't be sent to since the last update.
+    errors: Vec<ClientError>,
+    rng: Rng,
+    next_tick: Option<Instant>,
+}
+
+impl Server {
+    /// Listen on the port in `config`, on every interface.
+    pub fn bind(config: Config, map: Map) -> std::io::Result<Server> {
+        let endpoint = Endpoint::bind(("0.0.0.0", config.port()), transport::Config::new())?;
+
+        Ok(Server::new(endpoint, config, map))
+    }
+}
+
+impl<S> Server<S>
+where S: Socket {
+    /// Create a server on an endpoint.
+    ///
+    /// The endpoint is set to accept connections.
+    ///
+    /// # Panics
+    /// Panics if `config` isn't [`validate`](Config::validate)d.
+    pub fn new(mut endpoint: Endpoint<S>, config: Config, map: Map) -> Server<S> {
+        if let Err(e) = config.validate() {
+            panic!("invalid server config: {}", e);
+        }
+
+        endpoint.set_accept(true);
+
+        // the hasher of a hash map is randomly seeded by the system, so room
+        // codes can't be guessed from when the server started
+        let seed = RandomState::new().build_hasher().finish();
+
+        Server {
+            endpoint,
+            config,
+            map,
+            controller: Controller::new(RADIUS),
+            lobbies: BTreeMap::new(),
+            clients: BTreeMap::new(),
+            closing: Vec::new(),
+            errors: Vec::new(),
+            rng: Rng::new(seed),
+            next_tick: None,
+        }
+    }
+
+    /// The endpoint of the server.
+    pub fn endpoint(&self) -> &Endpoint<S> {
+        &self.endpoint
+    }
+
+    /// The configuration of the server.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// The map every game is played on.
+    pub fn map(&self) -> &Map {
+        &self.map
+    }
+
+    /// All of the open lobbies.
+    pub fn lobbies(&self) -> impl Iterator<Item = &Lobby> {
+        self.lobbies.values()
+    }
+
+    /// The lobby with a code.
+    pub fn lobby(&self, code: RoomCode) -> Option<&Lobby> {
+        self.lobbies.get(&code)
+    }
+
+    /// Run the server forever, or until the socket can't receive anymore.
+    ///
+    /// Clients that can't be sent to are passed to `on_error`.
+    pub fn run<F>(&mut self, mut on_error: F) -> Result<(), transport::Error>
+    where F: FnMut(ClientError) {
+        let mut errors = Vec::new();
+
+        loop {
+            let updated = self.update(Instant::now(), &mut errors);
+            errors.drain(..).for_each(&mut on_error);
+            updated?;
+
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Handle everything that was received, and step every game as many
+    /// ticks as are due.
+    ///
+    /// This only fails if the socket can't receive. Failing to send to a
+    /// client doesn't get in the way of anybody else, and is pushed onto
+    /// `errors` instead.
+    pub fn update(&mut self, now: Instant, errors: &mut Vec<ClientError>) -> Result<(), transport::Error> {
+        let updated = self.step(now);
+        errors.append(&mut self.errors);
+
+        updated
+    }
+
+    fn step(&mut self, now: Instant) -> Result<(), transport::Error> {
+        let mut events = Vec::new();
+        let polled = self.endpoint.poll(now, &mut events);
+
+        for event in events {
+            self.handle(event, now);
+        }
+
+        polled?;
+
+        let endpoint = &self.endpoint;
+        let (done, waiting) = self.closing.drain(..).partition(|&addr| {
+            endpoint.connection(addr).is_none_or(|connection| connection.unacked() == 0)
+        });
+        self.closing = waiting;
+
+        for addr in done {
+            if self.endpoint.connection(addr).is_some() {
+                if let Err(error) = self.endpoint.disconnect(addr, now) {
+                    self.errors.push(ClientError { addr, error });
+                }
+            }
+        }
+
+        let tick = self.config.tick();
+        let mut next_tick = self.next_tick.unwrap_or(now);
+
+        // a server that fell far behind skips ticks instead of trying to
+        // catch up all at once
+        if now > next_tick + tick * 5 {
+            next_tick = now;
+        }
+
+        while now >= next_tick {
+            self.tick(now);
+            next_tick += tick;
+        }
+
+        self.next_tick = Some(next_tick);
+
+        Ok(())
+    }
+
+    /// Step every game and tell everybody where everyone is.
+    fn tick(&mut self, now: Instant) {
+        let dt = self.config.tick().as_secs_f64() as FLOAT;
+
+        for lobby in self.lobbies.values_mut() {
+            lobby.state_mut().step(dt, &self.controller, self.map.walls());
+
+            let sequence = lobby.state().tick() as u16;
+
+            for (player, state) in lobby.state().players() {
+                let movement = Packet::Message(Message::Movement {
+                    player,
+                    sequence,
+                    position: state.position(),
+                    velocity: state.velocity(),
+                });
+
+                broadcast(&mut self.endpoint, &mut self.errors, lobby, Channel::Unreliable, &movement, now);
+            }
+        }
+    }
+
+    fn handle(&mut self, event: Event, now: Instant) {
+        let (addr, channel, bytes) = match event {
+            Event::Connected(addr) => {
+                self.clients.insert(addr, Client::Handshake);
+                return;
+            }
+            Event::Disconnected(addr, reason) => {
+                let reason = match reason {
+                    DisconnectReason::Closed => LeaveReason::Quit,
+                    DisconnectReason::Timeout => LeaveReason::Disconnected,
+                };
+
+                if let Some(Client::Playing { lobby, player, .. }) = self.clients.remove(&addr) {
+                    self.leave(lobby, player, reason, now);
+                }
+
+                return;
+            }
+            Event::Message(addr, channel, bytes) => (addr, channel, bytes),
+        };
+
+        if let Some(Client::Handshake) = self.clients.get(&addr) {
+            if channel == Channel::Reliable {
+                match protocol::accept(&bytes) {
+                    Ok(hello) => {
+                        let name = hello.name().to_string();
+                        self.clients.insert(addr, Client::Menu { name });
+                    }
+                    Err(reject) => self.reject(addr, reject, now),
+                }
+            }
+
+            return;
+        }
+
+        // clients that send garbage are only hurting themselves
+        let packet = match Packet::from_bytes(&bytes) {
+            Ok(packet) => packet,
+            Err(_) => return,
+        };
+
+        match (self.clients.get(&addr), packet) {
+            (Some(_), Packet::Lobby(LobbyRequest::List)) => {
+                let lobbies = self
+                    .lobbies
+                    .values()
+                    .filter(|lobby| lobby.is_public() && !lobby.state().is_started())
+                    .map(Lobby::info)
+                    .collect();
+
+                send(&mut self.endpoint, &mut self.errors, addr, Channel::Reliable, &Packet::LobbyList(lobbies), now);
+            }
+            (Some(Client::Menu { name }), Packet::Lobby(LobbyRequest::Create { public, max_players })) => {
+                let name = name.clone();
+
+                if self.lobbies.len() >= self.config.max_lobbies {
+                    return self.reject(addr, Reject::TooManyLobbies, now);
+                }
+
+                let code = match self.code() {
+                    Some(code) => code,
+                    None => return self.reject(addr, Reject::TooManyLobbies, now),
+                };
+                let max_players = (max_players as usize).clamp(1, MAX_PLAYERS);
+                self.lobbies.insert(code, Lobby::new(code, public, max_players));
+
+                self.join(addr, &name, code, now);
+            }
+            (Some(Client::Menu { name }), Packet::Lobby(LobbyRequest::Join { code })) => {
+                let name = name.clone();
+                self.join(addr, &name, code, now);
+            }
+            (Some(&Client::Playing { lobby, player, .. }), Packet::Lobby(LobbyRequest::Kick { player: target })) => {
+                self.kick(lobby, player, target, false, now);
+            }
+            (Some(&Client::Playing { lobby, player, .. }), Packet::Lobby(LobbyRequest::Ban { player: target })) => {
+                self.kick(lobby, player, target, true, now);
+            }
+            (Some(&Client::Playing { lobby, player, .. }), Packet::Message(message)) => {
+                self.message(addr, lobby, player, message, now);
+            }
+            _ => {}
+        }
+    }
+
+    /// Make a code no open lobby has, or `None` if none was found in
+    /// [`CODE_TRIES`] tries.
+    fn code(&mut self) -> Option<RoomCode> {
+        for _ in 0..CODE_TRIES {
+            let letters: String = (0..self.config.code_length)
+                .map(|_| (b'A' + (self.rng.next_u64() % 26) as u8) as char)
+                .collect();
+            let code = RoomCode::parse(&letters).expect("the code length is always valid");
+
+            if !self.lobbies.contains_key(&code) {
+                return Some(code);
+            }
+        }
+
+        None
+    }
+
+    fn join(&mut self, addr: SocketAddr, name: &str, code: RoomCode, now: Instant) {
+        let lobby = match self.lobbies.get_mut(&code) {
+            Some(lobby) => lobby,
+            None => return self.reject(addr, Reject::NotFound, now),
+        };
+
+        let spawns = self.map.spawns();
+        let spawn = spawns.get(lobby.state().len() % spawns.len().max(1)).copied().unwrap_or(self.map.button());
+
+        let player = match lobby.join(addr, name, spawn) {
+            Ok(player) => player,
+            Err(reject) => return self.reject(addr, reject, now),
+        };
+
+        self.clients.insert(addr, Client::Playing {
+            lobby: code,
+            player,
+            sequence: None,
+        });
+
+        send(&mut self.endpoint, &mut self.errors, addr, Channel::Reliable, &Packet::Welcome(Welcome::new(player)), now);
+
+        // the new player is told about everybody, and everybody else is only
+        // told about the new player
+        for (other, state) in lobby.state().players() {
+            let join = Packet::Message(Message::Join {
+                player: other,
+                name: state.name().to_string(),
+                color: state.color(),
+            });
+
+            if other == player {
+                broadcast(&mut self.endpoint, &mut self.errors, lobby, Channel::Reliable, &join, now);
+            } else {
+                send(&mut self.endpoint, &mut self.errors, addr, Channel::Reliable, &join, now);
+            }
+        }
+
+        broadcast(&mut self.endpoint, &mut self.errors, lobby, Channel::Reliable, &Packet::LobbyState(lobby.info()), now);
+    }
+
+    fn reject(&mut self, addr: SocketAddr, reject: Reject, now: Instant) {
+        self.clients.remove(&addr);
+        self.closing.push(addr);
+
+        send(&mut self.endpoint, &mut self.errors, addr, Channel::Reliable, &Packet::Reject(reject), now);
+    }
+
+    fn kick(&mut self, code: RoomCode, host: PlayerId, target: PlayerId, ban: bool, now: Instant) {
+        let lobby = match self.lobbies.get_mut(&code) {
+            Some(lobby) => lobby,
+            None => return,
+        };
+
+        let addr = match lobby.addr(target) {
+            Some(addr) if lobby.host() == host && target != host => addr,
+            _ => return,
+        };
+
+        let reason = if ban {
+            lobby.ban(target);
+            LeaveReason::Banned
+        } else {
+            lobby.leave(target);
+            LeaveReason::Kicked
+        };
+
+        // the player that was kicked is told why before they are disconnected
+        let leave = Packet::Message(Message::Leave { player: target, reason });
+        send(&mut self.endpoint, &mut self.errors, addr, Channel::Reliable, &leave, now);
+
+        self.clients.remove(&addr);
+        self.closing.push(addr);
+
+        self.left(code, target, reason, now);
+    }
+
+    fn leave(&mut self, code: RoomCode, player: PlayerId, reason: LeaveReason, now: Instant) {
+        if let Some(lobby) = self.lobbies.get_mut(&code) {
+            lobby.leave(player);
+        }
+
+        self.left(code, player, reason, now);
+    }
+
+    /// Tell everybody that is left in a lobby that a player left, or close
+    /// the lobby if nobody is left.
+    fn left(&mut self, code: RoomCode, player: PlayerId, reason: LeaveReason, now: Instant) {
+        let lobby = match self.lobbies.get(&code) {
+            Some(lobby) => lobby,
+            None => return,
+        };
+
+        if lobby.state().is_empty() {
+            self.lobbies.remove(&code);
+            return;
+        }
+
+        let leave = Packet::Message(Message::Leave { player, reason });
+        broadcast(&mut self.endpoint, &mut self.errors, lobby, Channel::Reliable, &leave, now);
+
+        // the host might have changed
+        broadcast(&mut self.endpoint, &mut self.errors, lobby, Channel::Reliable, &Packet::LobbyState(lobby.info()), now);
+    }
+
+    fn message(&mut self, addr: SocketAddr, code: RoomCode, player: PlayerId, message: Message, now: Instant) {
+        let lobby = match self.lobbies.get_mut(&code) {
+            Some(lobby) => lobby,
+            None => return,
+        };
+
+        match message {
+            Message::Movement { sequence, velocity, .. } => {
+                if let Some(Client::Playing { sequence: last, .. }) = self.clients.get_mut(&addr) {
+                    // movement is unreliable, so it can arrive out of order
+                    let newer = last.is_none_or(|last| sequence != last && sequence.wrapping_sub(last) < 1 << 15);
+
+                    if newer {
+                        *last = Some(sequence);
+
+                        if let Some(state) = lobby.state_mut().player_mut(player) {
+                            state.set_velocity(velocity);
+                        }
+                    }
+                }
+            }
+            Message::Chat { text, .. } => {
+                // players can only talk as themselves
+                let chat = Packet::Message(Message::Chat { player, text });
+                broadcast(&mut self.endpoint, &mut self.errors, lobby, Channel::Reliable, &chat, now);
+            }
+            // the rest of the game isn't run by the server yet
+            _ => {}
+        }
+    }
+}
+
+fn send<S>(endpoint: &mut Endpoint<S>, errors: &mut Vec<ClientError>, addr: SocketAddr, channel: Channel, packet: &Packet, now: Instant)
+where S: Socket {
+    let bytes = packet.to_bytes().expect("packets the server makes always encode");
+
+    match endpoint.send(addr, channel, &bytes, now) {
+        Ok(()) => {}
+        // the client is already gone, and the server will hear about it with
+        // the rest of the events
+        Err(transport::Error::NotConnected(_)) => {}
+        // one client that can't be sent to shouldn't stop everybody else
+        Err(error) => errors.push(ClientError { addr, error }),
+    }
+}
+
+fn broadcast<S>(endpoint: &mut Endpoint<S>, errors: &mut Vec<ClientError>, lobby: &Lobby, channel: Channel, packet: &Packet, now: Instant)
+where S: Socket {
+    for addr in lobby.addrs() {
+        send(endpoint, errors, addr, channel, packet, now);
+    }
+}
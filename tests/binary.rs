@@ -0,0 +1,472 @@
+//! Round trip tests for `net::binary`.
+
+use among_us::math::*;
+use among_us::net::binary::decode::{BorrowDecode, Cursor, Decode, ErrorKind, Limits};
+use among_us::net::binary::encode::{self, CursorMut, Encode};
+use among_us::net::binary::{EncodePrefixed, Packed};
+
+use proptest::prelude::*;
+
+fn encode<T>(value: &T) -> Vec<u8>
+where T: Encode + ?Sized {
+    let mut cursor = CursorMut::new();
+    assert!(cursor.encode(value).is_ok(), "encoding failed");
+    cursor.into()
+}
+
+fn round_trip<T>(value: &T) -> Option<T>
+where T: Encode + Decode {
+    Cursor::new(encode(value)).decode().ok()
+}
+
+macro_rules! round_trip_tests {
+    ($($name:ident: $ty:ty,)*) => {
+        proptest! {
+            $(
+                #[test]
+                fn $name(value in any::<$ty>()) {
+                    prop_assert_eq!(round_trip(&value), Some(value));
+                }
+            )*
+        }
+    }
+}
+
+round_trip_tests! {
+    round_trip_u8: u8,
+    round_trip_u16: u16,
+    round_trip_u32: u32,
+    round_trip_u64: u64,
+    round_trip_u128: u128,
+    round_trip_i8: i8,
+    round_trip_i16: i16,
+    round_trip_i32: i32,
+    round_trip_i64: i64,
+    round_trip_i128: i128,
+    round_trip_bool: bool,
+    round_trip_string: String,
+    round_trip_vec: Vec<u32>,
+    round_trip_option: Option<i64>,
+    round_trip_nested_option: Option<Option<bool>>,
+    round_trip_tuple: (u8, String, Option<u16>),
+    round_trip_array: [i32; 8],
+    round_trip_array_of_strings: [String; 3],
+}
+
+proptest! {
+    #[test]
+    fn round_trip_nested_vec(value in prop::collection::vec(prop::collection::vec(any::<String>(), 0..8), 0..8)) {
+        prop_assert_eq!(round_trip(&value), Some(value));
+    }
+
+    #[test]
+    fn round_trip_packed_u16(value in any::<u16>()) {
+        prop_assert_eq!(round_trip(&Packed(value)), Some(Packed(value)));
+    }
+
+    #[test]
+    fn round_trip_packed_u32(value in any::<u32>()) {
+        prop_assert_eq!(round_trip(&Packed(value)), Some(Packed(value)));
+    }
+
+    #[test]
+    fn round_trip_packed_u64(value in any::<u64>()) {
+        prop_assert_eq!(round_trip(&Packed(value)), Some(Packed(value)));
+    }
+
+    #[test]
+    fn round_trip_packed_i32(value in any::<i32>()) {
+        prop_assert_eq!(round_trip(&Packed(value)), Some(Packed(value)));
+    }
+
+    #[test]
+    fn round_trip_packed_i64(value in any::<i64>()) {
+        prop_assert_eq!(round_trip(&Packed(value)), Some(Packed(value)));
+    }
+
+    #[test]
+    fn packed_is_never_longer_than_needed(value in any::<u64>()) {
+        let bits = 64 - value.leading_zeros() as usize;
+        prop_assert_eq!(encode(&Packed(value)).len(), bits.div_ceil(7).max(1));
+    }
+
+    #[test]
+    fn round_trip_f32(value in any::<f32>()) {
+        prop_assert_eq!(round_trip(&value).map(f32::to_bits), Some(value.to_bits()));
+    }
+
+    #[test]
+    fn round_trip_f64(value in any::<f64>()) {
+        prop_assert_eq!(round_trip(&value).map(f64::to_bits), Some(value.to_bits()));
+    }
+
+    #[test]
+    fn round_trip_vector2(x in any::<FLOAT>(), y in any::<FLOAT>()) {
+        let value = Vector2::new(x, y);
+        let decoded = round_trip(&value).map(|v| (v.x.to_bits(), v.y.to_bits()));
+
+        prop_assert_eq!(decoded, Some((x.to_bits(), y.to_bits())));
+    }
+
+    #[test]
+    fn round_trip_vector3(x in any::<FLOAT>(), y in any::<FLOAT>(), z in any::<FLOAT>()) {
+        let value = Vector3::new(x, y, z);
+        let decoded = round_trip(&value).map(|v| (v.x.to_bits(), v.y.to_bits(), v.z.to_bits()));
+
+        prop_assert_eq!(decoded, Some((x.to_bits(), y.to_bits(), z.to_bits())));
+    }
+
+    #[test]
+    fn truncated_input_fails(value in any::<(u32, String, Vec<u16>)>(), cut in any::<prop::sample::Index>()) {
+        let bytes = encode(&value);
+        let cut = cut.index(bytes.len());
+
+        prop_assert!(Cursor::new(&bytes[..cut]).decode::<(u32, String, Vec<u16>)>().is_err());
+    }
+}
+
+#[test]
+fn bool_rejects_other_values() {
+    assert!(Cursor::new([2u8]).decode::<bool>().is_err());
+}
+
+#[test]
+fn option_rejects_other_tags() {
+    assert!(Cursor::new([2u8, 0]).decode::<Option<u8>>().is_err());
+}
+
+#[test]
+fn floats_are_little_endian() {
+    assert_eq!(encode(&1.0f32), 1.0f32.to_le_bytes());
+    assert_eq!(encode(&Vector2::new(1.0, -2.0)), [1.0f64.to_le_bytes(), (-2.0f64).to_le_bytes()].concat());
+}
+
+#[test]
+fn packed_matches_among_us() {
+    assert_eq!(encode(&Packed(0u32)), [0x00]);
+    assert_eq!(encode(&Packed(127u32)), [0x7f]);
+    assert_eq!(encode(&Packed(300u32)), [0xac, 0x02]);
+    assert_eq!(encode(&Packed(-1i32)), [0xff, 0xff, 0xff, 0xff, 0x0f]);
+}
+
+#[test]
+fn packed_rejects_overflow() {
+    assert!(Cursor::new([0xff, 0xff, 0x03]).decode::<Packed<u16>>().is_ok());
+    assert!(Cursor::new([0xff, 0xff, 0x04]).decode::<Packed<u16>>().is_err());
+    assert!(Cursor::new([0xff, 0xff, 0xff, 0xff, 0x1f]).decode::<Packed<u32>>().is_err());
+    assert!(Cursor::new([0x80; 11]).decode::<Packed<u64>>().is_err());
+}
+
+#[test]
+fn lengths_are_packed() {
+    assert_eq!(encode(&String::from("hi")), [2, b'h', b'i']);
+    assert_eq!(encode(&vec![1u8; 200])[..2], [0xc8, 0x01]);
+}
+
+/// Something that can be decoded, but never encoded.
+#[derive(Debug, PartialEq, Decode)]
+struct Received(u8);
+
+#[derive(Debug, PartialEq, Decode)]
+struct Batch {
+    #[binary(len = u8)]
+    items: Vec<Received>,
+}
+
+#[test]
+fn sequences_of_decode_only_types() {
+    assert_eq!(Cursor::new([2u8, 4, 5]).decode::<Vec<Received>>().unwrap(), [Received(4), Received(5)]);
+    assert_eq!(Cursor::new([1u8, 6]).decode::<Batch>().unwrap(), Batch { items: vec![Received(6)] });
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct Unit;
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct Pair(u8, u16);
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct Cached<T> {
+    value: T,
+    #[binary(skip)]
+    hash: u32,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+enum Narrow {
+    First,
+    #[binary(tag = 5)]
+    Second,
+    Third,
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+#[binary(tag = u16)]
+enum Wide {
+    First,
+    #[binary(tag = 300)]
+    Second(u8),
+    Third { x: i32 },
+}
+
+#[test]
+fn derived_structs_round_trip() {
+    assert_eq!(encode(&Unit), []);
+    assert_eq!(round_trip(&Unit), Some(Unit));
+
+    assert_eq!(encode(&Pair(1, 2)), [1, 2, 0]);
+    assert_eq!(round_trip(&Pair(1, 2)), Some(Pair(1, 2)));
+
+    // skipped fields aren't sent, and come back as their default
+    let cached = Cached { value: String::from("hi"), hash: 9 };
+    assert_eq!(encode(&cached), [2, b'h', b'i']);
+    assert_eq!(round_trip(&cached), Some(Cached { value: String::from("hi"), hash: 0 }));
+
+    let cached = Cached { value: Pair(3, 4), hash: 0 };
+    assert_eq!(round_trip(&cached), Some(cached));
+}
+
+#[test]
+fn derived_enums_round_trip() {
+    // variants without a tag take the one after the previous variant
+    assert_eq!(encode(&Narrow::First), [0]);
+    assert_eq!(encode(&Narrow::Second), [5]);
+    assert_eq!(encode(&Narrow::Third), [6]);
+
+    for value in [Narrow::First, Narrow::Second, Narrow::Third] {
+        assert_eq!(round_trip(&value), Some(value));
+    }
+
+    let error = Cursor::new([1u8]).decode::<Narrow>().unwrap_err();
+    assert_eq!(*error.kind(), ErrorKind::InvalidTag);
+
+    assert_eq!(encode(&Wide::First), [0, 0]);
+    assert_eq!(encode(&Wide::Second(3)), [44, 1, 3]);
+    assert_eq!(encode(&Wide::Third { x: -1 }), [45, 1, 0xff, 0xff, 0xff, 0xff]);
+
+    for value in [Wide::First, Wide::Second(3), Wide::Third { x: -1 }] {
+        assert_eq!(round_trip(&value), Some(value));
+    }
+
+    let error = Cursor::new([44u8]).decode::<Wide>().unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::UnexpectedEnd { .. }));
+}
+
+#[test]
+fn huge_lengths_fail_without_allocating() {
+    let string = Cursor::new([0xff, 0xff, 0xff, 0xff, 0x0f]).decode::<String>();
+    assert!(matches!(string.map_err(|e| *e.kind()), Err(ErrorKind::StringLimit { .. })));
+
+    let vec = Cursor::new([0xff, 0xff, 0xff, 0xff, 0x0f]).decode::<Vec<u64>>();
+    assert!(matches!(vec.map_err(|e| *e.kind()), Err(ErrorKind::LenLimit { .. })));
+
+    let mut limits = Limits::unlimited();
+    let string = Cursor::with_limits([0xff, 0xff, 0xff, 0xff, 0x0f], limits).decode::<String>();
+    assert!(matches!(string.map_err(|e| *e.kind()), Err(ErrorKind::UnexpectedEnd { .. })));
+
+    limits.set_max_alloc(1024);
+    let vec = Cursor::with_limits([0xff, 0xff, 0xff, 0xff, 0x0f], limits).decode::<Vec<u64>>();
+    assert!(matches!(vec.map_err(|e| *e.kind()), Err(ErrorKind::AllocLimit { .. })));
+}
+
+#[test]
+fn limits_are_shared_by_everything_decoded() {
+    let bytes = encode(&vec![String::from("hello"); 4]);
+
+    let mut limits = Limits::new();
+    limits.set_max_alloc(4 * std::mem::size_of::<String>() + 4 * 5);
+    let mut cursor = Cursor::with_limits(&bytes, limits);
+    assert!(cursor.decode::<Vec<String>>().is_ok());
+    assert_eq!(cursor.allocated(), limits.max_alloc());
+
+    limits.set_max_alloc(limits.max_alloc() - 1);
+    let decoded = Cursor::with_limits(&bytes, limits).decode::<Vec<String>>();
+    assert!(matches!(decoded.map_err(|e| *e.kind()), Err(ErrorKind::AllocLimit { .. })));
+
+    limits = Limits::new();
+    limits.set_max_string(4);
+    let decoded = Cursor::with_limits(&bytes, limits).decode::<Vec<String>>();
+    assert!(matches!(decoded.map_err(|e| *e.kind()), Err(ErrorKind::StringLimit { .. })));
+
+    limits = Limits::new();
+    limits.set_max_len(3);
+    let decoded = Cursor::with_limits(&bytes, limits).decode::<Vec<String>>();
+    assert!(matches!(decoded.map_err(|e| *e.kind()), Err(ErrorKind::LenLimit { .. })));
+}
+
+#[derive(Debug, PartialEq, Encode, BorrowDecode)]
+struct Chat<'a> {
+    player: u8,
+    message: &'a str,
+    position: Vector2,
+    #[binary(len = u8)]
+    tags: Vec<u16>,
+    extra: Option<Packed<u32>>,
+}
+
+proptest! {
+    #[test]
+    fn borrowed_str_round_trips(value in any::<String>()) {
+        let bytes = encode(&value);
+        let mut cursor = Cursor::new(&bytes[..]);
+        prop_assert_eq!(cursor.borrow_decode::<&str>().ok(), Some(&value[..]));
+    }
+
+    #[test]
+    fn borrowed_bytes_match_vec(value in any::<Vec<u8>>()) {
+        let bytes = encode(&value);
+        let mut cursor = Cursor::new(&bytes[..]);
+        prop_assert_eq!(cursor.borrow_decode::<&[u8]>().ok(), Some(&value[..]));
+    }
+
+    #[test]
+    fn borrowed_struct_round_trips(message in any::<String>(), tags in prop::collection::vec(any::<u16>(), 0..8)) {
+        let chat = Chat { player: 3, message: &message, position: Vector2::new(1.0, 2.0), tags, extra: Some(Packed(7)) };
+        let bytes = encode(&chat);
+        let decoded = Cursor::new(&bytes[..]).borrow_decode::<Chat>().ok();
+        prop_assert_eq!(decoded, Some(chat));
+    }
+}
+
+#[test]
+fn borrowed_str_points_into_input() {
+    let bytes = encode("hello");
+    let decoded = Cursor::new(&bytes[..]).borrow_decode::<&str>().ok();
+    assert_eq!(decoded.map(str::as_ptr), Some(bytes[1..].as_ptr()));
+
+    assert!(Cursor::new(&[5, b'h', b'i'][..]).borrow_decode::<&str>().is_err());
+    assert!(Cursor::new(&[1, 0xff][..]).borrow_decode::<&str>().is_err());
+}
+
+#[test]
+fn cursor_position_can_be_moved() {
+    let mut cursor = Cursor::new([1u8, 2, 3, 4]);
+
+    let mut buf = [0; 2];
+    assert_eq!(cursor.peek(&mut buf), 2);
+    assert_eq!(buf, [1, 2]);
+    assert_eq!(cursor.peek_decode::<u16>().ok(), Some(0x0201));
+    assert_eq!(cursor.remaining(), 4);
+
+    assert!(cursor.skip(3).is_ok());
+    assert_eq!(cursor.position(), 3);
+    assert!(cursor.skip(2).is_err());
+    assert!(cursor.decode::<u16>().is_err());
+
+    assert!(cursor.seek(1).is_ok());
+    assert_eq!(cursor.decode::<u8>().ok(), Some(2));
+    assert!(cursor.seek(4).is_ok());
+    assert!(cursor.seek(5).is_err());
+}
+
+#[test]
+fn nested_cursors_stay_in_bounds() {
+    let mut cursor = Cursor::new([3u8, b'a', b'b', b'c', 9]);
+
+    let nested = cursor.nested(2, |sub| sub.decode::<String>());
+    assert!(matches!(nested.map_err(|e| *e.kind()), Err(ErrorKind::UnexpectedEnd { .. })));
+    assert_eq!(cursor.position(), 2);
+    assert!(cursor.nested(4, |_| Ok(())).is_err());
+
+    assert!(cursor.seek(0).is_ok());
+    assert_eq!(cursor.nested(4, |sub| sub.decode::<String>()).ok(), Some(String::from("abc")));
+    assert_eq!(cursor.decode::<u8>().ok(), Some(9));
+}
+
+#[test]
+fn hazel_messages_round_trip_and_skip() {
+    let mut writer = CursorMut::new();
+    assert!(writer.message(1, |c| c.encode(&String::from("hello"))).is_ok());
+    assert!(writer.message(200, |c| c.encode(&(1u32, 2u64, [7u8; 10]))).is_ok());
+    assert!(writer.message(2, |c| {
+        c.encode(&5u8)?;
+        c.message(3, |c| c.encode(&-1i16))
+    }).is_ok());
+    let bytes: Vec<u8> = writer.into();
+
+    assert_eq!(bytes[..3], [6, 0, 1]);
+
+    let mut cursor = Cursor::new(&bytes);
+    let mut seen = Vec::new();
+
+    while cursor.remaining() > 0 {
+        let message = cursor.message(|tag, sub| {
+            Ok(match tag {
+                1 => Some(sub.decode::<String>()?),
+                2 => {
+                    let a = sub.decode::<u8>()?;
+                    let b = sub.message(|_, sub| sub.decode::<i16>())?;
+                    Some(format!("{} {}", a, b))
+                }
+                // unknown messages are skipped
+                _ => None,
+            })
+        });
+
+        assert!(message.is_ok());
+        seen.extend(message.ok().flatten());
+    }
+
+    assert_eq!(seen, ["hello", "5 -1"]);
+
+    assert!(cursor.seek(0).is_ok());
+    assert_eq!(cursor.skip_message().ok(), Some(1));
+    assert_eq!(cursor.skip_message().ok(), Some(200));
+    assert_eq!(cursor.skip_message().ok(), Some(2));
+    assert_eq!(cursor.remaining(), 0);
+}
+
+#[test]
+fn hazel_messages_must_fit_in_a_u16() {
+    let mut writer = CursorMut::new();
+    assert!(writer.message(0, |c| c.encode(&vec![0u8; 0x10000])).is_err());
+}
+
+#[test]
+fn decode_errors_have_context() {
+    let error = Cursor::new([0u8, 5, 1, 2]).decode::<(u8, Vec<u16>)>().unwrap_err();
+
+    assert_eq!(*error.kind(), ErrorKind::UnexpectedEnd { expected: 2, available: 0 });
+    assert_eq!(error.offset(), Some(4));
+    assert_eq!(error.type_name(), Some("u16"));
+    assert_eq!(error.to_string(), "failed to decode `u16` at byte 4: expected 2 bytes, but only 0 were left");
+
+    let error = Cursor::new([0u8, 0, 1, 0xff]).decode::<(u16, String)>().unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::Utf8(_)));
+    assert_eq!(error.offset(), Some(2));
+    assert!(std::error::Error::source(&error).is_some());
+
+    let mut cursor = Cursor::new([9u8, 1, 0, 7, 1]);
+    assert!(cursor.skip(1).is_ok());
+    let error = cursor.message(|_, sub| sub.decode::<(u8, bool)>()).unwrap_err();
+    assert_eq!(*error.kind(), ErrorKind::UnexpectedEnd { expected: 1, available: 0 });
+    assert_eq!(error.offset(), Some(5));
+}
+
+#[test]
+fn encode_errors_have_context() {
+    let mut cursor = CursorMut::new();
+    assert!(cursor.encode(&7u32).is_ok());
+
+    let error = "a".repeat(300).encode_prefixed::<u8, _>(&mut cursor).unwrap_err();
+    assert_eq!(*error.kind(), encode::ErrorKind::LengthOverflow { len: 300, prefix: "u8" });
+
+    let error = cursor.encode(&(1u8, [vec![0u8; 0x10000]])).and_then(|_| {
+        cursor.message(0, |c| c.encode(&vec![0u8; 0x10000]))
+    }).unwrap_err();
+    assert_eq!(error.offset(), Some(4 + 1 + 0x10000 + 3));
+    assert_eq!(error.to_string(), "failed to encode at byte 65544: length 65539 doesn't fit in a `u16` prefix");
+}
+
+#[test]
+fn errors_work_with_question_mark() {
+    fn decode_and_encode() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let value = Cursor::new([1u8, 2]).decode::<u16>()?;
+        let mut cursor = CursorMut::new();
+        cursor.encode(&value)?;
+        Cursor::new([1u8]).decode::<u16>()?;
+        Ok(())
+    }
+
+    assert!(decode_and_encode().unwrap_err().to_string().contains("expected 2 bytes"));
+}
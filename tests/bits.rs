@@ -0,0 +1,161 @@
+//! Tests for `net::binary::bits`.
+
+use among_us::math::*;
+use among_us::net::binary::bits::{BitReader, BitWriter, Quantize};
+use among_us::net::binary::decode::Cursor;
+use among_us::net::binary::encode::CursorMut;
+
+use proptest::prelude::*;
+
+/// A movement snapshot of one player.
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    id: u8,
+    position: Vector2,
+    velocity: Vector2,
+    moving: bool,
+    in_vent: bool,
+}
+
+fn positions() -> Quantize {
+    Quantize::new(-50.0, 50.0, 16)
+}
+
+fn velocities() -> Quantize {
+    Quantize::with_precision(-4.0, 4.0, 0.1)
+}
+
+fn write_snapshot(writer: &mut BitWriter, snapshot: &Snapshot) {
+    writer.write_bits(snapshot.id as u64, 4).unwrap();
+    writer.write_vector2(snapshot.position, positions());
+    writer.write_vector2(snapshot.velocity, velocities());
+    writer.write_bool(snapshot.moving);
+    writer.write_bool(snapshot.in_vent);
+}
+
+fn read_snapshot(reader: &mut BitReader) -> Snapshot {
+    Snapshot {
+        id: reader.read_bits(4).unwrap() as u8,
+        position: reader.read_vector2(positions()).unwrap(),
+        velocity: reader.read_vector2(velocities()).unwrap(),
+        moving: reader.read_bool().unwrap(),
+        in_vent: reader.read_bool().unwrap(),
+    }
+}
+
+proptest! {
+    #[test]
+    fn bits_round_trip(values in prop::collection::vec((any::<u64>(), 0..=64u32), 0..32)) {
+        let values = values.into_iter()
+            .map(|(value, bits)| (if bits == 64 { value } else { value & ((1 << bits) - 1) }, bits))
+            .collect::<Vec<_>>();
+
+        let mut writer = BitWriter::new();
+        for &(value, bits) in values.iter() {
+            prop_assert!(writer.write_bits(value, bits).is_ok());
+        }
+
+        let len = writer.len();
+        let bytes = writer.into_bytes();
+        prop_assert_eq!(bytes.len(), len.div_ceil(8));
+
+        let mut reader = BitReader::new(&bytes);
+        for &(value, bits) in values.iter() {
+            prop_assert_eq!(reader.read_bits(bits).ok(), Some(value));
+        }
+    }
+
+    #[test]
+    fn signed_round_trip(value in any::<i64>(), bits in 1..=64u32) {
+        let value = value >> (64 - bits);
+
+        let mut writer = BitWriter::new();
+        prop_assert!(writer.write_signed(value, bits).is_ok());
+        prop_assert_eq!(writer.len(), bits as usize);
+
+        let bytes = writer.into_bytes();
+        prop_assert_eq!(BitReader::new(&bytes).read_signed(bits).ok(), Some(value));
+    }
+
+    #[test]
+    fn quantized_floats_are_close(value in -60.0..60.0 as FLOAT) {
+        let quantize = positions();
+
+        let mut writer = BitWriter::new();
+        writer.write_float(value, quantize);
+        let bytes = writer.into_bytes();
+
+        let read = BitReader::new(&bytes).read_float(quantize).unwrap();
+        let clamped = value.clamp(quantize.min(), quantize.max());
+        prop_assert!((read - clamped).abs() <= quantize.precision() / 2.0 + 1e-9);
+    }
+}
+
+#[test]
+fn values_must_fit() {
+    let mut writer = BitWriter::new();
+
+    assert!(writer.write_bits(16, 4).is_err());
+    assert!(writer.write_bits(15, 4).is_ok());
+    assert!(writer.write_signed(-9, 4).is_err());
+    assert!(writer.write_signed(8, 4).is_err());
+    assert!(writer.write_signed(-8, 4).is_ok());
+    assert_eq!(writer.len(), 8);
+
+    let bytes = writer.into_bytes();
+    let mut reader = BitReader::new(&bytes);
+    assert_eq!(reader.read_bits(4).ok(), Some(15));
+    assert_eq!(reader.read_signed(4).ok(), Some(-8));
+    assert!(reader.read_bool().is_err());
+}
+
+#[test]
+fn quantize_ends_are_exact() {
+    let quantize = velocities();
+
+    assert_eq!(quantize.bits(), 7);
+    assert!(quantize.precision() <= 0.1);
+    assert_eq!(quantize.dequantize(quantize.quantize(-4.0)), -4.0);
+    assert_eq!(quantize.dequantize(quantize.quantize(4.0)), 4.0);
+    assert_eq!(quantize.quantize(100.0), quantize.quantize(4.0));
+}
+
+#[test]
+fn snapshots_fit_in_a_few_bytes() {
+    let snapshots = [
+        Snapshot { id: 3, position: Vector2::new(12.5, -3.25), velocity: Vector2::new(0.0, 2.5), moving: true, in_vent: false },
+        Snapshot { id: 9, position: Vector2::new(-50.0, 50.0), velocity: Vector2::new(-4.0, 4.0), moving: false, in_vent: true },
+    ];
+
+    let mut writer = BitWriter::new();
+    for snapshot in snapshots.iter() {
+        write_snapshot(&mut writer, snapshot);
+    }
+
+    // 4 + 2 * 16 + 2 * 7 + 2 = 52 bits a player
+    assert_eq!(writer.len(), 2 * 52);
+
+    // mix bit-packed snapshots with byte-aligned data
+    let bits = writer.into_bytes();
+    let mut cursor = CursorMut::new();
+    cursor.encode(&(bits.len() as u8)).unwrap();
+    cursor.write(&bits).unwrap();
+    cursor.encode(&0xffffu16).unwrap();
+    let bytes: Vec<u8> = cursor.into();
+    assert_eq!(bytes.len(), 1 + 13 + 2);
+
+    let mut cursor = Cursor::new(&bytes[..]);
+    let len = cursor.decode::<u8>().unwrap();
+    let mut reader = BitReader::new(cursor.take(len as usize).unwrap());
+
+    for snapshot in snapshots.iter() {
+        let read = read_snapshot(&mut reader);
+
+        assert_eq!(read.id, snapshot.id);
+        assert!((read.position - snapshot.position).magnitude() < positions().precision());
+        assert!((read.velocity - snapshot.velocity).magnitude() < velocities().precision());
+        assert_eq!((read.moving, read.in_vent), (snapshot.moving, snapshot.in_vent));
+    }
+
+    assert_eq!(cursor.decode::<u16>().unwrap(), 0xffff);
+}
@@ -0,0 +1,490 @@
+//! Tests for `collide`.
+
+use among_us::collide::broad::{Aabb, Grid};
+use among_us::collide::ray::{self, Ray, Raycast, Segment};
+use among_us::collide::transform::{Transform, Transformable};
+use among_us::collide::*;
+use among_us::math::*;
+
+const EPSILON: FLOAT = 1e-9;
+
+fn square(min: Vector2, size: FLOAT) -> Polygon {
+    vec![
+        min,
+        Vector2::new(min.x + size, min.y),
+        Vector2::new(min.x + size, min.y + size),
+        Vector2::new(min.x, min.y + size),
+    ]
+    .into()
+}
+
+fn assert_close(a: Vector2, b: Vector2) {
+    assert!((a - b).magnitude() < 1e-6, "{:?} != {:?}", a, b);
+}
+
+#[test]
+fn box_penetration() {
+    let a = square(Vector2::new(0.0, 0.0), 2.0);
+
+    // overlapping by 0.5 on the right, and 1.5 from the top
+    let b = square(Vector2::new(1.5, 0.5), 2.0);
+    let pen = a.penetration(&b).unwrap();
+
+    assert!((pen.depth() - 0.5).abs() < EPSILON);
+    assert_close(pen.axis(), Vector2::new(-1.0, 0.0));
+    assert_close(pen.mtv(), Vector2::new(-0.5, 0.0));
+
+    // moving by the mtv separates the boxes
+    let moved = square(Vector2::new(-0.5 - EPSILON, 0.0), 2.0);
+    assert!(moved.penetration(&b).is_none());
+
+    // and the other way round points the other way
+    assert_close(b.penetration(&a).unwrap().axis(), Vector2::new(1.0, 0.0));
+
+    // boxes that only touch don't collide
+    assert!(a.penetration(&square(Vector2::new(2.0, 0.0), 2.0)).is_none());
+    assert!(a.penetration(&square(Vector2::new(5.0, 5.0), 2.0)).is_none());
+}
+
+#[test]
+fn circle_penetration() {
+    let a = Circle::new(Vector2::new(0.0, 0.0), 1.0);
+    let b = Circle::new(Vector2::new(1.5, 0.0), 1.0);
+    let pen = a.penetration(&b).unwrap();
+
+    assert!((pen.depth() - 0.5).abs() < EPSILON);
+    assert_close(pen.axis(), Vector2::new(-1.0, 0.0));
+
+    assert!(a.penetration(&Circle::new(Vector2::new(2.5, 0.0), 1.0)).is_none());
+
+    // concentric circles still get pushed apart
+    let pen = a.penetration(&a).unwrap();
+    assert!((pen.depth() - 2.0).abs() < EPSILON);
+
+    // but two points in the same place only touch
+    let point = Circle::new(Vector2::new(0.0, 0.0), 0.0);
+    assert!(point.penetration(&point).is_none());
+}
+
+#[test]
+fn circle_box_penetration() {
+    let wall = square(Vector2::new(0.0, 0.0), 2.0);
+
+    // just above the top edge, sinking in by 0.25
+    let circle = Circle::new(Vector2::new(1.0, 2.75), 1.0);
+    let pen = circle.penetration(&wall).unwrap();
+
+    assert!((pen.depth() - 0.25).abs() < EPSILON);
+    assert_close(pen.axis(), Vector2::new(0.0, 1.0));
+
+    // near a corner, the circle is pushed away from the corner
+    let circle = Circle::new(Vector2::new(2.5, 2.5), 1.0);
+    let pen = circle.penetration(&wall).unwrap();
+    let diagonal = Vector2::new(1.0, 1.0).normalize();
+
+    assert!((pen.depth() - (1.0 - 0.5 * 2.0f64.sqrt())).abs() < EPSILON);
+    assert_close(pen.axis(), diagonal);
+
+    // past the corner, out of reach
+    assert!(Circle::new(Vector2::new(2.75, 2.75), 1.0).penetration(&wall).is_none());
+}
+
+#[test]
+fn closing_edge() {
+    // a triangle whose only separating axis is the edge from the last vertex
+    // back to the first
+    let triangle: Polygon = vec![Vector2::new(0.0, 2.0), Vector2::new(0.0, 0.0), Vector2::new(2.0, 0.0)].into();
+    let point = square(Vector2::new(1.2, 1.2), 0.1);
+
+    assert!(!triangle.collide(&point));
+    assert!(triangle.penetration(&point).is_none());
+}
+
+#[test]
+fn projections() {
+    let a = Projection::new(3.0, 1.0);
+    assert_eq!((a.start(), a.end()), (1.0, 3.0));
+
+    let b = Projection::new(2.5, 5.0);
+    assert!(a.overlap(&b));
+    assert!((a.overlap_amount(&b) - 0.5).abs() < EPSILON);
+
+    let c = Projection::new(4.0, 5.0);
+    assert!(!a.overlap(&c));
+    assert!(a.overlap_amount(&c) < 0.0);
+
+    assert!(Projection::new(0.0, 10.0).contains(&a));
+    assert!(!a.contains(&b));
+}
+
+#[test]
+fn time_of_impact_edge() {
+    let wall = square(Vector2::new(0.0, 0.0), 2.0);
+    let circle = Circle::new(Vector2::new(-2.0, 1.0), 0.5);
+
+    // moving right, the circle touches the left side after 1.5 of 3 units
+    let impact = sweep::time_of_impact(&circle, Vector2::new(3.0, 0.0), &wall).unwrap();
+    assert!((impact.time() - 0.5).abs() < EPSILON);
+    assert_close(impact.normal(), Vector2::new(-1.0, 0.0));
+
+    // stopping short, or moving away, never hits
+    assert!(sweep::time_of_impact(&circle, Vector2::new(1.0, 0.0), &wall).is_none());
+    assert!(sweep::time_of_impact(&circle, Vector2::new(-3.0, 0.0), &wall).is_none());
+
+    // a fast circle can't skip over a thin wall
+    let thin: Polygon = vec![Vector2::new(0.0, -5.0), Vector2::new(0.01, -5.0), Vector2::new(0.01, 5.0), Vector2::new(0.0, 5.0)].into();
+    let impact = sweep::time_of_impact(&circle, Vector2::new(100.0, 0.0), &thin).unwrap();
+    assert!((impact.time() - 0.015).abs() < EPSILON);
+}
+
+#[test]
+fn time_of_impact_corner() {
+    let wall = square(Vector2::new(0.0, 0.0), 2.0);
+    let circle = Circle::new(Vector2::new(-1.0, 3.0), 0.5);
+
+    // heading straight at the top left corner from up and to the left
+    let velocity = Vector2::new(2.0, -2.0);
+    let impact = sweep::time_of_impact(&circle, velocity, &wall).unwrap();
+    let touch = circle.center() + velocity * impact.time();
+
+    assert!(((touch - Vector2::new(0.0, 2.0)).magnitude() - 0.5).abs() < 1e-6);
+    assert_close(impact.normal(), Vector2::new(-1.0, 1.0).normalize());
+
+    // passing the corner with room to spare misses it
+    let circle = Circle::new(Vector2::new(-1.0, 3.0), 0.5);
+    assert!(sweep::time_of_impact(&circle, Vector2::new(4.0, 0.0), &wall).is_none());
+}
+
+#[test]
+fn time_of_impact_overlapping() {
+    let wall = square(Vector2::new(0.0, 0.0), 2.0);
+    let circle = Circle::new(Vector2::new(2.25, 1.0), 0.5);
+
+    // already touching, so the impact is immediate, even without moving
+    let impact = sweep::time_of_impact(&circle, Vector2::zero(), &wall).unwrap();
+    assert_eq!(impact.time(), 0.0);
+    assert_close(impact.normal(), Vector2::new(1.0, 0.0));
+
+    // and circles hit circles
+    let other = Circle::new(Vector2::new(4.0, 1.0), 0.5);
+    let impact = sweep::time_of_impact(&circle, Vector2::new(2.0, 0.0), &other).unwrap();
+    assert!((impact.time() - 0.375).abs() < EPSILON);
+    assert_close(impact.normal(), Vector2::new(-1.0, 0.0));
+}
+
+#[test]
+fn grid_query() {
+    let mut grid = Grid::new(1.0);
+    let a = grid.insert(&square(Vector2::new(0.0, 0.0), 0.5), 'a');
+    let b = grid.insert(&square(Vector2::new(3.0, 3.0), 0.5), 'b');
+    let big = grid.insert(&square(Vector2::new(-2.0, -2.0), 10.0), 'c');
+
+    let origin = Aabb::new(Vector2::new(-0.1, -0.1), Vector2::new(0.1, 0.1));
+    let corner = Circle::new(Vector2::new(3.25, 3.25), 0.1);
+
+    assert_eq!(grid.len(), 3);
+    assert_eq!(grid.query(&origin), vec![a, big]);
+    assert_eq!(grid.query_circle(&corner), vec![b, big]);
+    assert!(grid.query(&Aabb::new(Vector2::new(20.0, 20.0), Vector2::new(21.0, 21.0))).is_empty());
+
+    // shapes are found where they moved to, and not where they were
+    assert!(grid.update(a, &square(Vector2::new(3.2, 3.2), 0.5)));
+    assert_eq!(grid.query(&origin), vec![big]);
+    assert_eq!(grid.query_circle(&corner), vec![a, b, big]);
+
+    // removed shapes are gone, and their handles are reused
+    assert_eq!(grid.remove(b), Some('b'));
+    assert_eq!(grid.remove(b), None);
+    assert!(!grid.update(b, &square(Vector2::new(0.0, 0.0), 0.5)));
+    assert_eq!(grid.query_circle(&corner), vec![a, big]);
+    assert_eq!(grid.len(), 2);
+
+    let d = grid.insert(&square(Vector2::new(0.0, 0.0), 0.5), 'd');
+    assert_eq!(d, b);
+    assert_eq!(grid.get(d), Some(&'d'));
+    assert_eq!(grid.query(&origin), vec![d, big]);
+}
+
+#[test]
+fn grid_limits() {
+    let mut grid = Grid::new(0.001);
+    let origin = Aabb::new(Vector2::new(-0.1, -0.1), Vector2::new(0.1, 0.1));
+
+    // shapes that aren't finite are kept, but never found
+    let nan = grid.insert(&Circle::new(Vector2::new(FLOAT::NAN, 0.0), 1.0), 'n');
+    let infinite = grid.insert_aabb(Aabb::new(Vector2::new(FLOAT::NEG_INFINITY, 0.0), Vector2::new(1.0, 1.0)), 'i');
+    assert_eq!(grid.len(), 2);
+    assert!(grid.query(&origin).is_empty());
+
+    // shapes that cover far too many cells are still found, without filling
+    // the grid
+    let huge = grid.insert_aabb(Aabb::new(Vector2::new(-1e12, -1e12), Vector2::new(1e12, 1e12)), 'h');
+    assert_eq!(grid.query(&origin), vec![huge]);
+    assert_eq!(grid.query(&Aabb::new(Vector2::new(-1e15, 0.0), Vector2::new(1e15, 1.0))), vec![huge]);
+
+    // and so are shapes that move in and out of being finite
+    assert!(grid.update_aabb(nan, origin));
+    assert!(grid.update_aabb(huge, Aabb::new(Vector2::new(0.0, FLOAT::INFINITY), Vector2::new(1.0, 1.0))));
+    assert_eq!(grid.query(&origin), vec![nan]);
+    assert_eq!(grid.remove(infinite), Some('i'));
+
+    // a query that isn't finite finds nothing
+    assert!(grid.query_circle(&Circle::new(Vector2::new(FLOAT::NAN, 0.0), 1.0)).is_empty());
+    assert!(grid.query(&Aabb::new(Vector2::new(0.0, 0.0), Vector2::new(FLOAT::INFINITY, 1.0))).is_empty());
+}
+
+#[test]
+fn aabbs() {
+    let aabb = Aabb::from_geometry(&Circle::new(Vector2::new(1.0, 1.0), 0.5));
+    assert_eq!(aabb, Aabb::new(Vector2::new(1.5, 1.5), Vector2::new(0.5, 0.5)));
+    assert_eq!(aabb.min(), Vector2::new(0.5, 0.5));
+
+    // touching boxes overlap
+    assert!(aabb.overlap(&Aabb::new(Vector2::new(1.5, 0.0), Vector2::new(2.0, 1.0))));
+    assert!(!aabb.overlap(&Aabb::new(Vector2::new(1.6, 0.0), Vector2::new(2.0, 1.0))));
+    assert!(aabb.grow(0.1).overlap(&Aabb::new(Vector2::new(1.6, 0.0), Vector2::new(2.0, 1.0))));
+}
+
+#[test]
+fn zero_length_segments() {
+    let wall = square(Vector2::new(0.0, 0.0), 2.0);
+    let circle = Circle::new(Vector2::new(5.0, 0.0), 1.0);
+
+    // two players standing on the same spot can see each other
+    let point = Segment::new(Vector2::new(3.0, 1.0), Vector2::new(3.0, 1.0));
+    assert!(point.cast(&wall).is_none());
+    assert!(point.cast(&circle).is_none());
+    assert!(point.clear([&wall]));
+    assert!(point.clear([&circle]));
+
+    // unless that spot is inside of something
+    let inside = Segment::new(Vector2::new(1.0, 1.0), Vector2::new(1.0, 1.0));
+    let hit = inside.cast(&wall).unwrap();
+    assert_eq!(hit.distance(), 0.0);
+    assert!(hit.normal().x.is_finite() && hit.normal().y.is_finite());
+    assert!(!inside.clear([&wall]));
+
+    let inside = Segment::new(Vector2::new(5.5, 0.0), Vector2::new(5.5, 0.0));
+    assert!(!inside.clear([&circle]));
+
+    let ray = Ray::new(Vector2::new(1.0, 1.0), Vector2::zero());
+    assert_eq!(ray.direction(), Vector2::unit_x());
+}
+
+#[test]
+fn raycast() {
+    let wall = square(Vector2::new(2.0, -1.0), 2.0);
+    let ray = Ray::new(Vector2::new(0.0, 0.0), Vector2::new(3.0, 0.0));
+
+    let hit = wall.raycast(&ray, 10.0).unwrap();
+    assert!((hit.distance() - 2.0).abs() < EPSILON);
+    assert_close(hit.normal(), Vector2::new(-1.0, 0.0));
+    assert_close(ray.at(hit.distance()), Vector2::new(2.0, 0.0));
+
+    // too short, or facing away
+    assert!(wall.raycast(&ray, 1.5).is_none());
+    assert!(wall.raycast(&Ray::new(Vector2::new(0.0, 0.0), Vector2::new(-1.0, 0.0)), 10.0).is_none());
+
+    // hitting the top at an angle
+    let ray = Ray::new(Vector2::new(0.0, 3.0), Vector2::new(1.0, -1.0));
+    let hit = wall.raycast(&ray, 10.0).unwrap();
+    assert!((hit.distance() - 8.0f64.sqrt()).abs() < EPSILON);
+    assert_close(hit.normal(), Vector2::new(0.0, 1.0));
+
+    // circles
+    let circle = Circle::new(Vector2::new(5.0, 0.0), 1.0);
+    let hit = circle.raycast(&Ray::new(Vector2::new(0.0, 0.0), Vector2::unit_x()), 10.0).unwrap();
+    assert!((hit.distance() - 4.0).abs() < EPSILON);
+    assert_close(hit.normal(), Vector2::new(-1.0, 0.0));
+}
+
+#[test]
+fn segments() {
+    let near = square(Vector2::new(2.0, -1.0), 1.0);
+    let far = square(Vector2::new(5.0, -1.0), 1.0);
+    let shapes = [far.clone(), near.clone()];
+
+    let blocked = Segment::new(Vector2::new(0.0, -0.5), Vector2::new(10.0, -0.5));
+    assert!((blocked.length() - 10.0).abs() < EPSILON);
+    assert!((blocked.cast(&far).unwrap().distance() - 5.0).abs() < EPSILON);
+    assert!(!blocked.clear(&shapes));
+
+    // the segment stops before the far wall
+    let short = Segment::new(Vector2::new(0.0, -0.5), Vector2::new(4.0, -0.5));
+    assert!(short.cast(&far).is_none());
+    assert!(short.clear([&far]));
+
+    // the closest shape wins, whatever order they're in
+    let (i, hit) = ray::first_hit(&blocked.ray(), 10.0, &shapes).unwrap();
+    assert_eq!(i, 1);
+    assert!((hit.distance() - 2.0).abs() < EPSILON);
+
+    let above = Segment::new(Vector2::new(0.0, 1.0), Vector2::new(10.0, 1.0));
+    assert!(above.clear(&shapes));
+}
+
+#[test]
+fn point_in_concave_polygon() {
+    // an L shape, with the top right corner missing
+    let ring = [
+        Vector2::new(0.0, 0.0),
+        Vector2::new(2.0, 0.0),
+        Vector2::new(2.0, 1.0),
+        Vector2::new(1.0, 1.0),
+        Vector2::new(1.0, 2.0),
+        Vector2::new(0.0, 2.0),
+    ];
+
+    assert!(contains_point(&ring, Vector2::new(0.5, 0.5)));
+    assert!(contains_point(&ring, Vector2::new(1.5, 0.5)));
+    assert!(contains_point(&ring, Vector2::new(0.5, 1.5)));
+    assert!(!contains_point(&ring, Vector2::new(1.5, 1.5)));
+    assert!(!contains_point(&ring, Vector2::new(-0.5, 0.5)));
+    assert!(!contains_point(&[], Vector2::new(0.0, 0.0)));
+}
+
+#[test]
+fn transforms() {
+    let quarter = consts::FRAC_PI_2;
+    let transform = Transform::new(Vector2::new(1.0, 2.0), quarter, 2.0);
+
+    // scaled, then rotated, then moved
+    assert_close(transform.apply(Vector2::new(1.0, 0.0)), Vector2::new(1.0, 4.0));
+    assert_close(transform.rotate(Vector2::new(1.0, 0.0)), Vector2::new(0.0, 1.0));
+
+    // a full turn in quarters gets back to where it started
+    let point = Vector2::new(0.3, -1.7);
+    let mut rotated = point;
+    for _ in 0..4 {
+        rotated = transform.rotate(rotated);
+    }
+    assert_close(rotated, point);
+
+    // and rotating back undoes it
+    let back = Transform::new(Vector2::zero(), -quarter, 1.0);
+    assert_close(back.rotate(transform.rotate(point)), point);
+
+    assert_eq!(Transform::default().apply(point), point);
+}
+
+#[test]
+fn transformed_shapes() {
+    let mut door = square(Vector2::new(0.0, 0.0), 1.0).with_transform(Transform::translate(Vector2::new(5.0, 0.0)));
+
+    assert_close(door.world().vertices()[2], Vector2::new(6.0, 1.0));
+    assert_close(door.local().vertices()[2], Vector2::new(1.0, 1.0));
+
+    let player = Circle::new(Vector2::new(5.5, 0.5), 0.25);
+    assert!(player.collide(&door));
+
+    // open the door by swinging it a quarter turn around its hinge
+    door.set_transform(Transform::new(Vector2::new(5.0, 0.0), -consts::FRAC_PI_2, 1.0));
+    assert!(!player.collide(&door));
+    assert_close(door.world().vertices()[2], Vector2::new(6.0, -1.0));
+
+    let ray = Ray::new(Vector2::new(0.0, -0.5), Vector2::unit_x());
+    assert!((door.raycast(&ray, 10.0).unwrap().distance() - 5.0).abs() < EPSILON);
+
+    // circles grow with the scale
+    let circle = Circle::new(Vector2::new(1.0, 0.0), 0.5).with_transform(Transform::new(Vector2::zero(), 0.0, 3.0));
+    assert!((circle.world().radius() - 1.5).abs() < EPSILON);
+    assert_close(circle.world().center(), Vector2::new(3.0, 0.0));
+}
+
+#[test]
+fn decompose_non_finite() {
+    let mut outline = square(Vector2::new(0.0, 0.0), 4.0).vertices().to_vec();
+    let hole = square(Vector2::new(1.0, 1.0), 1.0).vertices().to_vec();
+
+    outline[2].x = FLOAT::NAN;
+    assert!(matches!(decompose::validate(&outline, &[]), Err(decompose::Error::NotFinite(decompose::Ring::Outline))));
+    assert!(matches!(decompose::decompose(&outline, std::slice::from_ref(&hole)), Err(decompose::Error::NotFinite(_))));
+
+    outline[2].x = 4.0;
+    let mut bad = hole;
+    bad[0].y = FLOAT::INFINITY;
+    assert!(matches!(decompose::decompose(&outline, &[bad]), Err(decompose::Error::NotFinite(decompose::Ring::Hole(0)))));
+}
+
+/// Get the area of a ring, whichever way it winds.
+fn area(vertices: &[Vector2]) -> FLOAT {
+    vertices.iter().zip(vertices.iter().cycle().skip(1)).map(|(a, b)| a.perp_dot(*b)).sum::<FLOAT>().abs() / 2.0
+}
+
+fn is_convex(vertices: &[Vector2]) -> bool {
+    let len = vertices.len();
+
+    (0..len).all(|i| (vertices[(i + 1) % len] - vertices[i]).perp_dot(vertices[(i + 2) % len] - vertices[(i + 1) % len]) >= -EPSILON)
+}
+
+#[test]
+fn decompose_with_hole() {
+    let outline = square(Vector2::new(0.0, 0.0), 4.0).vertices().to_vec();
+    let mut hole = square(Vector2::new(1.0, 1.0), 2.0).vertices().to_vec();
+
+    // holes can wind either way
+    hole.reverse();
+
+    let polygons = decompose::decompose(&outline, &[hole]).unwrap();
+
+    assert!(polygons.len() > 1);
+    assert!(polygons.iter().all(|polygon| is_convex(polygon.vertices())));
+    assert!((polygons.iter().map(|polygon| area(polygon.vertices())).sum::<FLOAT>() - 12.0).abs() < 1e-9);
+
+    // the ring around the hole is covered, and the hole isn't
+    let covered = |point: Vector2| polygons.iter().any(|polygon| contains_point(polygon.vertices(), point));
+
+    for point in [Vector2::new(0.5, 0.5), Vector2::new(3.5, 2.0), Vector2::new(2.0, 3.5), Vector2::new(0.5, 3.5)] {
+        assert!(covered(point), "{:?}", point);
+    }
+
+    assert!(!covered(Vector2::new(2.0, 2.0)));
+    assert!(!covered(Vector2::new(1.5, 2.5)));
+}
+
+#[test]
+fn decompose_concave() {
+    // an L shape only needs two pieces
+    let outline = [
+        Vector2::new(0.0, 0.0),
+        Vector2::new(2.0, 0.0),
+        Vector2::new(2.0, 1.0),
+        Vector2::new(1.0, 1.0),
+        Vector2::new(1.0, 2.0),
+        Vector2::new(0.0, 2.0),
+    ];
+
+    let polygons = decompose::decompose(&outline, &[]).unwrap();
+
+    assert_eq!(polygons.len(), 2);
+    assert!(polygons.iter().all(|polygon| is_convex(polygon.vertices())));
+    assert!((polygons.iter().map(|polygon| area(polygon.vertices())).sum::<FLOAT>() - 3.0).abs() < 1e-9);
+}
+
+#[test]
+fn decompose_invalid() {
+    let outline = square(Vector2::new(0.0, 0.0), 4.0).vertices().to_vec();
+
+    // a bow tie crosses itself
+    let bow_tie = [Vector2::new(0.0, 0.0), Vector2::new(2.0, 2.0), Vector2::new(2.0, 0.0), Vector2::new(0.0, 2.0)];
+    match decompose::validate(&bow_tie, &[]) {
+        Err(decompose::Error::Intersections(intersections)) => {
+            assert_eq!(intersections.len(), 1);
+            assert_close(intersections[0].point(), Vector2::new(1.0, 1.0));
+        }
+        other => panic!("{:?}", other),
+    }
+
+    // a hole outside of the outline, or inside of another hole
+    let outside = square(Vector2::new(5.0, 5.0), 1.0).vertices().to_vec();
+    assert!(matches!(decompose::validate(&outline, &[outside]), Err(decompose::Error::Misplaced(0))));
+
+    let big = square(Vector2::new(0.5, 0.5), 3.0).vertices().to_vec();
+    let small = square(Vector2::new(1.0, 1.0), 1.0).vertices().to_vec();
+    assert!(matches!(decompose::validate(&outline, &[big, small]), Err(decompose::Error::Misplaced(_))));
+
+    // a line has no area
+    let line = [Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0), Vector2::new(2.0, 0.0)];
+    assert!(decompose::validate(&line, &[]).is_err());
+    assert!(matches!(decompose::validate(&line[..2], &[]), Err(decompose::Error::Degenerate(decompose::Ring::Outline))));
+}
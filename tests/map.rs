@@ -0,0 +1,160 @@
+//! Tests for `map`.
+
+use among_us::collide::Polygon;
+use among_us::game::task::Task;
+use among_us::map::{Console, Error, Map, Room, Vent};
+use among_us::math::*;
+use among_us::net::binary::decode::{Cursor, ErrorKind};
+use among_us::net::binary::encode::CursorMut;
+
+fn rect(min: Vector2, max: Vector2) -> Polygon {
+    vec![min, Vector2::new(max.x, min.y), max, Vector2::new(min.x, max.y)].into()
+}
+
+fn map() -> Map {
+    let mut map = Map::new("test", Vector2::new(5.0, 5.0));
+    map.push_room(Room::new("cafeteria", rect(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0))));
+    map.push_wall(rect(Vector2::new(2.0, 2.0), Vector2::new(3.0, 3.0)));
+
+    let mut a = Vent::new("a", Vector2::new(1.0, 1.0));
+    let mut b = Vent::new("b", Vector2::new(9.0, 9.0));
+    a.connect("b");
+    b.connect("a");
+    map.push_vent(a);
+    map.push_vent(b);
+
+    map
+}
+
+fn encode(map: &Map) -> Vec<u8> {
+    let mut cursor = CursorMut::new();
+    assert!(cursor.encode(map).is_ok(), "encoding failed");
+    cursor.into()
+}
+
+#[test]
+fn validate() {
+    assert!(map().validate().is_ok());
+
+    let mut bad = map();
+    bad.push_wall(vec![Vector2::new(0.0, 0.0), Vector2::new(1.0, 0.0)].into());
+    assert!(matches!(bad.validate(), Err(Error::Wall(1))));
+
+    let mut bad = map();
+    let mut vent = Vent::new("c", Vector2::new(0.0, 0.0));
+    vent.connect("nowhere");
+    bad.push_vent(vent);
+    assert!(matches!(bad.validate(), Err(Error::Connection(..))));
+
+    let mut bad = map();
+    bad.set_spawns(Vec::new());
+    assert!(matches!(bad.validate(), Err(Error::NoSpawns)));
+}
+
+#[test]
+fn concave_walls() {
+    // an L shaped wall has to be split up before it can collide
+    let mut bad = map();
+    bad.push_wall(
+        vec![
+            Vector2::new(0.0, 0.0),
+            Vector2::new(2.0, 0.0),
+            Vector2::new(2.0, 1.0),
+            Vector2::new(1.0, 1.0),
+            Vector2::new(1.0, 2.0),
+            Vector2::new(0.0, 2.0),
+        ]
+        .into(),
+    );
+    assert!(matches!(bad.validate(), Err(Error::Concave(1))));
+
+    // and so does a wall that crosses itself, even if it always turns the
+    // same way
+    let mut bad = map();
+    let star = (0..5).map(|i| {
+        let angle = i as FLOAT * 4.0 * consts::PI / 5.0;
+        Vector2::new(angle.cos(), angle.sin())
+    });
+    bad.push_wall(star.collect::<Vec<_>>().into());
+    assert!(matches!(bad.validate(), Err(Error::Concave(1))));
+
+    // walls can go either way around
+    let mut good = map();
+    good.push_wall(vec![Vector2::new(0.0, 0.0), Vector2::new(0.0, 1.0), Vector2::new(1.0, 0.0)].into());
+    assert!(good.validate().is_ok());
+
+    let mut bad = map();
+    bad.push_wall(vec![Vector2::new(0.0, 0.0), Vector2::new(FLOAT::NAN, 1.0), Vector2::new(1.0, 0.0)].into());
+    assert!(bad.validate().is_err());
+}
+
+#[test]
+fn not_finite() {
+    let nan = Vector2::new(FLOAT::NAN, 0.0);
+    let inf = Vector2::new(0.0, FLOAT::INFINITY);
+
+    let bad = Map::new("test", nan);
+    assert!(matches!(bad.validate(), Err(Error::NotFinite(what)) if what == "the emergency button"));
+
+    let mut bad = map();
+    bad.set_spawns(vec![Vector2::new(1.0, 1.0), inf]);
+    assert!(matches!(bad.validate(), Err(Error::NotFinite(what)) if what == "spawn 1"));
+
+    let mut bad = map();
+    bad.push_wall(vec![Vector2::new(0.0, 0.0), inf, Vector2::new(1.0, 0.0)].into());
+    assert!(matches!(bad.validate(), Err(Error::NotFinite(what)) if what == "wall 1"));
+
+    let mut bad = map();
+    bad.push_room(Room::new("nowhere", rect(Vector2::new(0.0, 0.0), Vector2::new(1.0, FLOAT::INFINITY))));
+    assert!(matches!(bad.validate(), Err(Error::NotFinite(what)) if what == "room \"nowhere\""));
+
+    let mut bad = map();
+    bad.push_vent(Vent::new("c", nan));
+    assert!(matches!(bad.validate(), Err(Error::NotFinite(what)) if what == "vent \"c\""));
+
+    let mut bad = map();
+    bad.push_console(Console::new(Task::new("wires"), Vector2::new(1.0, 1.0)));
+    bad.push_console(Console::new(Task::new("fuel"), inf));
+    assert!(matches!(bad.validate(), Err(Error::NotFinite(what)) if what == "console 1"));
+    assert_eq!(bad.validate().unwrap_err().to_string(), "console 1 has a position that isn't finite");
+}
+
+#[test]
+fn rooms() {
+    let map = map();
+
+    assert_eq!(map.room_at(Vector2::new(5.0, 5.0)).map(|room| room.name()), Some("cafeteria"));
+    assert!(map.room_at(Vector2::new(15.0, 5.0)).is_none());
+    assert_eq!(map.vent("a").unwrap().connections(), ["b"]);
+}
+
+#[test]
+fn decode_validates() {
+    let bytes = encode(&map());
+    let decoded: Map = Cursor::new(&bytes).decode().unwrap();
+    assert_eq!(encode(&decoded), bytes);
+
+    // a map that fails validation can't be decoded
+    let mut bad = map();
+    bad.push_wall(vec![Vector2::new(0.0, 0.0), Vector2::new(2.0, 0.0), Vector2::new(1.0, 0.5), Vector2::new(1.0, 2.0)].into());
+
+    let error = Cursor::new(&encode(&bad)).decode::<Map>().unwrap_err();
+    assert_eq!(*error.kind(), ErrorKind::Invalid);
+    assert!(error.to_string().contains("Map"), "{}", error);
+    assert!(error.to_string().ends_with("wall 1 is not convex"), "{}", error);
+
+    // and the decode error keeps why
+    let reason = std::error::Error::source(&error).and_then(|e| e.downcast_ref::<Error>());
+    assert!(matches!(reason, Some(Error::Concave(1))), "{:?}", reason);
+}
+
+#[test]
+#[cfg(feature = "ron")]
+fn from_ron() {
+    let map = Map::from_ron(include_str!("../maps/example.ron")).unwrap();
+    assert_eq!(map.name(), "Example");
+
+    let concave = "(name: \"bad\", walls: [[(x: 0.0, y: 0.0), (x: 2.0, y: 0.0), (x: 1.0, y: 0.5), (x: 1.0, y: 2.0)]], button: (x: 5.0, y: 5.0), spawns: [(x: 5.0, y: 5.0)])";
+    assert!(matches!(Map::from_ron(concave), Err(Error::Concave(0))));
+    assert_eq!(Map::from_ron(concave).unwrap_err().to_string(), "wall 0 is not convex");
+}
@@ -0,0 +1,95 @@
+//! Tests for `game::movement`.
+
+use among_us::collide::{Circle, Geometry, Polygon};
+use among_us::game::movement::Controller;
+use among_us::math::*;
+
+fn rect(min: Vector2, max: Vector2) -> Polygon {
+    vec![min, Vector2::new(max.x, min.y), max, Vector2::new(min.x, max.y)].into()
+}
+
+#[test]
+fn open_space() {
+    let controller = Controller::new(0.5);
+    let position = controller.step(Vector2::new(1.0, 2.0), Vector2::new(3.0, -1.0), &[]);
+
+    assert_eq!(position, Vector2::new(4.0, 1.0));
+}
+
+#[test]
+fn stops_at_walls() {
+    let controller = Controller::new(0.5);
+    let walls = [rect(Vector2::new(2.0, -5.0), Vector2::new(3.0, 5.0))];
+
+    let position = controller.step(Vector2::new(0.0, 0.0), Vector2::new(10.0, 0.0), &walls);
+
+    assert!(position.x < 1.5 && position.x > 1.49, "{:?}", position);
+    assert_eq!(position.y, 0.0);
+    assert!(!Circle::new(position, 0.5).collide(&walls[0]));
+}
+
+#[test]
+fn slides_along_walls() {
+    let controller = Controller::new(0.5);
+    let walls = [rect(Vector2::new(2.0, -5.0), Vector2::new(3.0, 5.0))];
+
+    // walking diagonally into the wall keeps the part along it
+    let position = controller.step(Vector2::new(0.0, 0.0), Vector2::new(3.0, 2.0), &walls);
+
+    assert!(position.x < 1.5 && position.x > 1.49, "{:?}", position);
+    assert!((position.y - 2.0).abs() < 1e-2, "{:?}", position);
+}
+
+#[test]
+fn slides_into_corners() {
+    let controller = Controller::new(0.5);
+    let walls = [
+        rect(Vector2::new(2.0, -5.0), Vector2::new(3.0, 5.0)),
+        rect(Vector2::new(-5.0, 2.0), Vector2::new(3.0, 3.0)),
+    ];
+
+    let position = controller.step(Vector2::new(0.0, 0.0), Vector2::new(5.0, 4.0), &walls);
+
+    assert!(position.x < 1.5 && position.x > 1.49, "{:?}", position);
+    assert!(position.y < 1.5 && position.y > 1.49, "{:?}", position);
+}
+
+#[test]
+fn depenetrates() {
+    let controller = Controller::new(0.5);
+    let walls = [rect(Vector2::new(0.0, 0.0), Vector2::new(2.0, 2.0))];
+
+    // stuck in the right side of a wall gets pushed out of it
+    let position = controller.depenetrate(Vector2::new(2.25, 1.0), &walls);
+
+    assert!(position.x >= 2.5, "{:?}", position);
+    assert_eq!(position.y, 1.0);
+    assert!(!Circle::new(position, 0.5).collide(&walls[0]));
+
+    // and the same happens before moving
+    let position = controller.step(Vector2::new(2.25, 1.0), Vector2::new(0.0, 1.0), &walls);
+    assert!(position.x >= 2.5 && (position.y - 2.0).abs() < 1e-9, "{:?}", position);
+}
+
+#[test]
+fn never_enters_walls() {
+    let controller = Controller::new(0.35);
+    let walls = [
+        rect(Vector2::new(2.0, -5.0), Vector2::new(3.0, 5.0)),
+        vec![Vector2::new(-1.0, 1.0), Vector2::new(1.0, 1.5), Vector2::new(0.0, 3.0)].into(),
+    ];
+
+    // wander up and to the right, into the walls
+    let mut position = Vector2::new(0.0, 0.0);
+
+    for i in 0..200 {
+        let angle = i as FLOAT * 0.7;
+        position = controller.step(position, Vector2::new(angle.cos() + 0.3, angle.sin() + 0.1) * 0.5, &walls);
+
+        for wall in &walls {
+            assert!(Circle::new(position, 0.35).penetration(wall).is_none(), "{:?} at step {}", position, i);
+        }
+    }
+
+    assert!(position.x > 1.6, "{:?}", position);
+}
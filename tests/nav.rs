@@ -0,0 +1,147 @@
+//! Tests for `nav`.
+
+use among_us::collide::ray::Segment;
+use among_us::collide::{Circle, Geometry, Polygon};
+use among_us::map::{Map, Room};
+use among_us::math::*;
+use among_us::nav::{funnel, Error, NavMesh};
+
+const RADIUS: FLOAT = 0.35;
+
+fn rect(min: Vector2, max: Vector2) -> Polygon {
+    vec![min, Vector2::new(max.x, min.y), max, Vector2::new(min.x, max.y)].into()
+}
+
+fn length(path: &[Vector2]) -> FLOAT {
+    path.windows(2).map(|pair| (pair[1] - pair[0]).magnitude()).sum()
+}
+
+#[test]
+fn straight_line() {
+    let floor = [rect(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0))];
+    let mesh = NavMesh::new(&floor, &[], RADIUS, 0.5).unwrap();
+
+    let path = mesh.find_path(Vector2::new(1.0, 1.0), Vector2::new(9.0, 8.0)).unwrap();
+    assert_eq!(path, vec![Vector2::new(1.0, 1.0), Vector2::new(9.0, 8.0)]);
+}
+
+#[test]
+fn around_an_obstacle() {
+    let floor = [rect(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0))];
+    let walls = [rect(Vector2::new(4.0, 0.0), Vector2::new(6.0, 7.0))];
+    let mesh = NavMesh::new(&floor, &walls, RADIUS, 0.25).unwrap();
+
+    // nobody can stand closer to the wall than their radius
+    for cell in mesh.cells() {
+        let near = Circle::new(cell.clamp(Vector2::new(5.0, 3.5)), RADIUS);
+        assert!(!near.collide(&walls[0]), "{:?}", cell);
+    }
+
+    let (from, to) = (Vector2::new(1.0, 1.0), Vector2::new(9.0, 1.0));
+    let path = mesh.find_path(from, to).unwrap();
+
+    assert_eq!(path.first(), Some(&from));
+    assert_eq!(path.last(), Some(&to));
+
+    // the path bends around the top of the wall, and never goes through it
+    assert!(path.len() >= 4, "{:?}", path);
+    assert!(path.iter().any(|point| point.y > 7.0), "{:?}", path);
+
+    for pair in path.windows(2) {
+        assert!(Segment::new(pair[0], pair[1]).clear(&walls), "{:?}", path);
+    }
+
+    // it's pulled tight, so it isn't much longer than going over the corners
+    let shortest = (Vector2::new(4.0, 7.0) - from).magnitude() + 2.0 + (to - Vector2::new(6.0, 7.0)).magnitude();
+    let distance = mesh.distance(from, to).unwrap();
+
+    assert!((distance - length(&path)).abs() < 1e-9);
+    assert!(distance > shortest && distance < shortest + 2.0, "{} vs {}", distance, shortest);
+}
+
+#[test]
+fn no_way_through() {
+    let floor = [rect(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0))];
+    let walls = [rect(Vector2::new(4.0, -1.0), Vector2::new(6.0, 11.0))];
+    let mesh = NavMesh::new(&floor, &walls, RADIUS, 0.5).unwrap();
+
+    assert!(mesh.find_path(Vector2::new(1.0, 1.0), Vector2::new(9.0, 1.0)).is_none());
+    assert!(mesh.find_path(Vector2::new(1.0, 1.0), Vector2::new(1.0, 9.0)).is_some());
+
+    // a mesh with no floor has nowhere to go
+    let empty = NavMesh::new(&[], &walls, RADIUS, 0.5).unwrap();
+    assert!(empty.cells().is_empty());
+    assert!(empty.find_path(Vector2::new(1.0, 1.0), Vector2::new(1.0, 9.0)).is_none());
+}
+
+#[test]
+fn limits() {
+    let floor = [rect(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0))];
+
+    // a floor that isn't finite, or needs far too many cells, is rejected
+    // instead of taking forever
+    let nan = [floor[0].clone(), rect(Vector2::new(0.0, 0.0), Vector2::new(FLOAT::NAN, 1.0))];
+    assert_eq!(NavMesh::new(&nan, &[], RADIUS, 0.5).unwrap_err(), Error::NotFinite(1));
+
+    let infinite = [rect(Vector2::new(0.0, 0.0), Vector2::new(FLOAT::INFINITY, 1.0))];
+    assert_eq!(NavMesh::new(&infinite, &[], RADIUS, 0.5).unwrap_err(), Error::NotFinite(0));
+
+    assert_eq!(NavMesh::new(&floor, &[], RADIUS, 1e-4).unwrap_err(), Error::TooBig);
+
+    let huge = [rect(Vector2::new(-1e300, -1e300), Vector2::new(1e300, 1e300))];
+    assert_eq!(NavMesh::new(&huge, &[], RADIUS, 0.5).unwrap_err(), Error::TooBig);
+
+    let line = [vec![Vector2::new(0.0, -1e308), Vector2::new(0.0, 1e308), Vector2::new(0.0, 0.0)].into()];
+    assert_eq!(NavMesh::new(&line, &[], RADIUS, 0.5).unwrap_err(), Error::TooBig);
+
+    // but right up to the limit is fine
+    assert!(NavMesh::new(&floor, &[], RADIUS, 10.0 / 1024.0).is_ok());
+}
+
+#[test]
+fn off_the_mesh() {
+    let floor = [rect(Vector2::new(0.0, 0.0), Vector2::new(10.0, 10.0))];
+    let mesh = NavMesh::new(&floor, &[], RADIUS, 0.5).unwrap();
+
+    // points off of the mesh are moved onto it
+    let path = mesh.find_path(Vector2::new(-5.0, 5.0), Vector2::new(5.0, 5.0)).unwrap();
+    let start = path[0];
+
+    assert!(mesh.cell_at(start).is_some());
+    assert_eq!(start, Vector2::new(0.0, 5.0));
+}
+
+#[test]
+fn from_map() {
+    // two rooms joined by a narrow hallway
+    let mut map = Map::new("test", Vector2::new(2.0, 2.0));
+    map.push_room(Room::new("left", rect(Vector2::new(0.0, 0.0), Vector2::new(4.0, 4.0))));
+    map.push_room(Room::new("hallway", rect(Vector2::new(4.0, 1.0), Vector2::new(8.0, 3.0))));
+    map.push_room(Room::new("right", rect(Vector2::new(8.0, 0.0), Vector2::new(12.0, 4.0))));
+
+    let mesh = NavMesh::from_map(&map, RADIUS, 0.25).unwrap();
+    let path = mesh.find_path(Vector2::new(1.0, 3.8), Vector2::new(11.0, 3.8)).unwrap();
+
+    // going straight across would miss the hallway, so the path dips into it
+    assert_eq!(path.len(), 4, "{:?}", path);
+
+    // and hugs the top corners of the hallway
+    assert_eq!(path[1], Vector2::new(4.0, 3.0));
+    assert_eq!(path[2], Vector2::new(8.0, 3.0));
+}
+
+#[test]
+fn funnel() {
+    // a straight corridor of portals needs no corners
+    let portals = [
+        (Vector2::new(1.0, -1.0), Vector2::new(1.0, 1.0)),
+        (Vector2::new(2.0, -1.0), Vector2::new(2.0, 1.0)),
+    ];
+    let path = funnel::string_pull(Vector2::new(0.0, 0.0), Vector2::new(3.0, 0.0), &portals);
+    assert_eq!(path, vec![Vector2::new(0.0, 0.0), Vector2::new(3.0, 0.0)]);
+
+    // but a portal off to the side bends the path around its edge
+    let portals = [(Vector2::new(1.0, 2.0), Vector2::new(1.0, 4.0))];
+    let path = funnel::string_pull(Vector2::new(0.0, 0.0), Vector2::new(2.0, 0.0), &portals);
+    assert_eq!(path, vec![Vector2::new(0.0, 0.0), Vector2::new(1.0, 2.0), Vector2::new(2.0, 0.0)]);
+}
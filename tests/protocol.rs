@@ -0,0 +1,150 @@
+//! Tests for `net::protocol`.
+
+use among_us::math::*;
+use among_us::net::protocol::*;
+
+fn round_trip(packet: Packet) {
+    let bytes = packet.to_bytes().unwrap();
+    assert_eq!(Packet::from_bytes(&bytes).unwrap(), packet);
+}
+
+#[test]
+fn messages_round_trip() {
+    let (red, blue) = (PlayerId(0), PlayerId(1));
+
+    let messages = vec![
+        Message::Join { player: red, name: String::from("red"), color: 0 },
+        Message::Leave { player: blue, reason: LeaveReason::Kicked },
+        Message::Movement { player: red, sequence: 7, position: Vector2::new(1.0, 2.0), velocity: Vector2::new(0.0, -1.0) },
+        Message::Chat { player: blue, text: String::from("where?") },
+        Message::TaskProgress { player: red, task: 3, step: 1, steps: 2 },
+        Message::Kill { killer: blue, victim: red },
+        Message::Report { reporter: red, body: Some(blue) },
+        Message::Report { reporter: red, body: None },
+        Message::Meeting { caller: red, body: None, duration: 120 },
+        Message::Vote { voter: blue, target: Some(red) },
+        Message::Vote { voter: red, target: None },
+        Message::GameOver { winner: Team::Impostors, reason: GameOverReason::Kills },
+    ];
+
+    for message in messages {
+        round_trip(Packet::Message(message));
+    }
+
+    round_trip(Packet::Hello(Hello::new("red")));
+    round_trip(Packet::Welcome(Welcome::new(red)));
+    round_trip(Packet::Reject(Reject::Full));
+}
+
+#[test]
+fn tags_are_stable() {
+    let bytes = Packet::Message(Message::Kill { killer: PlayerId(4), victim: PlayerId(2) }).to_bytes().unwrap();
+    assert_eq!(bytes, [3, 5, 4, 2]);
+
+    let bytes = Packet::Hello(Hello::new("a")).to_bytes().unwrap();
+    assert_eq!(bytes[..5], [0, VERSION as u8, 0, 0, 0]);
+}
+
+#[test]
+fn handshake_accepts_the_same_version() {
+    let bytes = Packet::Hello(Hello::new("red")).to_bytes().unwrap();
+    let hello = accept(&bytes).unwrap();
+
+    assert_eq!(hello.name(), "red");
+    assert_eq!(hello.version(), VERSION);
+    assert!(hello.check().is_ok());
+}
+
+#[test]
+fn handshake_rejects_other_versions() {
+    // a client from the future, with fields we don't know about
+    let mut bytes = vec![0];
+    bytes.extend((VERSION + 1).to_le_bytes());
+    bytes.extend([0xff; 9]);
+
+    let reject = accept(&bytes).unwrap_err();
+    assert_eq!(reject, Reject::Version { client: VERSION + 1, server: VERSION });
+    assert!(reject.to_string().contains(&format!("protocol version {}", VERSION + 1)));
+
+    // a client from the past
+    let mut bytes = vec![0];
+    bytes.extend(0u32.to_le_bytes());
+
+    let reject = accept(&bytes).unwrap_err();
+    assert!(reject.to_string().contains("update the client"));
+
+    // the rejection can be sent back to the client
+    round_trip(Packet::Reject(reject));
+}
+
+#[test]
+fn handshake_rejects_garbage() {
+    assert_eq!(accept(&[]), Err(Reject::Malformed));
+    assert_eq!(accept(&[3, 0, 0, 0, 0]), Err(Reject::Malformed));
+    assert_eq!(accept(&[0, VERSION as u8, 0]), Err(Reject::Malformed));
+
+    let mut bytes = Packet::Hello(Hello::new("red")).to_bytes().unwrap();
+    bytes.push(0);
+    assert_eq!(accept(&bytes), Err(Reject::Malformed));
+}
+
+#[test]
+fn room_codes() {
+    let code = RoomCode::parse("abcd").unwrap();
+    assert_eq!(code, RoomCode::parse("ABCD").unwrap());
+    assert_eq!(code.to_string(), "ABCD");
+    assert_eq!(format!("{:?}", code), "RoomCode(ABCD)");
+    assert!(!code.is_long());
+
+    let code = RoomCode::parse("ZzAaQx").unwrap();
+    assert_eq!(code.to_string(), "ZZAAQX");
+    assert!(code.is_long());
+
+    // the same letters are a different code at a different length
+    assert_ne!(RoomCode::parse("AAAA"), RoomCode::parse("AAAAAA"));
+
+    for code in ["", "ABC", "ABCDE", "ABCDEFG", "AB1D", "ÄBC"] {
+        assert_eq!(RoomCode::parse(code), None, "{:?}", code);
+    }
+}
+
+#[test]
+fn lobbies_round_trip() {
+    let code = RoomCode::parse("QWERTY").unwrap();
+    let info = LobbyInfo {
+        code,
+        public: true,
+        host: PlayerId(3),
+        host_name: String::from("red"),
+        players: 4,
+        max_players: 10,
+        started: false,
+    };
+
+    round_trip(Packet::Lobby(LobbyRequest::Create { public: false, max_players: 6 }));
+    round_trip(Packet::Lobby(LobbyRequest::Join { code }));
+    round_trip(Packet::Lobby(LobbyRequest::List));
+    round_trip(Packet::Lobby(LobbyRequest::Kick { player: PlayerId(2) }));
+    round_trip(Packet::Lobby(LobbyRequest::Ban { player: PlayerId(2) }));
+    round_trip(Packet::LobbyState(info.clone()));
+    round_trip(Packet::LobbyList(vec![]));
+    round_trip(Packet::LobbyList(vec![info.clone(), LobbyInfo { code: RoomCode::parse("ABCD").unwrap(), ..info }]));
+    round_trip(Packet::Reject(Reject::NotFound));
+    round_trip(Packet::Reject(Reject::Banned));
+    round_trip(Packet::Reject(Reject::TooManyLobbies));
+}
+
+#[test]
+fn room_codes_out_of_range() {
+    let code = RoomCode::parse("ZZZZ").unwrap();
+    let mut bytes = Packet::Lobby(LobbyRequest::Join { code }).to_bytes().unwrap();
+    assert_eq!(bytes[..2], [4, 1]);
+
+    // one past "ZZZZ" doesn't fit in four letters
+    bytes[2..6].copy_from_slice(&26u32.pow(4).to_le_bytes());
+    assert!(Packet::from_bytes(&bytes).is_err());
+
+    // but it does in six
+    bytes[2..6].copy_from_slice(&(26u32.pow(4) | 1 << 31).to_le_bytes());
+    assert_eq!(Packet::from_bytes(&bytes).unwrap(), Packet::Lobby(LobbyRequest::Join { code: RoomCode::parse("ABAAAA").unwrap() }));
+}
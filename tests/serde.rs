@@ -0,0 +1,94 @@
+//! Tests that the serde format matches `net::binary`.
+
+#![cfg(feature = "serde")]
+
+use among_us::math::*;
+use among_us::net::binary::decode::{Cursor, Decode};
+use among_us::net::binary::encode::{CursorMut, Encode};
+use among_us::net::binary::serde::{from_bytes, from_reader, to_bytes, to_writer, Error};
+
+use proptest::prelude::*;
+use serde::{Deserialize, Serialize};
+
+fn encode<T>(value: &T) -> Vec<u8>
+where T: Encode + ?Sized {
+    let mut cursor = CursorMut::new();
+    assert!(cursor.encode(value).is_ok(), "encoding failed");
+    cursor.into()
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+enum Action {
+    Idle,
+    Walk(Vector2),
+    Chat { channel: u8, message: String },
+    Vote(Option<u8>, bool),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Encode, Decode)]
+struct Packet {
+    id: u32,
+    time: f64,
+    name: String,
+    players: Vec<(u8, i16)>,
+    colors: [u8; 4],
+    action: Action,
+    unit: (),
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        Just(Action::Idle),
+        (any::<FLOAT>(), any::<FLOAT>()).prop_map(|(x, y)| Action::Walk(Vector2::new(x, y))),
+        (any::<u8>(), any::<String>()).prop_map(|(channel, message)| Action::Chat { channel, message }),
+        (any::<Option<u8>>(), any::<bool>()).prop_map(|(a, b)| Action::Vote(a, b)),
+    ]
+}
+
+fn packet() -> impl Strategy<Value = Packet> {
+    (any::<u32>(), -1e9..1e9f64, any::<String>(), any::<Vec<(u8, i16)>>(), any::<[u8; 4]>(), action())
+        .prop_map(|(id, time, name, players, colors, action)| Packet { id, time, name, players, colors, action, unit: () })
+}
+
+proptest! {
+    #[test]
+    fn serde_matches_binary(packet in packet()) {
+        let bytes = to_bytes(&packet).ok();
+        prop_assert_eq!(bytes.as_deref(), Some(&encode(&packet)[..]));
+    }
+
+    #[test]
+    fn serde_round_trips(packet in packet()) {
+        let bytes = encode(&packet);
+        prop_assert_eq!(from_bytes::<Packet>(&bytes).ok(), Some(packet.clone()));
+        prop_assert_eq!(Cursor::new(&bytes).decode::<Packet>().ok(), Some(packet));
+    }
+}
+
+#[test]
+#[cfg(feature = "ron")]
+fn maps_match_binary() {
+    use among_us::map::Map;
+
+    let map = Map::from_ron(include_str!("../maps/example.ron")).expect("example map is valid");
+    let bytes = encode(&map);
+
+    assert_eq!(to_bytes(&map).ok().as_deref(), Some(&bytes[..]));
+    assert_eq!(from_bytes::<Map>(&bytes).ok().map(|map| encode(&map)), Some(bytes));
+}
+
+#[test]
+fn serde_errors() {
+    assert!(matches!(from_bytes::<u8>(&[1, 2]), Err(Error::TrailingBytes)));
+    assert!(matches!(from_bytes::<u16>(&[1]), Err(Error::Decode(_))));
+    assert!(matches!(from_bytes::<Action>(&[9]), Err(Error::Custom(_))));
+}
+
+proptest! {
+    #[test]
+    fn serde_streams_match_binary(packet in packet()) {
+        let bytes = to_writer(&packet, Vec::new()).ok();
+        prop_assert_eq!(bytes.as_deref(), Some(&encode(&packet)[..]));
+        prop_assert_eq!(from_reader::<Packet, _>(&encode(&packet)[..]).ok(), Some(packet));
+    }
+}
@@ -0,0 +1,679 @@
+//! Tests for the dedicated server, on a simulated network.
+
+use std::cell::Cell;
+use std::io;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::time::Duration;
+
+use among_us::game::MAX_PLAYERS;
+use among_us::map::Map;
+use among_us::math::*;
+use among_us::net::protocol::*;
+use among_us::net::transport::sim::*;
+use among_us::net::transport::{self, Channel, Endpoint, Event, Socket};
+use among_us::server::{ClientError, Config, ConfigError, Server};
+
+const SERVER: u8 = 1;
+
+fn addr(i: u8) -> SocketAddr {
+    SocketAddr::from(([10, 0, 0, i], 22023))
+}
+
+struct Client {
+    endpoint: Endpoint<SimSocket>,
+    packets: Vec<Packet>,
+    disconnected: bool,
+}
+
+impl Client {
+    fn connect(network: &Network, i: u8, hello: &[u8]) -> Client {
+        Client::connect_from(network, addr(i), hello)
+    }
+
+    fn connect_from(network: &Network, from: SocketAddr, hello: &[u8]) -> Client {
+        let mut endpoint = Endpoint::new(network.socket(from), transport::Config::new());
+        endpoint.connect(addr(SERVER), network.now()).unwrap();
+        endpoint.send(addr(SERVER), Channel::Reliable, hello, network.now()).unwrap();
+
+        Client {
+            endpoint,
+            packets: Vec::new(),
+            disconnected: false,
+        }
+    }
+
+    fn hello(network: &Network, i: u8) -> Client {
+        let hello = Packet::Hello(Hello::new(format!("player {}", i)));
+        Client::connect(network, i, &hello.to_bytes().unwrap())
+    }
+
+    /// Connect, and create a lobby.
+    fn host(network: &Network, i: u8, public: bool, max_players: u8) -> Client {
+        let mut client = Client::hello(network, i);
+        client.send(network, Channel::Reliable, Packet::Lobby(LobbyRequest::Create { public, max_players }));
+        client
+    }
+
+    /// Connect, and join a lobby.
+    fn join(network: &Network, i: u8, code: RoomCode) -> Client {
+        let mut client = Client::hello(network, i);
+        client.send(network, Channel::Reliable, Packet::Lobby(LobbyRequest::Join { code }));
+        client
+    }
+
+    fn send(&mut self, network: &Network, channel: Channel, packet: Packet) {
+        self.endpoint.send(addr(SERVER), channel, &packet.to_bytes().unwrap(), network.now()).unwrap();
+    }
+
+    fn poll(&mut self, network: &Network) {
+        if self.disconnected {
+            return;
+        }
+
+        let mut events = Vec::new();
+        self.endpoint.poll(network.now(), &mut events).unwrap();
+
+        for event in events {
+            match event {
+                Event::Message(_, _, bytes) => self.packets.push(Packet::from_bytes(&bytes).unwrap()),
+                Event::Disconnected(..) => self.disconnected = true,
+                Event::Connected(_) => {}
+            }
+        }
+    }
+
+    fn welcome(&self) -> Option<PlayerId> {
+        self.packets.iter().find_map(|packet| match packet {
+            Packet::Welcome(welcome) => Some(welcome.player()),
+            _ => None,
+        })
+    }
+
+    /// The last the client heard about its lobby.
+    fn lobby(&self) -> Option<&LobbyInfo> {
+        self.packets.iter().rev().find_map(|packet| match packet {
+            Packet::LobbyState(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    fn rejected(&self) -> Option<&Reject> {
+        self.packets.iter().find_map(|packet| match packet {
+            Packet::Reject(reject) => Some(reject),
+            _ => None,
+        })
+    }
+
+    fn reliable(&self) -> Vec<&Message> {
+        self.packets
+            .iter()
+            .filter_map(|packet| match packet {
+                Packet::Message(Message::Movement { .. }) => None,
+                Packet::Message(message) => Some(message),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn server(network: &Network, config: Config) -> Server<SimSocket> {
+    let endpoint = Endpoint::new(network.socket(addr(SERVER)), transport::Config::new());
+    Server::new(endpoint, config, Map::new("test", Vector2::new(0.0, 0.0)))
+}
+
+/// Run everything for `duration` of simulated time, in steps of 5ms,
+/// returning the clients the server couldn't send to.
+fn run<S>(network: &Network, server: &mut Server<S>, clients: &mut [Client], duration: Duration) -> Vec<ClientError>
+where S: Socket {
+    let end = network.now() + duration;
+    let mut errors = Vec::new();
+
+    while network.now() < end {
+        network.advance(Duration::from_millis(5));
+        server.update(network.now(), &mut errors).unwrap();
+
+        for client in clients.iter_mut() {
+            client.poll(network);
+        }
+    }
+
+    errors
+}
+
+/// Open a public lobby with `players` in it, the first one being the host.
+fn lobby(network: &Network, server: &mut Server<SimSocket>, players: u8) -> (RoomCode, Vec<Client>) {
+    let mut clients = vec![Client::host(network, 2, true, MAX_PLAYERS as u8)];
+    run(network, server, &mut clients, Duration::from_millis(200));
+
+    let code = clients[0].lobby().unwrap().code;
+
+    for i in 1..players {
+        clients.push(Client::join(network, 2 + i, code));
+        run(network, server, &mut clients, Duration::from_millis(200));
+    }
+
+    (code, clients)
+}
+
+fn network() -> Network {
+    let mut conditions = Conditions::new();
+    conditions.set_latency(Duration::from_millis(20));
+    Network::new(conditions, 1)
+}
+
+#[test]
+fn join() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (code, clients) = lobby(&network, &mut server, 2);
+
+    assert_eq!(clients[0].welcome(), Some(PlayerId(0)));
+    assert_eq!(clients[1].welcome(), Some(PlayerId(1)));
+
+    let join = |player: u8| Message::Join { player: PlayerId(player), name: format!("player {}", player + 2), color: player };
+
+    assert_eq!(clients[0].reliable(), vec![&join(0), &join(1)]);
+    assert_eq!(clients[1].reliable(), vec![&join(0), &join(1)]);
+
+    let info = LobbyInfo {
+        code,
+        public: true,
+        host: PlayerId(0),
+        host_name: String::from("player 2"),
+        players: 2,
+        max_players: MAX_PLAYERS as u8,
+        started: false,
+    };
+    assert_eq!(clients[0].lobby(), Some(&info));
+    assert_eq!(clients[1].lobby(), Some(&info));
+
+    assert_eq!(server.lobbies().count(), 1);
+
+    let lobby = server.lobby(code).unwrap();
+    assert_eq!(lobby.state().len(), 2);
+    assert_eq!(lobby.addr(PlayerId(1)), Some(addr(3)));
+}
+
+#[test]
+fn room_codes() {
+    let network = network();
+    let mut config = Config::new();
+    config.set_code_length(4);
+
+    let mut server = server(&network, config);
+    let mut clients: Vec<_> = (2..12).map(|i| Client::host(&network, i, false, 4)).collect();
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    let mut codes: Vec<_> = clients.iter().map(|client| client.lobby().unwrap().code).collect();
+    assert!(codes.iter().all(|code| !code.is_long() && code.to_string().len() == 4));
+
+    codes.sort();
+    codes.dedup();
+    assert_eq!(codes.len(), 10);
+
+    // six letters by default
+    let network = self::network();
+    let mut server = self::server(&network, Config::new());
+    let (code, _) = lobby(&network, &mut server, 1);
+
+    assert!(code.is_long());
+    assert_eq!(code.to_string().len(), 6);
+    assert!(code.to_string().chars().all(|c| c.is_ascii_uppercase()));
+}
+
+#[test]
+fn not_found() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (code, mut clients) = lobby(&network, &mut server, 1);
+
+    let other = if code.to_string() == "AAAAAA" { "BBBBBB" } else { "AAAAAA" };
+    clients.push(Client::join(&network, 3, RoomCode::parse(other).unwrap()));
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+
+    assert_eq!(clients[1].rejected(), Some(&Reject::NotFound));
+    assert!(clients[1].disconnected);
+}
+
+#[test]
+fn list() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+
+    let mut clients = vec![
+        Client::host(&network, 2, true, 4),
+        Client::host(&network, 3, false, 4),
+        Client::host(&network, 4, true, 8),
+    ];
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    // only public lobbies are listed, in the order of their codes
+    clients.push(Client::hello(&network, 5));
+    clients[3].send(&network, Channel::Reliable, Packet::Lobby(LobbyRequest::List));
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    let mut expected = vec![clients[0].lobby().unwrap().clone(), clients[2].lobby().unwrap().clone()];
+    expected.sort_by_key(|info| info.code);
+
+    assert_eq!(clients[3].packets, vec![Packet::LobbyList(expected)]);
+    assert!(!clients[3].disconnected);
+}
+
+#[test]
+fn max_players() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+
+    let mut clients = vec![Client::host(&network, 2, true, 2)];
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    let code = clients[0].lobby().unwrap().code;
+    assert_eq!(clients[0].lobby().unwrap().max_players, 2);
+
+    clients.push(Client::join(&network, 3, code));
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+    clients.push(Client::join(&network, 4, code));
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+
+    assert!(clients[1].welcome().is_some());
+    assert_eq!(clients[2].rejected(), Some(&Reject::Full));
+    assert!(clients[2].disconnected);
+
+    // lobbies can't be bigger than a game
+    clients.push(Client::host(&network, 5, true, u8::MAX));
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+    assert_eq!(clients[3].lobby().unwrap().max_players, MAX_PLAYERS as u8);
+}
+
+#[test]
+fn too_many_lobbies() {
+    let network = network();
+    let mut config = Config::new();
+    config.set_max_lobbies(1);
+
+    let mut server = server(&network, config);
+    let (_, mut clients) = lobby(&network, &mut server, 1);
+
+    clients.push(Client::host(&network, 3, true, 4));
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+
+    assert_eq!(clients[1].rejected(), Some(&Reject::TooManyLobbies));
+    assert!(clients[1].disconnected);
+    assert_eq!(server.lobbies().count(), 1);
+}
+
+#[test]
+fn config_limits() {
+    let mut config = Config::new();
+    assert_eq!(config.validate(), Ok(()));
+
+    // there have to be plenty of codes left over for new lobbies
+    config.set_code_length(4);
+    config.set_max_lobbies(26 * 26 * 26 * 26 / 2);
+    assert_eq!(config.validate(), Ok(()));
+
+    config.set_max_lobbies(26 * 26 * 26 * 26);
+    assert_eq!(config.validate(), Err(ConfigError::MaxLobbies(26 * 26 * 26 * 26 / 2)));
+
+    config.set_code_length(6);
+    assert_eq!(config.validate(), Ok(()));
+}
+
+#[test]
+fn kick() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (code, mut clients) = lobby(&network, &mut server, 3);
+
+    // only the host can kick
+    clients[1].send(&network, Channel::Reliable, Packet::Lobby(LobbyRequest::Kick { player: PlayerId(2) }));
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+    assert_eq!(server.lobby(code).unwrap().state().len(), 3);
+
+    clients[0].send(&network, Channel::Reliable, Packet::Lobby(LobbyRequest::Kick { player: PlayerId(2) }));
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+
+    let kicked = Message::Leave { player: PlayerId(2), reason: LeaveReason::Kicked };
+    assert_eq!(clients[0].reliable().last(), Some(&&kicked));
+    assert_eq!(clients[2].reliable().last(), Some(&&kicked));
+    assert!(clients[2].disconnected);
+    assert_eq!(server.lobby(code).unwrap().state().len(), 2);
+    assert_eq!(clients[0].lobby().unwrap().players, 2);
+
+    // kicked players can come back
+    clients.push(Client::join(&network, 4, code));
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+    assert_eq!(clients[3].welcome(), Some(PlayerId(2)));
+}
+
+#[test]
+fn ban() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (code, mut clients) = lobby(&network, &mut server, 2);
+
+    clients[0].send(&network, Channel::Reliable, Packet::Lobby(LobbyRequest::Ban { player: PlayerId(1) }));
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+
+    let banned = Message::Leave { player: PlayerId(1), reason: LeaveReason::Banned };
+    assert_eq!(clients[1].reliable().last(), Some(&&banned));
+    assert!(clients[1].disconnected);
+    assert!(server.lobby(code).unwrap().is_banned(addr(3).ip()));
+
+    // banned players can't come back, even from another port
+    let mut from = addr(3);
+    from.set_port(1234);
+
+    let hello = Packet::Hello(Hello::new("player 3 again"));
+    let mut client = Client::connect_from(&network, from, &hello.to_bytes().unwrap());
+    client.send(&network, Channel::Reliable, Packet::Lobby(LobbyRequest::Join { code }));
+
+    clients.push(client);
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+
+    assert_eq!(clients[2].rejected(), Some(&Reject::Banned));
+    assert!(clients[2].disconnected);
+    assert_eq!(server.lobby(code).unwrap().state().len(), 1);
+}
+
+#[test]
+fn host_migration() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (code, mut clients) = lobby(&network, &mut server, 3);
+
+    clients[0].endpoint.disconnect(addr(SERVER), network.now()).unwrap();
+    clients[0].disconnected = true;
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    assert_eq!(server.lobby(code).unwrap().host(), PlayerId(1));
+
+    let info = clients[2].lobby().unwrap();
+    assert_eq!(info.host, PlayerId(1));
+    assert_eq!(info.host_name, "player 3");
+    assert_eq!(info.players, 2);
+
+    // the new host can kick
+    clients[1].send(&network, Channel::Reliable, Packet::Lobby(LobbyRequest::Kick { player: PlayerId(2) }));
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+    assert!(clients[2].disconnected);
+}
+
+#[test]
+fn movement() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (code, mut clients) = lobby(&network, &mut server, 1);
+
+    // nobody can walk faster than the game allows
+    let velocity = Vector2::new(100.0, 0.0);
+    let movement = Message::Movement { player: PlayerId(0), sequence: 0, position: Vector2::new(50.0, 50.0), velocity };
+    clients[0].send(&network, Channel::Unreliable, Packet::Message(movement));
+    run(&network, &mut server, &mut clients, Duration::from_secs(1));
+
+    let lobby = server.lobby(code).unwrap();
+    let player = lobby.state().player(PlayerId(0)).unwrap();
+    let position = player.position();
+
+    assert_eq!(player.velocity(), Vector2::new(among_us::game::SPEED, 0.0));
+    assert!(position.x > 2.0 && position.x < among_us::game::SPEED, "{:?}", position);
+    assert_eq!(position.y, 0.0);
+
+    // an older movement that arrives late is ignored
+    let old = Message::Movement { player: PlayerId(0), sequence: u16::MAX, position: Vector2::new(0.0, 0.0), velocity: Vector2::new(0.0, 0.0) };
+    clients[0].send(&network, Channel::Unreliable, Packet::Message(old));
+    run(&network, &mut server, &mut clients, Duration::from_millis(100));
+
+    let lobby = server.lobby(code).unwrap();
+    assert_ne!(lobby.state().player(PlayerId(0)).unwrap().velocity(), Vector2::new(0.0, 0.0));
+
+    // and the client is told where it ended up
+    let last = clients[0].packets.iter().rev().find_map(|packet| match packet {
+        Packet::Message(Message::Movement { position, .. }) => Some(*position),
+        _ => None,
+    });
+    assert!(last.unwrap().x > 2.0);
+}
+
+#[test]
+fn non_finite_movement() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (code, mut clients) = lobby(&network, &mut server, 1);
+
+    let walk = Message::Movement { player: PlayerId(0), sequence: 0, position: Vector2::new(0.0, 0.0), velocity: Vector2::new(1.0, 0.0) };
+    clients[0].send(&network, Channel::Unreliable, Packet::Message(walk));
+    run(&network, &mut server, &mut clients, Duration::from_millis(100));
+
+    // a velocity that isn't a number stops the player instead of poisoning
+    // where they are
+    let velocity = Vector2::new(FLOAT::NAN, 1.0);
+    let movement = Message::Movement { player: PlayerId(0), sequence: 1, position: Vector2::new(0.0, 0.0), velocity };
+    clients[0].send(&network, Channel::Unreliable, Packet::Message(movement));
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+
+    let lobby = server.lobby(code).unwrap();
+    let player = lobby.state().player(PlayerId(0)).unwrap();
+    let position = player.position();
+
+    assert_eq!(player.velocity(), Vector2::new(0.0, 0.0));
+    assert!(position.x > 0.0 && position.x.is_finite() && position.y == 0.0, "{:?}", position);
+
+    let last = clients[0].packets.iter().rev().find_map(|packet| match packet {
+        Packet::Message(Message::Movement { position, .. }) => Some(*position),
+        _ => None,
+    });
+    assert_eq!(last, Some(position));
+}
+
+#[test]
+fn chat() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (_, mut clients) = lobby(&network, &mut server, 2);
+
+    let me = clients[0].welcome().unwrap();
+    let other = clients[1].welcome().unwrap();
+
+    // players can't put words in each other's mouths
+    let chat = Message::Chat { player: other, text: String::from("I'm the impostor") };
+    clients[0].send(&network, Channel::Reliable, Packet::Message(chat));
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    let expected = Message::Chat { player: me, text: String::from("I'm the impostor") };
+    assert_eq!(clients[1].reliable().last(), Some(&&expected));
+}
+
+#[test]
+fn leave() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (_, mut clients) = lobby(&network, &mut server, 2);
+
+    let gone = clients[0].welcome().unwrap();
+    clients[0].endpoint.disconnect(addr(SERVER), network.now()).unwrap();
+    clients[0].disconnected = true;
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    let leave = Message::Leave { player: gone, reason: LeaveReason::Quit };
+    assert_eq!(clients[1].reliable().last(), Some(&&leave));
+
+    clients[1].endpoint.disconnect(addr(SERVER), network.now()).unwrap();
+    clients[1].disconnected = true;
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    assert_eq!(server.lobbies().count(), 0);
+}
+
+#[test]
+fn timeout() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (_, mut clients) = lobby(&network, &mut server, 2);
+
+    // the client stops answering without saying goodbye
+    let gone = clients[0].welcome().unwrap();
+    clients[0].disconnected = true;
+    run(&network, &mut server, &mut clients, transport::Config::new().timeout() + Duration::from_secs(1));
+
+    let leave = Message::Leave { player: gone, reason: LeaveReason::Disconnected };
+    assert_eq!(clients[1].reliable().last(), Some(&&leave));
+}
+
+#[test]
+fn wrong_version() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+
+    // a hello from a newer client, with an empty name
+    let mut hello = vec![0];
+    hello.extend_from_slice(&(VERSION + 1).to_le_bytes());
+    hello.push(0);
+
+    let mut clients = vec![Client::connect(&network, 2, &hello)];
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+
+    let reject = Reject::Version { client: VERSION + 1, server: VERSION };
+    assert_eq!(clients[0].packets, vec![Packet::Reject(reject)]);
+    assert!(clients[0].disconnected);
+    assert_eq!(server.lobbies().count(), 0);
+    assert_eq!(server.endpoint().connections().count(), 0);
+}
+
+#[test]
+fn full() {
+    let network = network();
+    let mut server = server(&network, Config::new());
+    let (code, mut clients) = lobby(&network, &mut server, MAX_PLAYERS as u8);
+
+    assert!(clients.iter().all(|client| client.welcome().is_some()));
+
+    clients.push(Client::join(&network, 100, code));
+    run(&network, &mut server, &mut clients, Duration::from_millis(500));
+
+    assert_eq!(clients.last().unwrap().packets, vec![Packet::Reject(Reject::Full)]);
+    assert!(clients.last().unwrap().disconnected);
+}
+
+#[test]
+fn under_loss() {
+    let mut conditions = Conditions::new();
+    conditions.set_latency(Duration::from_millis(30));
+    conditions.set_jitter(Duration::from_millis(20));
+    conditions.set_loss(0.2);
+
+    let network = Network::new(conditions, 7);
+    let mut server = server(&network, Config::new());
+
+    let mut clients = vec![Client::host(&network, 2, true, 4)];
+    run(&network, &mut server, &mut clients, Duration::from_secs(1));
+
+    let code = clients[0].lobby().unwrap().code;
+    clients.extend((3..6).map(|i| Client::join(&network, i, code)));
+    run(&network, &mut server, &mut clients, Duration::from_secs(3));
+
+    for client in &clients {
+        assert!(!client.disconnected);
+        assert_eq!(client.reliable().len(), 4);
+        assert_eq!(client.lobby().unwrap().players, 4);
+    }
+
+    let mut players: Vec<_> = clients.iter().map(|client| client.welcome().unwrap()).collect();
+    players.sort();
+    assert_eq!(players, (0..4).map(PlayerId).collect::<Vec<_>>());
+}
+
+/// A socket that can't send to an address while it's unreachable.
+struct Unreachable {
+    inner: SimSocket,
+    addr: SocketAddr,
+    unreachable: Rc<Cell<bool>>,
+}
+
+impl Socket for Unreachable {
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<()> {
+        if addr == self.addr && self.unreachable.get() {
+            return Err(io::ErrorKind::HostUnreachable.into());
+        }
+
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        self.inner.recv_from(buf)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[test]
+fn unreachable_client() {
+    let network = network();
+    let unreachable = Rc::new(Cell::new(false));
+    let socket = Unreachable { inner: network.socket(addr(SERVER)), addr: addr(2), unreachable: unreachable.clone() };
+    let endpoint = Endpoint::new(socket, transport::Config::new());
+    let mut server = Server::new(endpoint, Config::new(), Map::new("test", Vector2::new(0.0, 0.0)));
+
+    let mut clients = vec![Client::host(&network, 2, true, MAX_PLAYERS as u8)];
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+    let code = clients[0].lobby().unwrap().code;
+
+    // the host can't be sent to anymore, which doesn't stop the server
+    // talking to anybody else
+    unreachable.set(true);
+    clients.push(Client::join(&network, 3, code));
+    let errors = run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    assert_eq!(clients[1].welcome(), Some(PlayerId(1)));
+    assert_eq!(clients[1].lobby().unwrap().players, 2);
+
+    let chat = Message::Chat { player: PlayerId(1), text: "hello?".to_string() };
+    clients[1].send(&network, Channel::Reliable, Packet::Message(chat.clone()));
+    run(&network, &mut server, &mut clients, Duration::from_millis(200));
+
+    assert!(clients[1].reliable().contains(&&chat));
+    assert_eq!(clients[0].lobby().unwrap().players, 1);
+
+    // and whoever runs the server hears about it
+    assert!(!errors.is_empty());
+    assert!(errors.iter().all(|e| e.addr() == addr(2) && matches!(e.error(), transport::Error::Io(_))));
+}
+
+#[test]
+fn tick_rate() {
+    let mut config = Config::new();
+    config.set_tick_rate(20);
+    assert_eq!(config.tick(), Duration::from_millis(50));
+
+    let network = network();
+    let mut server = server(&network, config);
+    let (code, mut clients) = lobby(&network, &mut server, 1);
+
+    let start = server.lobby(code).unwrap().state().tick();
+    run(&network, &mut server, &mut clients, Duration::from_secs(1));
+
+    let ticks = server.lobby(code).unwrap().state().tick() - start;
+    assert_eq!(ticks, 20);
+}
+
+#[test]
+#[cfg(feature = "ron")]
+fn config_file() {
+    let config: Config = ron::from_str("(port: 1234, tick_rate: 60, code_length: 4)").unwrap();
+
+    assert_eq!(config.port(), 1234);
+    assert_eq!(config.tick_rate(), 60);
+    assert_eq!(config.code_length(), 4);
+    assert_eq!(config.max_lobbies(), Config::new().max_lobbies());
+    assert_eq!(config.validate(), Ok(()));
+
+    // the setters can't check a file
+    let config: Config = ron::from_str("(tick_rate: 0)").unwrap();
+    assert_eq!(config.validate(), Err(ConfigError::TickRate));
+
+    let config: Config = ron::from_str("(code_length: 5)").unwrap();
+    assert_eq!(config.validate(), Err(ConfigError::CodeLength(5)));
+}
@@ -0,0 +1,505 @@
+//! Tests for `net::transport::sim`, and the protocol over a bad network.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::rc::Rc;
+use std::time::Duration;
+
+use among_us::math::*;
+use among_us::net::binary::encode::CursorMut;
+use among_us::net::protocol::{self, *};
+use among_us::net::transport::sim::*;
+use among_us::net::transport::*;
+
+fn addr(i: u8) -> SocketAddr {
+    SocketAddr::from(([10, 0, 0, i], 22023))
+}
+
+fn receive_all(socket: &mut SimSocket) -> Vec<Vec<u8>> {
+    let mut buf = [0; 64];
+    let mut received = Vec::new();
+
+    while let Some((len, _)) = socket.recv_from(&mut buf).unwrap() {
+        received.push(buf[..len].to_vec());
+    }
+
+    received
+}
+
+#[test]
+fn rng_is_seeded() {
+    let mut a = Rng::new(7);
+    let mut b = Rng::new(7);
+    let mut c = Rng::new(8);
+
+    let a: Vec<_> = (0..100).map(|_| a.next_u64()).collect();
+    let b: Vec<_> = (0..100).map(|_| b.next_u64()).collect();
+    let c: Vec<_> = (0..100).map(|_| c.next_u64()).collect();
+
+    assert_eq!(a, b);
+    assert_ne!(a, c);
+
+    let mut rng = Rng::new(0);
+    let hits = (0..10_000).filter(|_| rng.chance(0.2)).count();
+
+    assert!((1_800..2_200).contains(&hits), "{} hits", hits);
+    assert!((0..1_000).all(|_| (0.0..1.0).contains(&rng.next_f64())));
+}
+
+#[test]
+fn latency_and_jitter() {
+    let mut conditions = Conditions::new();
+    conditions.set_latency(Duration::from_millis(50));
+    conditions.set_jitter(Duration::from_millis(10));
+
+    let network = Network::new(conditions, 1);
+    let mut a = network.socket(addr(1));
+    let mut b = network.socket(addr(2));
+
+    for i in 0..10 {
+        a.send_to(&[i], addr(2)).unwrap();
+    }
+
+    network.advance(Duration::from_millis(49));
+    assert!(receive_all(&mut b).is_empty());
+
+    network.advance(Duration::from_millis(11));
+    assert_eq!(receive_all(&mut b).len(), 10);
+    assert_eq!(network.in_flight(), 0);
+}
+
+#[test]
+fn loss() {
+    let mut conditions = Conditions::new();
+    conditions.set_loss(1.0);
+
+    let network = Network::new(conditions, 1);
+    let mut a = network.socket(addr(1));
+    let mut b = network.socket(addr(2));
+
+    a.send_to(&[0], addr(2)).unwrap();
+    assert!(receive_all(&mut b).is_empty());
+
+    let mut conditions = network.conditions();
+    conditions.set_loss(0.2);
+    network.set_conditions(conditions);
+
+    for i in 0..100 {
+        a.send_to(&[i], addr(2)).unwrap();
+    }
+
+    let received = receive_all(&mut b).len();
+    assert!((65..95).contains(&received), "{} received", received);
+}
+
+#[test]
+fn duplicate() {
+    let mut conditions = Conditions::new();
+    conditions.set_duplicate(1.0);
+
+    let network = Network::new(conditions, 1);
+    let mut a = network.socket(addr(1));
+    let mut b = network.socket(addr(2));
+
+    a.send_to(&[1], addr(2)).unwrap();
+    a.send_to(&[2], addr(2)).unwrap();
+
+    assert_eq!(receive_all(&mut b), vec![vec![1], vec![1], vec![2], vec![2]]);
+}
+
+#[test]
+fn reorder() {
+    let mut conditions = Conditions::new();
+    conditions.set_latency(Duration::from_millis(10));
+    conditions.set_reorder(0.5);
+
+    let network = Network::new(conditions, 3);
+    let mut a = network.socket(addr(1));
+    let mut b = network.socket(addr(2));
+
+    for i in 0..20 {
+        a.send_to(&[i], addr(2)).unwrap();
+    }
+
+    network.advance(Duration::from_millis(100));
+    let received: Vec<u8> = receive_all(&mut b).into_iter().map(|bytes| bytes[0]).collect();
+
+    let mut sorted = received.clone();
+    sorted.sort_unstable();
+
+    assert_eq!(sorted, (0..20).collect::<Vec<_>>());
+    assert_ne!(received, sorted);
+}
+
+/// A socket that can't send to one address, and can be made to fail after
+/// receiving some number of datagrams.
+struct Faulty {
+    inner: SimSocket,
+    unreachable: SocketAddr,
+    receives: Rc<Cell<Option<usize>>>,
+}
+
+impl Socket for Faulty {
+    fn send_to(&mut self, buf: &[u8], addr: SocketAddr) -> io::Result<()> {
+        if addr == self.unreachable {
+            return Err(io::ErrorKind::ConnectionRefused.into());
+        }
+
+        self.inner.send_to(buf, addr)
+    }
+
+    fn recv_from(&mut self, buf: &mut [u8]) -> io::Result<Option<(usize, SocketAddr)>> {
+        if self.receives.get() == Some(0) {
+            return Err(io::Error::other("the socket broke"));
+        }
+
+        let received = self.inner.recv_from(buf)?;
+
+        if received.is_some() {
+            self.receives.set(self.receives.get().map(|n| n - 1));
+        }
+
+        Ok(received)
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+#[test]
+fn socket_errors() {
+    let network = Network::new(Conditions::new(), 1);
+    let receives = Rc::new(Cell::new(None));
+    let socket = Faulty {
+        inner: network.socket(addr(1)),
+        unreachable: addr(2),
+        receives: receives.clone(),
+    };
+
+    let mut server = Endpoint::new(socket, Config::new());
+    server.set_accept(true);
+
+    let mut unreachable = Endpoint::new(network.socket(addr(2)), Config::new());
+    let mut client = Endpoint::new(network.socket(addr(3)), Config::new());
+    unreachable.connect(addr(1), network.now()).unwrap();
+    client.connect(addr(1), network.now()).unwrap();
+
+    // not being able to answer one client doesn't stop the server answering
+    // the other
+    let mut events = Vec::new();
+    server.poll(network.now(), &mut events).unwrap();
+    assert_eq!(events, [Event::Connected(addr(2)), Event::Connected(addr(3))]);
+
+    let mut events = Vec::new();
+    unreachable.poll(network.now(), &mut events).unwrap();
+    client.poll(network.now(), &mut events).unwrap();
+    assert_eq!(events, [Event::Connected(addr(1))]);
+
+    // the socket breaks after the first message, which is still returned
+    client.send(addr(1), Channel::Reliable, b"one", network.now()).unwrap();
+    client.send(addr(1), Channel::Reliable, b"two", network.now()).unwrap();
+    receives.set(Some(1));
+
+    let mut events = Vec::new();
+    assert!(matches!(server.poll(network.now(), &mut events), Err(Error::Io(_))));
+    assert_eq!(events, [Event::Message(addr(3), Channel::Reliable, b"one".to_vec())]);
+
+    // the ack for it is still sent
+    client.poll(network.now(), &mut events).unwrap();
+    assert_eq!(client.connection(addr(1)).unwrap().unacked(), 1);
+
+    // and nothing is lost once it works again
+    receives.set(None);
+
+    let mut events = Vec::new();
+    server.poll(network.now(), &mut events).unwrap();
+    assert_eq!(events, [Event::Message(addr(3), Channel::Reliable, b"two".to_vec())]);
+
+    client.poll(network.now(), &mut events).unwrap();
+    assert_eq!(client.connection(addr(1)).unwrap().unacked(), 0);
+}
+
+#[test]
+fn max_connections() {
+    let network = Network::new(Conditions::new(), 1);
+
+    let mut config = Config::new();
+    config.set_max_connections(2);
+
+    let mut server = Endpoint::new(network.socket(addr(1)), config);
+    server.set_accept(true);
+
+    let mut clients: Vec<_> = (2..5).map(|i| Endpoint::new(network.socket(addr(i)), Config::new())).collect();
+
+    for client in &mut clients {
+        client.connect(addr(1), network.now()).unwrap();
+    }
+
+    let mut events = Vec::new();
+    server.poll(network.now(), &mut events).unwrap();
+    assert_eq!(events, [Event::Connected(addr(2)), Event::Connected(addr(3))]);
+    assert_eq!(server.connections().count(), 2);
+
+    // once someone leaves, there's room again
+    server.disconnect(addr(2), network.now()).unwrap();
+    network.advance(Duration::from_secs(1));
+    clients[2].poll(network.now(), &mut Vec::new()).unwrap();
+
+    let mut events = Vec::new();
+    server.poll(network.now(), &mut events).unwrap();
+    assert_eq!(events, [Event::Connected(addr(4))]);
+}
+
+/// Encode a reliable datagram by hand, so fragments can be sent out of order.
+fn reliable(id: u16, payload: &[u8]) -> Vec<u8> {
+    fragment(id, 0, 1, payload)
+}
+
+/// A reliable datagram with a fragment of a message, encoded by hand.
+fn fragment(id: u16, fragment: u8, fragments: u8, payload: &[u8]) -> Vec<u8> {
+    let mut cursor = CursorMut::new();
+    cursor.encode(&(3u8, id, fragment, fragments)).unwrap();
+    cursor.encode(payload).unwrap();
+    cursor.into()
+}
+
+/// The ids of the reliable datagrams that were acked.
+fn acks(socket: &mut SimSocket) -> Vec<u16> {
+    let mut buf = [0; 64];
+    let mut acks = Vec::new();
+
+    while let Some((len, _)) = socket.recv_from(&mut buf).unwrap() {
+        if len == 7 && buf[0] == 4 {
+            acks.push(u16::from_le_bytes([buf[1], buf[2]]));
+        }
+    }
+
+    acks
+}
+
+#[test]
+fn max_reassembly() {
+    let network = Network::new(Conditions::new(), 1);
+
+    let mut config = Config::new();
+    config.set_max_reassembly(4000);
+
+    let mut server = Endpoint::new(network.socket(addr(1)), config);
+    server.set_accept(true);
+
+    let mut client = network.socket(addr(2));
+    client.send_to(&[0], addr(1)).unwrap();
+    server.poll(network.now(), &mut Vec::new()).unwrap();
+
+    // everything but the first message, which only four of fit
+    for id in 1..10 {
+        client.send_to(&reliable(id, &[id as u8; 1000]), addr(1)).unwrap();
+    }
+
+    let mut events = Vec::new();
+    server.poll(network.now(), &mut events).unwrap();
+    assert!(events.is_empty());
+    assert_eq!(acks(&mut client), [1, 2, 3, 4]);
+
+    // the first message lets the others through, and makes room for more
+    client.send_to(&reliable(0, &[0; 1000]), addr(1)).unwrap();
+    client.send_to(&reliable(5, &[5; 1000]), addr(1)).unwrap();
+    server.poll(network.now(), &mut events).unwrap();
+
+    let messages: Vec<_> = (0..6).map(|id| Event::Message(addr(2), Channel::Reliable, vec![id; 1000])).collect();
+    assert_eq!(events, messages);
+    assert_eq!(acks(&mut client), [0, 5]);
+
+    // a message that can never fit isn't received, even in order
+    let big = [fragment(6, 0, 5, &[6; 1000]), fragment(7, 1, 5, &[7; 1000]), fragment(8, 2, 5, &[8; 1000])];
+
+    for bytes in big.iter() {
+        client.send_to(bytes, addr(1)).unwrap();
+    }
+
+    let mut events = Vec::new();
+    server.poll(network.now(), &mut events).unwrap();
+    assert!(events.is_empty());
+    assert_eq!(acks(&mut client), [6, 7, 8]);
+
+    client.send_to(&fragment(9, 3, 5, &[9; 1000]), addr(1)).unwrap();
+    client.send_to(&fragment(10, 4, 5, &[10; 1000]), addr(1)).unwrap();
+    server.poll(network.now(), &mut events).unwrap();
+    assert!(events.is_empty());
+    assert_eq!(acks(&mut client), [9]);
+}
+
+#[test]
+fn bad_fragments() {
+    let network = Network::new(Conditions::new(), 1);
+
+    let mut server = Endpoint::new(network.socket(addr(1)), Config::new());
+    server.set_accept(true);
+
+    let mut client = network.socket(addr(2));
+    client.send_to(&[0], addr(1)).unwrap();
+    server.poll(network.now(), &mut Vec::new()).unwrap();
+
+    // fragments of messages with no fragments, which would count the
+    // fragments received past what fits in a `u8` if they were kept
+    for i in 0..=255 {
+        client.send_to(&fragment(i as u16, i, 0, b"bad"), addr(1)).unwrap();
+    }
+
+    // and a fragment past the end of its message
+    client.send_to(&fragment(0, 1, 1, b"bad"), addr(1)).unwrap();
+
+    let mut events = Vec::new();
+    server.poll(network.now(), &mut events).unwrap();
+    assert!(events.is_empty());
+    assert_eq!(acks(&mut client), []);
+
+    // none of them took up an id
+    client.send_to(&reliable(0, b"good"), addr(1)).unwrap();
+    server.poll(network.now(), &mut events).unwrap();
+    assert_eq!(events, [Event::Message(addr(2), Channel::Reliable, b"good".to_vec())]);
+    assert_eq!(acks(&mut client), [0]);
+}
+
+/// What happened on a simulated game, and when.
+type Log = Vec<(u32, SocketAddr, Event)>;
+
+const CHATS: usize = 20;
+
+/// Connect three clients to a server, do the handshake, and chat, on a
+/// network that loses 20% of its datagrams.
+fn game(seed: u64) -> Log {
+    let mut conditions = Conditions::new();
+    conditions.set_latency(Duration::from_millis(40));
+    conditions.set_jitter(Duration::from_millis(20));
+    conditions.set_loss(0.2);
+    conditions.set_duplicate(0.05);
+    conditions.set_reorder(0.05);
+
+    let network = Network::new(conditions, seed);
+    let mut server = Endpoint::new(network.socket(addr(1)), Config::new());
+    server.set_accept(true);
+
+    let mut clients: Vec<_> = (2..5).map(|i| Endpoint::new(network.socket(addr(i)), Config::new())).collect();
+
+    for (i, client) in clients.iter_mut().enumerate() {
+        client.connect(addr(1), network.now()).unwrap();
+
+        let hello = Packet::Hello(Hello::new(format!("player {}", i)));
+        client.send(addr(1), Channel::Reliable, &hello.to_bytes().unwrap(), network.now()).unwrap();
+    }
+
+    let mut log = Log::new();
+    let mut players = BTreeMap::new();
+    let mut chats: BTreeMap<SocketAddr, Vec<String>> = BTreeMap::new();
+    let mut welcomed = 0;
+
+    for tick in 0.. {
+        assert!(tick < 6_000, "the game didn't finish in a minute");
+
+        network.advance(Duration::from_millis(10));
+        let now = network.now();
+
+        let mut events = Vec::new();
+        server.poll(now, &mut events).unwrap();
+
+        for event in events {
+            log.push((tick, addr(1), event.clone()));
+
+            if let Event::Message(from, Channel::Reliable, bytes) = event {
+                match players.get(&from) {
+                    None => {
+                        let hello = protocol::accept(&bytes).unwrap();
+                        let player = PlayerId(players.len() as u8);
+                        players.insert(from, player);
+
+                        let welcome = Packet::Welcome(Welcome::new(player));
+                        server.send(from, Channel::Reliable, &welcome.to_bytes().unwrap(), now).unwrap();
+
+                        let join = Packet::Message(Message::Join { player, name: hello.name().to_string(), color: player.0 });
+                        for &other in players.keys().filter(|&&other| other != from) {
+                            server.send(other, Channel::Reliable, &join.to_bytes().unwrap(), now).unwrap();
+                        }
+                    }
+                    Some(&player) => match Packet::from_bytes(&bytes).unwrap() {
+                        Packet::Message(Message::Chat { player: sender, text }) => {
+                            assert_eq!(sender, player);
+                            chats.entry(from).or_default().push(text);
+                        }
+                        packet => panic!("unexpected packet: {:?}", packet),
+                    },
+                }
+            }
+        }
+
+        for client in &mut clients {
+            let local = client.local_addr().unwrap();
+
+            let mut events = Vec::new();
+            client.poll(now, &mut events).unwrap();
+
+            for event in events {
+                log.push((tick, local, event.clone()));
+
+                if let Event::Message(_, Channel::Reliable, bytes) = event {
+                    if let Packet::Welcome(welcome) = Packet::from_bytes(&bytes).unwrap() {
+                        welcomed += 1;
+
+                        let player = welcome.player();
+                        for i in 0..CHATS {
+                            let chat = Packet::Message(Message::Chat { player, text: format!("chat {}", i) });
+                            client.send(addr(1), Channel::Reliable, &chat.to_bytes().unwrap(), now).unwrap();
+                        }
+                    }
+                }
+            }
+
+            // movement is sent all the time, and it doesn't matter if some of
+            // it is lost
+            let movement = Packet::Message(Message::Movement {
+                player: PlayerId(0),
+                sequence: tick as u16,
+                position: Vector2::new(0.0, 0.0),
+                velocity: Vector2::new(1.0, 0.0),
+            });
+            client.send(addr(1), Channel::Unreliable, &movement.to_bytes().unwrap(), now).unwrap();
+        }
+
+        if welcomed == clients.len() && chats.values().map(Vec::len).sum::<usize>() == CHATS * clients.len() {
+            break;
+        }
+    }
+
+    assert_eq!(players.len(), 3);
+    for chats in chats.values() {
+        let expected: Vec<_> = (0..CHATS).map(|i| format!("chat {}", i)).collect();
+        assert_eq!(chats, &expected);
+    }
+
+    log
+}
+
+#[test]
+fn game_under_loss() {
+    let log = game(1);
+
+    // every client connects exactly once, and nobody is disconnected
+    let connected = log.iter().filter(|(_, _, e)| matches!(e, Event::Connected(_))).count();
+    assert_eq!(connected, 6);
+    assert!(!log.iter().any(|(_, _, e)| matches!(e, Event::Disconnected(..))));
+
+    // some movement is lost, but most of it arrives
+    let movements = log.iter().filter(|(_, _, e)| matches!(e, Event::Message(_, Channel::Unreliable, _))).count();
+    let ticks = log.last().unwrap().0 as usize;
+    assert!(movements < ticks * 3);
+    assert!(movements > ticks * 3 / 2);
+}
+
+#[test]
+fn deterministic() {
+    assert_eq!(game(1), game(1));
+    assert_ne!(game(1), game(2));
+}
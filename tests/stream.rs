@@ -0,0 +1,130 @@
+//! Tests for encoding and decoding over `io::Write` and `io::Read`.
+
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use among_us::math::*;
+use among_us::net::binary::decode::{Cursor, Decode, ErrorKind};
+use among_us::net::binary::encode::{self, CursorMut, Encode};
+
+use proptest::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+enum Event {
+    Join { name: String },
+    Move(u8, Vector2),
+    Leave(u8),
+}
+
+/// A reader that only gives out one byte at a time.
+struct Trickle<R>(R);
+
+impl<R> Read for Trickle<R>
+where R: Read {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
+
+/// A writer that fails after `0` bytes.
+struct Full(usize);
+
+impl Write for Full {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.0 == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+
+        let len = buf.len().min(self.0);
+        self.0 -= len;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn event() -> impl Strategy<Value = Event> {
+    prop_oneof![
+        any::<String>().prop_map(|name| Event::Join { name }),
+        (any::<u8>(), -1e6..1e6, -1e6..1e6).prop_map(|(id, x, y)| Event::Move(id, Vector2::new(x, y))),
+        any::<u8>().prop_map(Event::Leave),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn streams_match_buffers(events in prop::collection::vec(event(), 0..16)) {
+        let mut buffer = CursorMut::new();
+        let mut stream = CursorMut::from_writer(Vec::new());
+
+        for event in events.iter() {
+            prop_assert!(buffer.encode(event).is_ok());
+            prop_assert!(stream.encode(event).is_ok());
+        }
+
+        let bytes: Vec<u8> = buffer.into();
+        prop_assert_eq!(stream.len(), bytes.len());
+        prop_assert_eq!(&stream.into_inner(), &bytes);
+
+        let mut cursor = Cursor::from_reader(Trickle(&bytes[..]));
+
+        for event in events {
+            prop_assert_eq!(cursor.decode::<Event>().ok(), Some(event));
+        }
+
+        prop_assert_eq!(cursor.position(), bytes.len());
+    }
+}
+
+#[test]
+fn events_go_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let events = vec![
+        Event::Join { name: String::from("red") },
+        Event::Move(0, Vector2::new(1.0, -2.5)),
+        Event::Leave(0),
+    ];
+
+    let sent = events.clone();
+    let client = thread::spawn(move || {
+        let mut cursor = CursorMut::from_writer(TcpStream::connect(address).unwrap());
+
+        for event in sent.iter() {
+            cursor.encode(event).unwrap();
+        }
+
+        cursor.flush().unwrap();
+    });
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut cursor = Cursor::from_reader(stream);
+
+    for event in events {
+        assert_eq!(cursor.decode::<Event>().unwrap(), event);
+        cursor.reset_allocated();
+    }
+
+    client.join().unwrap();
+
+    // the client hung up, so there is nothing left
+    let error = cursor.decode::<Event>().unwrap_err();
+    assert_eq!(*error.kind(), ErrorKind::UnexpectedEnd { expected: 1, available: 0 });
+}
+
+#[test]
+fn io_errors_are_reported() {
+    let mut cursor = CursorMut::from_writer(Full(3));
+    let error = cursor.encode(&(1u16, 2u16)).unwrap_err();
+
+    assert_eq!(*error.kind(), encode::ErrorKind::Io(io::ErrorKind::WriteZero));
+    assert_eq!(error.offset(), Some(2));
+
+    let error = Cursor::from_reader(Trickle(&[3u8, b'a', b'b'][..])).decode::<String>().unwrap_err();
+    assert_eq!(*error.kind(), ErrorKind::UnexpectedEnd { expected: 3, available: 2 });
+}
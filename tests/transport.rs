@@ -0,0 +1,226 @@
+//! Tests for `net::transport` over localhost.
+
+use std::net::{SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use among_us::net::transport::*;
+
+fn socket() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_nonblocking(true).unwrap();
+    socket
+}
+
+fn config() -> Config {
+    let mut config = Config::new();
+    config.set_keepalive(Duration::from_millis(50));
+    config.set_timeout(Duration::from_millis(500));
+    config.set_min_resend(Duration::from_millis(5));
+    config.set_max_resend(Duration::from_millis(50));
+    config
+}
+
+/// Poll both endpoints until `done` returns true, collecting their events.
+fn run<S, F>(server: &mut Endpoint<S>, client: &mut Endpoint<S>, mut done: F) -> (Vec<Event>, Vec<Event>)
+where
+    S: Socket,
+    F: FnMut(&[Event], &[Event]) -> bool,
+{
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let (mut server_events, mut client_events) = (Vec::new(), Vec::new());
+
+    while !done(&server_events, &client_events) {
+        assert!(Instant::now() < deadline, "timed out");
+
+        server.poll(Instant::now(), &mut server_events).unwrap();
+        client.poll(Instant::now(), &mut client_events).unwrap();
+        thread::sleep(Duration::from_millis(1));
+    }
+
+    (server_events, client_events)
+}
+
+fn messages(events: &[Event], channel: Channel) -> Vec<Vec<u8>> {
+    events
+        .iter()
+        .filter_map(|e| match e {
+            Event::Message(_, c, payload) if *c == channel => Some(payload.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn connected<S>(server: &mut Endpoint<S>, client: &mut Endpoint<S>) -> SocketAddr
+where S: Socket {
+    let server_addr = server.local_addr().unwrap();
+    client.connect(server_addr, Instant::now()).unwrap();
+
+    let (server_events, client_events) = run(server, client, |s, c| !s.is_empty() && !c.is_empty());
+
+    assert_eq!(client_events, vec![Event::Connected(server_addr)]);
+    match server_events[..] {
+        [Event::Connected(addr)] => addr,
+        _ => panic!("unexpected events: {:?}", server_events),
+    }
+}
+
+#[test]
+fn connect_and_send() {
+    let mut server = Endpoint::new(socket(), config());
+    let mut client = Endpoint::new(socket(), config());
+    server.set_accept(true);
+
+    let client_addr = connected(&mut server, &mut client);
+    let server_addr = server.local_addr().unwrap();
+    assert_eq!(client_addr, client.local_addr().unwrap());
+
+    client.send(server_addr, Channel::Reliable, b"hello", Instant::now()).unwrap();
+    client.send(server_addr, Channel::Unreliable, b"moving", Instant::now()).unwrap();
+    server.send(client_addr, Channel::Reliable, b"welcome", Instant::now()).unwrap();
+
+    let (server_events, client_events) = run(&mut server, &mut client, |s, c| s.len() == 2 && !c.is_empty());
+
+    assert_eq!(messages(&server_events, Channel::Reliable), vec![b"hello".to_vec()]);
+    assert_eq!(messages(&server_events, Channel::Unreliable), vec![b"moving".to_vec()]);
+    assert_eq!(messages(&client_events, Channel::Reliable), vec![b"welcome".to_vec()]);
+    assert!(client.connection(server_addr).unwrap().rtt().is_some());
+}
+
+#[test]
+fn not_accepting() {
+    let mut server = Endpoint::new(socket(), config());
+    let mut client = Endpoint::new(socket(), config());
+    let server_addr = server.local_addr().unwrap();
+
+    client.connect(server_addr, Instant::now()).unwrap();
+    let (server_events, client_events) = run(&mut server, &mut client, |_, c| !c.is_empty());
+
+    assert!(server_events.is_empty());
+    assert_eq!(client_events, vec![Event::Disconnected(server_addr, DisconnectReason::Timeout)]);
+    assert!(matches!(
+        client.send(server_addr, Channel::Reliable, b"hello", Instant::now()),
+        Err(Error::NotConnected(_))
+    ));
+}
+
+#[test]
+fn reliable_under_loss() {
+    let mut server = Endpoint::new(Lossy::new(socket(), 0.2, 1), config());
+    let mut client = Endpoint::new(Lossy::new(socket(), 0.2, 2), config());
+    server.set_accept(true);
+
+    connected(&mut server, &mut client);
+    let server_addr = server.local_addr().unwrap();
+
+    // a few big messages in between the small ones have to be fragmented
+    let sent: Vec<Vec<u8>> = (0..200u32)
+        .map(|i| match i % 50 {
+            0 => (0..10_000).map(|j| (i + j) as u8).collect(),
+            _ => i.to_le_bytes().to_vec(),
+        })
+        .collect();
+
+    for payload in &sent {
+        client.send(server_addr, Channel::Reliable, payload, Instant::now()).unwrap();
+    }
+
+    let (server_events, _) = run(&mut server, &mut client, |s, _| s.len() == sent.len());
+
+    assert_eq!(messages(&server_events, Channel::Reliable), sent);
+
+    // the last acks can still be on their way
+    let deadline = Instant::now() + Duration::from_secs(10);
+
+    while client.connection(server_addr).unwrap().unacked() > 0 {
+        assert!(Instant::now() < deadline, "timed out");
+
+        let mut events = Vec::new();
+        server.poll(Instant::now(), &mut events).unwrap();
+        client.poll(Instant::now(), &mut events).unwrap();
+        assert!(events.is_empty());
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn too_long() {
+    let mut server = Endpoint::new(socket(), config());
+    let mut client = Endpoint::new(socket(), config());
+    server.set_accept(true);
+
+    connected(&mut server, &mut client);
+    let server_addr = server.local_addr().unwrap();
+    let mtu = client.config().mtu();
+
+    assert!(matches!(
+        client.send(server_addr, Channel::Unreliable, &vec![0; mtu], Instant::now()),
+        Err(Error::TooLong { .. })
+    ));
+    assert!(matches!(
+        client.send(server_addr, Channel::Reliable, &vec![0; mtu * 256], Instant::now()),
+        Err(Error::TooLong { .. })
+    ));
+}
+
+#[test]
+fn keepalive() {
+    let mut server = Endpoint::new(socket(), config());
+    let mut client = Endpoint::new(socket(), config());
+    server.set_accept(true);
+
+    connected(&mut server, &mut client);
+
+    // nothing is sent for a few timeouts, but pings keep both sides alive
+    let start = Instant::now();
+    let (server_events, client_events) = run(&mut server, &mut client, |_, _| start.elapsed() > Duration::from_millis(1500));
+
+    assert!(server_events.is_empty());
+    assert!(client_events.is_empty());
+}
+
+#[test]
+fn disconnect() {
+    let mut server = Endpoint::new(socket(), config());
+    let mut client = Endpoint::new(socket(), config());
+    server.set_accept(true);
+
+    let client_addr = connected(&mut server, &mut client);
+    let server_addr = server.local_addr().unwrap();
+
+    client.disconnect(server_addr, Instant::now()).unwrap();
+    let (server_events, _) = run(&mut server, &mut client, |s, _| !s.is_empty());
+
+    assert_eq!(server_events, vec![Event::Disconnected(client_addr, DisconnectReason::Closed)]);
+    assert!(server.connection(client_addr).is_none());
+}
+
+#[test]
+fn timeout() {
+    let mut server = Endpoint::new(socket(), config());
+    let mut client = Endpoint::new(socket(), config());
+    server.set_accept(true);
+
+    // the server last heard from the client after this
+    let start = Instant::now();
+    let client_addr = connected(&mut server, &mut client);
+
+    // the client is gone without saying so
+    drop(client);
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let events = loop {
+        let mut events = Vec::new();
+        server.poll(Instant::now(), &mut events).unwrap();
+
+        if !events.is_empty() {
+            break events;
+        }
+
+        assert!(Instant::now() < deadline, "timed out");
+        thread::sleep(Duration::from_millis(1));
+    };
+
+    assert_eq!(events, vec![Event::Disconnected(client_addr, DisconnectReason::Timeout)]);
+    assert!(start.elapsed() >= server.config().timeout());
+}
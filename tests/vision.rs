@@ -0,0 +1,56 @@
+//! Tests for `game::vision`.
+
+use among_us::collide::{Circle, Polygon};
+use among_us::game::vision::Vision;
+use among_us::math::*;
+
+fn rect(min: Vector2, max: Vector2) -> Polygon {
+    vec![min, Vector2::new(max.x, min.y), max, Vector2::new(min.x, max.y)].into()
+}
+
+#[test]
+fn open_space() {
+    let eye = Vector2::new(1.0, 2.0);
+    let vision = Vision::with_resolution(eye, 5.0, &[], 32);
+
+    assert_eq!(vision.points().len(), 32);
+    assert!(vision.points().iter().all(|point| ((point - eye).magnitude() - 5.0).abs() < 1e-9));
+
+    assert!(vision.contains(Vector2::new(4.0, 2.0)));
+    assert!(!vision.contains(Vector2::new(7.0, 2.0)));
+    assert_eq!(vision.triangles().count(), 32);
+}
+
+#[test]
+fn walls_cast_shadows() {
+    let eye = Vector2::new(0.0, 0.0);
+    let walls = [rect(Vector2::new(2.0, -1.0), Vector2::new(3.0, 1.0))];
+    let vision = Vision::new(eye, 10.0, &walls);
+
+    // in front of the wall, and off to the side of it
+    assert!(vision.contains(Vector2::new(1.5, 0.0)));
+    assert!(vision.contains(Vector2::new(5.0, 4.0)));
+
+    // right behind it
+    assert!(!vision.contains(Vector2::new(5.0, 0.0)));
+    assert!(!vision.contains(Vector2::new(8.0, 1.5)));
+
+    // nothing is seen through the wall
+    assert!(vision.points().iter().all(|point| point.x <= 2.0 + 1e-9 || point.y.abs() >= point.x / 2.0 - 1e-6));
+}
+
+#[test]
+fn sees_circles() {
+    let eye = Vector2::new(0.0, 0.0);
+    let walls = [rect(Vector2::new(2.0, -1.0), Vector2::new(3.0, 1.0))];
+    let vision = Vision::new(eye, 10.0, &walls);
+
+    // a player hiding behind the wall, and one poking out of its shadow
+    assert!(!vision.sees(&Circle::new(Vector2::new(6.0, 0.0), 0.35)));
+    assert!(!vision.contains(Vector2::new(6.0, 2.8)));
+    assert!(vision.sees(&Circle::new(Vector2::new(6.0, 2.8), 0.35)));
+
+    // and one just past the edge of the vision radius
+    assert!(vision.sees(&Circle::new(Vector2::new(0.0, -10.2), 0.35)));
+    assert!(!vision.sees(&Circle::new(Vector2::new(0.0, -11.0), 0.35)));
+}